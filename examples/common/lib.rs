@@ -44,7 +44,7 @@ type Back = backend::Backend;
 
 use std::{
     borrow::{Borrow, Cow}, cell::UnsafeCell, collections::HashMap, sync::Arc,
-    iter::{once, empty},
+    iter::{once, empty}, marker::PhantomData,
     time::{Duration, Instant},
     ops::{Index, Range},
     mem::{size_of, zeroed},
@@ -59,7 +59,7 @@ use glsl_layout::*;
 
 use hal::{
     buffer, command::{
-        ClearValue, ClearColor, ClearDepthStencil, CommandBuffer, Primary, RenderPassInlineEncoder,
+        ClearValue, ClearColor, ClearDepthStencil, Primary, RenderPassInlineEncoder,
         DescriptorSetOffset, BufferImageCopy,
     },
     device::WaitFor,
@@ -86,74 +86,6 @@ use winit::{EventsLoop, WindowBuilder};
 use xfg::render::*;
 use xfg::*;
 
-/// Descriptor pool that caches set allocations.
-#[derive(Debug)]
-pub struct XfgDescriptorPool<B: Backend> {
-    pools: Vec<B::DescriptorPool>,
-    sets: Vec<B::DescriptorSet>,
-}
-
-impl<B> XfgDescriptorPool<B>
-where
-    B: Backend,
-{
-    /// Create empty pool.
-    pub fn new() -> Self {
-        XfgDescriptorPool {
-            pools: Vec::new(),
-            sets: Vec::new(),
-        }
-    }
-
-    /// Allocate descriptor set for the render pass.
-    pub fn allocate<D>(
-        &mut self,
-        device: &mut D,
-        layout: &B::DescriptorSetLayout,
-        bindings: &[DescriptorSetLayoutBinding]
-    ) -> B::DescriptorSet
-    where
-        D: Device<B>,
-    {
-        let ref mut pools = self.pools;
-        let exp = pools.len() as u32;
-
-        self.sets.pop().unwrap_or_else(|| {
-            pools
-                .last_mut()
-                .and_then(|pool| match pool.allocate_set(layout) {
-                    Ok(set) => Some(set),
-                    Err(AllocationError::OutOfPoolMemory) | Err(AllocationError::FragmentedPool) => None,
-                    Err(AllocationError::IncompatibleLayout) => unreachable!(),
-                    Err(err) => panic!("Unhandled error in XfgDescriptorPool: {}", err),
-                })
-                .unwrap_or_else(|| {
-                    let multiplier = 2usize.pow(exp);
-                    let mut pool = device.create_descriptor_pool(
-                        multiplier,
-                        bindings_to_range(multiplier, bindings),
-                    );
-
-                    let set = match pool.allocate_set(layout) {
-                        Ok(set) => set,
-                        Err(AllocationError::OutOfPoolMemory)
-                        | Err(AllocationError::FragmentedPool)
-                        | Err(AllocationError::IncompatibleLayout) => unreachable!(),
-                        Err(err) => panic!("Unhandled error in XfgDescriptorPool: {}", err),
-                    };
-
-                    pools.push(pool);
-                    set
-                })
-        })
-    }
-
-    /// Free descriptor set.
-    pub fn free(&mut self, set: B::DescriptorSet) {
-        self.sets.push(set)
-    }
-}
-
 pub struct Cache<B: Backend> {
     pub uniforms: Vec<Buffer<B>>,
     pub set: B::DescriptorSet,
@@ -212,7 +144,11 @@ where
         factory: &mut Factory<B>,
         scene: &mut Scene<B, T, Y>,
     ) -> usize {
-        self.0.run(families, factory, scene, fences)
+        let status = self.0.run(families, factory, scene, fences);
+        for &(present, ref err) in &status.errors {
+            warn!("Present {} failed: {:?}", present, err);
+        }
+        status.fences
     }
 
     fn dispose(self, factory: &mut Factory<B>, scene: &mut Scene<B, T, Y>) -> Backbuffer<B> {
@@ -290,7 +226,7 @@ where
             &mut scene,
             |surface, families, factory, scene| -> Result<_, ::std::io::Error> {
                 profile!("render setup");
-                let (capabilites, formats, _) = factory.compatibility(&surface);
+                let (capabilites, formats, present_modes) = factory.compatibility(&surface);
                 let surface_format = formats.map_or(Format::Rgba8Srgb, |formats| {
                     info!("Surface formats: {:#?}", formats);
                     formats
@@ -323,12 +259,14 @@ where
                         families,
                         create_buffer,
                         create_image,
-                        Some(present::PresentBuilder::new(
-                            surface_id,
-                            surface_format,
-                            surface,
-                            capabilites,
-                        )),
+                        Some(
+                            present::PresentBuilder::new(
+                                surface_id,
+                                surface_format,
+                                surface,
+                                capabilites,
+                            ).with_present_modes(present_modes),
+                        ),
                         factory,
                         scene,
                     )
@@ -376,29 +314,6 @@ where
     // is_send_sync::<back::Device>();
 }
 
-fn bindings_to_range(
-    multiplier: usize,
-    bindings: &[DescriptorSetLayoutBinding],
-) -> Vec<DescriptorRangeDesc> {
-    let mut ranges = Vec::new();
-
-    for binding in bindings {
-        let index = binding.ty as usize;
-        while ranges.len() <= index {
-            ranges.push(DescriptorRangeDesc {
-                ty: DescriptorType::Sampler,
-                count: 0,
-            });
-        }
-
-        let ref mut range = ranges[index];
-        range.ty = binding.ty;
-        range.count += binding.count * multiplier;
-    }
-
-    ranges
-}
-
 fn create_image<B, T, Y>(
     kind: image::Kind,
     format: Format,
@@ -16,7 +16,7 @@ unsafe impl Pod for TrProjView {}
 
 #[derive(Debug)]
 struct DrawFlat<B: Backend> {
-    pool: XfgDescriptorPool<B>,
+    pd: PhantomData<B>,
 }
 
 impl<B> DrawFlat<B>
@@ -141,26 +141,19 @@ where
     }
 
     fn build<I>(_sampled: I, _storage: I, _device: &mut Factory<B>, _aux: &mut Scene<B>) -> Self {
-        DrawFlat {
-            pool: XfgDescriptorPool::new(),
-        }
+        DrawFlat { pd: PhantomData }
     }
 
-    fn prepare<A, S>(
+    fn prepare(
         &mut self,
-        sets: &A,
-        cbuf: &mut CommandBuffer<B, Graphics>,
+        sets: &mut Descriptors<B>,
+        _buffers: &[&B::Buffer],
+        cbuf: &mut PrepareBuffer<B>,
         factory: &mut Factory<B>,
         scene: &Scene<B>,
-    ) where
-        A: Index<usize>,
-        A::Output: Index<usize, Output = S>,
-        S: Borrow<B::DescriptorSetLayout>,
-    {
+    ) {
         use std::mem::size_of;
 
-        let set = sets[0][0].borrow();
-
         let view = scene.camera.transform.inverse_transform().unwrap();
 
         // Update uniform cache
@@ -182,7 +175,7 @@ where
                         Properties::DEVICE_LOCAL,
                     )
                     .unwrap();
-                let set = self.pool.allocate(factory, set, Self::bindings());
+                let set = sets.allocate(factory, 0, 0).unwrap();
                 factory.write_descriptor_sets(Some(DescriptorSetWrite {
                     set: &set,
                     binding: 0,
@@ -195,6 +188,7 @@ where
                 }
             });
 
+            cbuf.own_buffer(cache.uniforms[0].borrow());
             cbuf.update_buffer(cache.uniforms[0].borrow(), 0, cast_slice(&[trprojview]));
 
             cbuf.pipeline_barrier(
@@ -210,6 +204,8 @@ where
 
     fn draw<L, P>(
         &mut self,
+        _sets: &Descriptors<B>,
+        _buffers: &[&B::Buffer],
         layouts: &L,
         pipelines: &P,
         mut encoder: RenderPassInlineEncoder<B, Primary>,
@@ -240,10 +236,9 @@ where
         }
     }
 
-    fn dispose(mut self, factory: &mut Factory<B>, scene: &mut Scene<B>) {
+    fn dispose(self, factory: &mut Factory<B>, scene: &mut Scene<B>) {
         for object in &mut scene.objects {
             for cache in unsafe { &mut *object.cache.get() }.take() {
-                self.pool.free(cache.set);
                 for uniform in cache.uniforms {
                     factory.destroy_buffer(uniform);
                 }
@@ -0,0 +1,316 @@
+//! Render offscreen without window and save the result to `headless.ppm`.
+
+#![allow(unused_imports)]
+#![allow(dead_code)]
+#![deny(unused_must_use)]
+
+extern crate env_logger;
+extern crate gfx_hal as hal;
+#[macro_use]
+extern crate log;
+extern crate smallvec;
+extern crate xfg;
+
+#[cfg(feature = "dx12")]
+extern crate gfx_backend_dx12 as backend;
+#[cfg(not(any(feature = "vulkan", feature = "dx12", feature = "metal")))]
+extern crate gfx_backend_empty as backend;
+#[cfg(feature = "metal")]
+extern crate gfx_backend_metal as backend;
+#[cfg(feature = "vulkan")]
+extern crate gfx_backend_vulkan as backend;
+
+use std::{
+    borrow::Borrow, collections::HashMap, fs::File, io::Write, iter::{once, Empty},
+    marker::PhantomData,
+};
+
+use hal::{
+    adapter::MemoryType, buffer,
+    command::{ClearColorRaw, ClearDepthStencilRaw, CommandBufferFlags, RawCommandBuffer, RawLevel},
+    format::Format, image, memory::{Barrier, Dependencies, Properties},
+    pool::{CommandPool, CommandPoolCreateFlags, RawCommandPool}, pso::PipelineStage,
+    queue::{CommandQueue, General, Graphics, RawCommandQueue, RawSubmission}, Backend, Device,
+    Instance, PhysicalDevice,
+};
+
+use smallvec::SmallVec;
+
+use xfg::{readback::ReadbackBuilder, *};
+
+type Back = backend::Backend;
+
+/// Memory of images and buffers created for the graph.
+struct Aux<B: Backend> {
+    memory_types: Vec<MemoryType>,
+    memories: Vec<B::Memory>,
+}
+
+/// Clears the image with solid color.
+struct ClearNode<B: Backend> {
+    pool: B::CommandPool,
+    cbuf: B::CommandBuffer,
+}
+
+impl<B> NodeDesc for ClearNode<B>
+where
+    B: Backend,
+{
+    type Buffers = Empty<(buffer::Usage, buffer::State, PipelineStage)>;
+    type Images = Option<(image::Usage, image::State, PipelineStage)>;
+    type Capability = Graphics;
+
+    fn name() -> &'static str {
+        "Clear"
+    }
+
+    fn buffers() -> Self::Buffers {
+        Default::default()
+    }
+
+    fn images() -> Self::Images {
+        Some((
+            image::Usage::TRANSFER_DST,
+            (image::Access::TRANSFER_WRITE, image::Layout::TransferDstOptimal),
+            PipelineStage::TRANSFER,
+        ))
+    }
+}
+
+impl<B, D, T> Node<B, D, T> for ClearNode<B>
+where
+    B: Backend,
+    D: Device<B>,
+{
+    fn build<F, U, I>(
+        buffers: Vec<BufferInfo<U>>,
+        images: Vec<ImageInfo<I>>,
+        mut pools: F,
+        device: &mut D,
+        _aux: &mut T,
+    ) -> Self
+    where
+        F: FnMut(&mut D, CommandPoolCreateFlags) -> CommandPool<B, Graphics>,
+        U: Borrow<B::Buffer>,
+        I: Borrow<B::Image>,
+    {
+        let ref info = images[0];
+        let range = image::SubresourceRange {
+            aspects: info.format.surface_desc().aspects,
+            levels: 0..1,
+            layers: 0..1,
+        };
+
+        let mut pool = pools(device, CommandPoolCreateFlags::empty()).into_raw();
+        let mut cbuf = pool.allocate(1, RawLevel::Primary).pop().unwrap();
+        cbuf.begin(CommandBufferFlags::EMPTY, Default::default());
+        barriers::record_barriers::<B, _, _, _>(&mut cbuf, &buffers, &images, 0, false);
+        cbuf.clear_image(
+            info.image.borrow(),
+            image::Layout::TransferDstOptimal,
+            ClearColorRaw {
+                float32: [0.2, 0.4, 0.8, 1.0],
+            },
+            ClearDepthStencilRaw {
+                depth: 0.0,
+                stencil: 0,
+            },
+            Some(range),
+        );
+        barriers::record_barriers::<B, _, _, _>(&mut cbuf, &buffers, &images, 0, true);
+        cbuf.finish();
+
+        ClearNode { pool, cbuf }
+    }
+
+    fn run<'a, W, S>(
+        &'a mut self,
+        frame: &FrameInfo<B>,
+        wait: W,
+        queue: &mut CommandQueue<B, Graphics>,
+        signal: S,
+        fence: Option<&B::Fence>,
+        _device: &mut D,
+        _aux: &'a T,
+    ) where
+        W: IntoIterator<Item = (&'a B::Semaphore, PipelineStage)>,
+        S: IntoIterator<Item = &'a B::Semaphore>,
+    {
+        unsafe {
+            queue.as_raw_mut().submit_raw(
+                RawSubmission {
+                    wait_semaphores: &wait.into_iter().collect::<SmallVec<[_; 16]>>(),
+                    signal_semaphores: &signal.into_iter().collect::<SmallVec<[_; 16]>>(),
+                    cmd_buffers: frame.command_buffers(once(&self.cbuf)),
+                },
+                fence,
+            );
+        }
+    }
+
+    fn dispose(mut self, device: &mut D, _aux: &mut T) {
+        self.pool.free(vec![self.cbuf]);
+        device.destroy_command_pool(self.pool);
+    }
+}
+
+fn create_image<B>(
+    kind: image::Kind,
+    format: Format,
+    usage: image::Usage,
+    device: &mut B::Device,
+    aux: &mut Aux<B>,
+) -> B::Image
+where
+    B: Backend,
+{
+    let unbound = device
+        .create_image(
+            kind,
+            1,
+            format,
+            image::Tiling::Optimal,
+            usage,
+            image::StorageFlags::empty(),
+        )
+        .unwrap();
+    let requirements = device.get_image_requirements(&unbound);
+    let memory_type = aux
+        .memory_types
+        .iter()
+        .enumerate()
+        .find(|&(id, memory_type)| {
+            requirements.type_mask & (1u64 << id) != 0
+                && memory_type.properties.contains(Properties::DEVICE_LOCAL)
+        })
+        .map(|(id, _)| id)
+        .unwrap();
+    let memory = device
+        .allocate_memory(hal::MemoryTypeId(memory_type), requirements.size)
+        .unwrap();
+    let image = device.bind_image_memory(&memory, 0, unbound).unwrap();
+    aux.memories.push(memory);
+    image
+}
+
+fn create_buffer<B>(
+    size: u64,
+    usage: buffer::Usage,
+    device: &mut B::Device,
+    aux: &mut Aux<B>,
+) -> B::Buffer
+where
+    B: Backend,
+{
+    let unbound = device.create_buffer(size, usage).unwrap();
+    let requirements = device.get_buffer_requirements(&unbound);
+    let memory_type = aux
+        .memory_types
+        .iter()
+        .enumerate()
+        .find(|&(id, memory_type)| {
+            requirements.type_mask & (1u64 << id) != 0
+                && memory_type.properties.contains(Properties::DEVICE_LOCAL)
+        })
+        .map(|(id, _)| id)
+        .unwrap();
+    let memory = device
+        .allocate_memory(hal::MemoryTypeId(memory_type), requirements.size)
+        .unwrap();
+    let buffer = device.bind_buffer_memory(&memory, 0, unbound).unwrap();
+    aux.memories.push(memory);
+    buffer
+}
+
+#[cfg(not(any(feature = "dx12", feature = "metal", feature = "vulkan")))]
+fn main() {
+    env_logger::init();
+    error!("You need to enable the native API feature (vulkan/metal/dx12) in order to run example");
+}
+
+#[cfg(any(feature = "dx12", feature = "metal", feature = "vulkan"))]
+fn main() {
+    env_logger::init();
+
+    const WIDTH: u32 = 256;
+    const HEIGHT: u32 = 256;
+    const FRAMES: usize = 3;
+
+    let instance = backend::Instance::create("xfg-headless", 1);
+    let adapter = instance.enumerate_adapters().into_iter().next().unwrap();
+    let memory_types = adapter.physical_device.memory_properties().memory_types;
+    let (mut device, group) = adapter.open_with::<_, General>(1, |_| true).unwrap();
+    let family_id = group.family();
+    let family = adapter
+        .queue_families
+        .into_iter()
+        .find(|family| family.id() == family_id)
+        .unwrap();
+
+    let mut queues = HashMap::new();
+    queues.insert(
+        family_id,
+        group
+            .queues
+            .into_iter()
+            .map(|queue| unsafe {
+                ::std::mem::transmute::<_, <Back as Backend>::CommandQueue>(queue)
+            })
+            .collect::<Vec<_>>(),
+    );
+
+    let mut aux = Aux::<Back> {
+        memory_types: memory_types.clone(),
+        memories: Vec::new(),
+    };
+
+    let mut builder = GraphBuilder::new();
+    let target = builder.create_image(
+        image::Kind::D2(WIDTH, HEIGHT, 1, 1),
+        Format::Rgba8Unorm,
+        None,
+    );
+    let clear = builder.add_node(ClearNode::<Back>::builder().with_image(target));
+    let readback = builder.add_readback(
+        ReadbackBuilder::image(target, memory_types).with_dependency(clear),
+    );
+
+    let mut graph: Graph<Back, _, _, <Back as Backend>::Buffer, _> = builder.build_headless(
+        once(&family),
+        create_buffer::<Back>,
+        create_image::<Back>,
+        &mut device,
+        &mut aux,
+    );
+
+    let mut fences = Vec::new();
+    let mut readout = None;
+    for _ in 0..FRAMES {
+        let status = graph.run(&mut queues, &mut device, &mut aux, &mut fences);
+        assert!(status.is_ok());
+        for fence in &fences[..status.fences] {
+            device.wait_for_fence(fence, !0);
+        }
+        readout = graph.readback(readback, &mut device).or(readout);
+    }
+
+    let readout = readout.expect("Readback didn't complete");
+    info!("Read back frame {}", readout.frame);
+
+    let extent = readout.extent.unwrap();
+    let mut file = File::create("headless.ppm").unwrap();
+    write!(file, "P6\n{} {}\n255\n", extent.width, extent.height).unwrap();
+    for row in readout.data.chunks(readout.row_pitch) {
+        for texel in row[..extent.width as usize * 4].chunks(4) {
+            file.write_all(&texel[..3]).unwrap();
+        }
+    }
+
+    graph.dispose(&mut device, &mut aux);
+    for fence in fences {
+        device.destroy_fence(fence);
+    }
+    for memory in aux.memories {
+        device.free_memory(memory);
+    }
+}
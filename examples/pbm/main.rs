@@ -16,7 +16,7 @@ struct Material {
 
 #[derive(Debug)]
 struct DrawColorDepthNormal<B: Backend> {
-    pool: XfgDescriptorPool<B>,
+    pd: PhantomData<B>,
 }
 
 impl<B> DrawColorDepthNormal<B>
@@ -149,27 +149,20 @@ where
         _device: &mut Factory<B>,
         _aux: &mut Scene<B, Material>,
     ) -> Self {
-        DrawColorDepthNormal {
-            pool: XfgDescriptorPool::new(),
-        }
+        DrawColorDepthNormal { pd: PhantomData }
     }
 
     #[inline]
-    fn prepare<A, S>(
+    fn prepare(
         &mut self,
-        sets: &A,
-        cbuf: &mut CommandBuffer<B, Graphics>,
+        sets: &mut Descriptors<B>,
+        _buffers: &[&B::Buffer],
+        cbuf: &mut PrepareBuffer<B>,
         factory: &mut Factory<B>,
         scene: &Scene<B, Material>,
-    ) where
-        A: Index<usize>,
-        A::Output: Index<usize, Output = S>,
-        S: Borrow<B::DescriptorSetLayout>,
-    {
+    ) {
         use std::iter::once;
 
-        let set = sets[0][0].borrow();
-
         #[repr(C)]
         #[derive(Clone, Copy, Debug, PartialEq)]
         struct VertexArgs {
@@ -226,7 +219,7 @@ where
                         Properties::DEVICE_LOCAL,
                     )
                     .unwrap();
-                let set = self.pool.allocate(factory, set, Self::bindings());
+                let set = sets.allocate(factory, 0, 0).unwrap();
                 factory.write_descriptor_sets(
                     once(DescriptorSetWrite {
                         set: &set,
@@ -247,6 +240,7 @@ where
                     })),
                 );
 
+                cbuf.own_buffer(buffer.borrow());
                 cbuf.update_buffer(
                     buffer.borrow(),
                     vertex_args_range.start.unwrap(),
@@ -276,6 +270,8 @@ where
 
     fn draw<L, P>(
         &mut self,
+        _sets: &Descriptors<B>,
+        _buffers: &[&B::Buffer],
         layouts: &L,
         pipelines: &P,
         mut encoder: RenderPassInlineEncoder<B, Primary>,
@@ -308,10 +304,9 @@ where
         }
     }
 
-    fn dispose(mut self, factory: &mut Factory<B>, scene: &mut Scene<B, Material>) {
+    fn dispose(self, factory: &mut Factory<B>, scene: &mut Scene<B, Material>) {
         for object in &mut scene.objects {
             for cache in unsafe { &mut *object.cache.get() }.take() {
-                self.pool.free(cache.set);
                 for uniform in cache.uniforms {
                     factory.destroy_buffer(uniform);
                 }
@@ -322,7 +317,6 @@ where
 
 #[derive(Debug)]
 struct DrawLights<B: Backend> {
-    pool: XfgDescriptorPool<B>,
     input: [*const B::ImageView; 4],
 }
 
@@ -465,7 +459,6 @@ where
     {
         let mut inputs = storage.into_iter();
         DrawLights {
-            pool: XfgDescriptorPool::new(),
             input: [
                 inputs.next().unwrap().borrow(),
                 inputs.next().unwrap().borrow(),
@@ -475,21 +468,16 @@ where
         }
     }
 
-    fn prepare<A, S>(
+    fn prepare(
         &mut self,
-        sets: &A,
-        cbuf: &mut CommandBuffer<B, Graphics>,
+        sets: &mut Descriptors<B>,
+        _buffers: &[&B::Buffer],
+        cbuf: &mut PrepareBuffer<B>,
         factory: &mut Factory<B>,
         scene: &Scene<B, T>,
-    ) where
-        A: Index<usize>,
-        A::Output: Index<usize, Output = S>,
-        S: Borrow<B::DescriptorSetLayout>,
-    {
+    ) {
         use std::iter::once;
 
-        let set = sets[0][0].borrow();
-
         #[derive(Clone, Copy, Debug, PartialEq)]
         struct FragmentArgs {
             light_position: [f32; 3],
@@ -535,7 +523,7 @@ where
                         Properties::DEVICE_LOCAL,
                     )
                     .unwrap();
-                let set = self.pool.allocate(factory, set, Self::bindings());
+                let set = sets.allocate(factory, 0, 0).unwrap();
                 factory.write_descriptor_sets(
                     once(DescriptorSetWrite {
                         set: &set,
@@ -583,6 +571,7 @@ where
                         })),
                 );
 
+                cbuf.own_buffer(buffer.borrow());
                 cbuf.update_buffer(buffer.borrow(), 0, cast_slice(&[fragment_args]));
 
                 cbuf.pipeline_barrier(
@@ -604,6 +593,8 @@ where
 
     fn draw<L, P>(
         &mut self,
+        _sets: &Descriptors<B>,
+        _buffers: &[&B::Buffer],
         layouts: &L,
         pipelines: &P,
         mut encoder: RenderPassInlineEncoder<B, Primary>,
@@ -629,10 +620,9 @@ where
         }
     }
 
-    fn dispose(mut self, factory: &mut Factory<B>, scene: &mut Scene<B, T>) {
+    fn dispose(self, factory: &mut Factory<B>, scene: &mut Scene<B, T>) {
         for light in &mut scene.lights {
             for cache in unsafe { &mut *light.cache.get() }.take() {
-                self.pool.free(cache.set);
                 for uniform in cache.uniforms {
                     factory.destroy_buffer(uniform);
                 }
@@ -67,7 +67,6 @@ struct ConrodRenderPass<B: Backend> {
     image: Image<B>,
     upload: Option<Buffer<B>>,
     vertex: Option<Vertex<B>>,
-    pool: XfgDescriptorPool<B>,
     sampler: B::Sampler,
     view: B::ImageView,
     set: Option<B::DescriptorSet>,
@@ -227,8 +226,6 @@ where
         I: IntoIterator,
         I::Item: Borrow<B::ImageView>,
     {
-        let mut pool = XfgDescriptorPool::new();
-
         let sampler = factory.create_sampler(image::SamplerInfo::new(
             image::Filter::Linear,
             image::WrapMode::Clamp,
@@ -271,38 +268,31 @@ where
             sampler,
             image,
             view,
-            pool,
             set: None,
             upload: None,
             vertex: None,
         }
     }
 
-    fn prepare<A, S>(
+    fn prepare(
         &mut self,
-        sets: &A,
-        cbuf: &mut CommandBuffer<B, Graphics>,
+        sets: &mut Descriptors<B>,
+        _buffers: &[&B::Buffer],
+        cbuf: &mut PrepareBuffer<B>,
         factory: &mut Factory<B>,
         scene: &Scene<B, T, Ui>,
-    ) where
-        A: Index<usize>,
-        A::Output: Index<usize, Output = S>,
-        S: Borrow<B::DescriptorSetLayout>,
-    {
+    ) {
         trace!("Prepare UI rendering");
 
         if scene.other.is_none() {
             return;
         }
 
-        let set = sets[0][0].borrow();
-
-        let ref mut pool = self.pool;
         let ref view = self.view;
         let ref sampler = self.sampler;
 
         self.set.get_or_insert_with(|| {
-            let set = pool.allocate(factory, set, Self::bindings());
+            let set = sets.allocate(factory, 0, 0).unwrap();
             factory.write_descriptor_sets(Some(DescriptorSetWrite {
                 set: &set,
                 binding: 0,
@@ -380,6 +370,8 @@ where
                     .unwrap()
             });
 
+            cbuf.own_buffer(upload.borrow());
+            cbuf.own_image(image.borrow());
             cbuf.pipeline_barrier(
                 PipelineStage::TRANSFER..PipelineStage::TRANSFER,
                 Dependencies::empty(),
@@ -492,6 +484,7 @@ where
         };
 
         let vertex_buffer = vertex.buffer.borrow();
+        cbuf.own_buffer(vertex_buffer);
 
         // Prepare vertex buffer for transfer
         cbuf.pipeline_barrier(
@@ -517,7 +510,7 @@ where
                 |pos: [f32; 3],
                  uv: [f32; 2],
                  color: [f32; 4],
-                 cbuf: &mut CommandBuffer<B, Graphics>| {
+                 cbuf: &mut PrepareBuffer<B>| {
                     // trace!("Text push :{:?} :{:?} :{:?}", pos, uv, color);
 
                     if text_count == 3 * 64 {
@@ -536,7 +529,7 @@ where
                 };
 
             let mut geom_push =
-                |pos: [f32; 3], color: [f32; 4], cbuf: &mut CommandBuffer<B, Graphics>| {
+                |pos: [f32; 3], color: [f32; 4], cbuf: &mut PrepareBuffer<B>| {
                     // trace!("Geom push :{:?} :{:?}", pos, color);
 
                     if geom_count == 3 * 64 {
@@ -683,6 +676,8 @@ where
 
     fn draw<L, P>(
         &mut self,
+        _sets: &Descriptors<B>,
+        _buffers: &[&B::Buffer],
         layouts: &L,
         pipelines: &P,
         mut encoder: RenderPassInlineEncoder<B, Primary>,
@@ -0,0 +1,268 @@
+use hal::{
+    pso::{
+        AllocationError, DescriptorPool as RawDescriptorPool, DescriptorRangeDesc,
+        DescriptorSetLayoutBinding, DescriptorType,
+    },
+    Backend, Device,
+};
+
+use node::render::Layout;
+
+/// Growable pool of descriptor sets with the same layout.
+/// Every time pool runs out of sets new underlying pool is created with twice the capacity.
+#[derive(Debug)]
+pub struct DescriptorPool<B: Backend> {
+    bindings: Vec<DescriptorSetLayoutBinding>,
+    pools: Vec<B::DescriptorPool>,
+    free: Vec<B::DescriptorSet>,
+}
+
+impl<B> DescriptorPool<B>
+where
+    B: Backend,
+{
+    /// Create empty pool for sets with specified bindings.
+    pub fn new(bindings: Vec<DescriptorSetLayoutBinding>) -> Self {
+        DescriptorPool {
+            bindings,
+            pools: Vec::new(),
+            free: Vec::new(),
+        }
+    }
+
+    /// Allocate descriptor set.
+    /// `layout` must be created from the same bindings this pool was created with.
+    /// Errors other than running out of pool memory are returned,
+    /// e.g. `AllocationError::IncompatibleLayout` if `layout` doesn't match the bindings.
+    pub fn allocate<D>(
+        &mut self,
+        device: &mut D,
+        layout: &B::DescriptorSetLayout,
+    ) -> Result<B::DescriptorSet, AllocationError>
+    where
+        D: Device<B>,
+    {
+        if let Some(set) = self.free.pop() {
+            return Ok(set);
+        }
+
+        if let Some(pool) = self.pools.last_mut() {
+            match pool.allocate_set(layout) {
+                Ok(set) => return Ok(set),
+                Err(AllocationError::OutOfPoolMemory) | Err(AllocationError::FragmentedPool) => {}
+                Err(err) => return Err(err),
+            }
+        }
+
+        let multiplier = 2usize.pow(self.pools.len() as u32);
+        trace!("Create descriptor pool for {} sets", multiplier);
+        let mut pool = device.create_descriptor_pool(
+            multiplier,
+            bindings_to_ranges(multiplier, &self.bindings),
+        );
+        let set = pool.allocate_set(layout);
+        self.pools.push(pool);
+        set
+    }
+
+    /// Return descriptor set to the pool.
+    /// Set will be reused by subsequent allocations.
+    pub fn free(&mut self, set: B::DescriptorSet) {
+        self.free.push(set);
+    }
+
+    /// Dispose of the pool.
+    /// All sets allocated from this pool become invalid.
+    pub fn dispose<D>(self, device: &mut D)
+    where
+        D: Device<B>,
+    {
+        drop(self.free);
+        for pool in self.pools {
+            device.destroy_descriptor_pool(pool);
+        }
+    }
+}
+
+#[derive(Debug)]
+struct Set<B: Backend> {
+    layout: B::DescriptorSetLayout,
+    pool: DescriptorPool<B>,
+    /// Sets with graph images by frame. See `ImageInfo::frames`.
+    bound: Vec<B::DescriptorSet>,
+    /// Freed sets by frame slot they were freed in.
+    pending: Vec<Vec<B::DescriptorSet>>,
+}
+
+/// Descriptor set layouts of the render pass along with pools to allocate sets from.
+/// Indexed by pipeline layout and set index in `RenderPassDesc::layouts`.
+/// Freed sets are reused only when the frame slot they were freed in comes around again,
+/// so sets used by frames in flight are not overwritten.
+/// See `GraphBuilder::with_frames_in_flight`.
+#[derive(Debug)]
+pub struct Descriptors<B: Backend> {
+    layouts: Vec<Vec<Set<B>>>,
+    slot: usize,
+    frame: usize,
+}
+
+impl<B> Descriptors<B>
+where
+    B: Backend,
+{
+    /// Create set layouts for pipeline layouts.
+    pub(crate) fn new<D>(layouts: &[Layout], device: &mut D) -> Self
+    where
+        D: Device<B>,
+    {
+        Descriptors {
+            layouts: layouts
+                .iter()
+                .map(|layout| {
+                    layout
+                        .sets
+                        .iter()
+                        .map(|set| Set {
+                            layout: device.create_descriptor_set_layout(
+                                set.bindings.iter().cloned(),
+                                ::std::iter::empty::<B::Sampler>(),
+                            ),
+                            pool: DescriptorPool::new(set.bindings.clone()),
+                            bound: Vec::new(),
+                            pending: Vec::new(),
+                        })
+                        .collect()
+                })
+                .collect(),
+            slot: 0,
+            frame: 0,
+        }
+    }
+
+    /// Start using the frame slot.
+    /// Sets freed the last time the slot was used become available for allocation.
+    pub(crate) fn next_frame(&mut self, slot: usize) {
+        self.slot = slot;
+        for set in self.layouts.iter_mut().flat_map(|sets| sets) {
+            if let Some(pending) = set.pending.get_mut(slot) {
+                set.pool.free.extend(pending.drain(..));
+            }
+        }
+    }
+
+    /// Use sets with graph images of the frame.
+    /// See `ImageInfo::frames`.
+    pub(crate) fn select_frame(&mut self, frame: usize) {
+        self.frame = frame;
+    }
+
+    /// Get set layouts of the pipeline layout.
+    pub fn set_layouts<'a>(
+        &'a self,
+        layout: usize,
+    ) -> impl Iterator<Item = &'a B::DescriptorSetLayout> {
+        self.layouts[layout].iter().map(|set| &set.layout)
+    }
+
+    /// Get set layout.
+    pub fn set_layout(&self, layout: usize, set: usize) -> &B::DescriptorSetLayout {
+        &self.layouts[layout][set].layout
+    }
+
+    /// Allocate descriptor set compatible with set layout.
+    /// See `DescriptorPool::allocate`.
+    pub fn allocate<D>(
+        &mut self,
+        device: &mut D,
+        layout: usize,
+        set: usize,
+    ) -> Result<B::DescriptorSet, AllocationError>
+    where
+        D: Device<B>,
+    {
+        let Set {
+            layout: ref set_layout,
+            ref mut pool,
+            ..
+        } = self.layouts[layout][set];
+        pool.allocate(device, set_layout)
+    }
+
+    /// Return descriptor set allocated with `allocate` for the same `layout` and `set`.
+    /// The set is reused once commands of the current frame complete.
+    pub fn free(&mut self, layout: usize, set: usize, descriptor_set: B::DescriptorSet) {
+        let slot = self.slot;
+        let ref mut pending = self.layouts[layout][set].pending;
+        while pending.len() <= slot {
+            pending.push(Vec::new());
+        }
+        pending[slot].push(descriptor_set);
+    }
+
+    /// Get descriptor set with graph images written by the node for the current frame.
+    /// Returns `None` if no images are bound to the set.
+    /// See `RenderPassDesc::sampled_bindings` and `RenderPassDesc::storage_bindings`.
+    pub fn bound(&self, layout: usize, set: usize) -> Option<&B::DescriptorSet> {
+        self.layouts[layout][set].bound.get(self.frame)
+    }
+
+    /// Allocate sets for graph images of each frame if not allocated yet.
+    pub(crate) fn bind<D>(
+        &mut self,
+        device: &mut D,
+        layout: usize,
+        set: usize,
+        frames: usize,
+    ) -> Result<(), AllocationError>
+    where
+        D: Device<B>,
+    {
+        let Set {
+            layout: ref set_layout,
+            ref mut pool,
+            ref mut bound,
+            ..
+        } = self.layouts[layout][set];
+        while bound.len() < frames {
+            bound.push(pool.allocate(device, set_layout)?);
+        }
+        Ok(())
+    }
+
+    /// Dispose of all layouts and pools.
+    pub(crate) fn dispose<D>(self, device: &mut D)
+    where
+        D: Device<B>,
+    {
+        for set in self.layouts.into_iter().flat_map(|sets| sets) {
+            drop(set.bound);
+            drop(set.pending);
+            set.pool.dispose(device);
+            device.destroy_descriptor_set_layout(set.layout);
+        }
+    }
+}
+
+fn bindings_to_ranges(
+    multiplier: usize,
+    bindings: &[DescriptorSetLayoutBinding],
+) -> Vec<DescriptorRangeDesc> {
+    let mut ranges = Vec::new();
+
+    for binding in bindings {
+        let index = binding.ty as usize;
+        while ranges.len() <= index {
+            ranges.push(DescriptorRangeDesc {
+                ty: DescriptorType::Sampler,
+                count: 0,
+            });
+        }
+
+        let ref mut range = ranges[index];
+        range.ty = binding.ty;
+        range.count += binding.count * multiplier;
+    }
+
+    ranges.retain(|range| range.count > 0);
+    ranges
+}
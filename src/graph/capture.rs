@@ -0,0 +1,330 @@
+use std::{
+    collections::{BTreeSet, HashMap}, io::{self, Write}, ops::Range, time::{Duration, Instant},
+};
+
+use chain::sync::SyncData;
+use hal::queue::QueueFamilyId;
+
+/// Frames recorded between `Graph::start_capture` and `Graph::stop_capture`.
+/// Can be written as Chrome trace-event JSON viewable in `chrome://tracing`.
+#[derive(Clone, Debug)]
+pub struct Capture {
+    origin: Instant,
+    names: Vec<String>,
+    frames: Vec<CapturedFrame>,
+}
+
+#[derive(Clone, Debug)]
+struct CapturedFrame {
+    frame: u64,
+    start: Duration,
+    nodes: Vec<CapturedNode>,
+    gpu: Option<Vec<Range<u64>>>,
+}
+
+#[derive(Clone, Debug)]
+struct CapturedNode {
+    node: usize,
+    track: (QueueFamilyId, usize),
+    start: Duration,
+    cpu: Duration,
+    waits: Vec<usize>,
+    signals: Vec<usize>,
+}
+
+impl Capture {
+    pub(crate) fn new(names: Vec<String>) -> Self {
+        Capture {
+            origin: Instant::now(),
+            names,
+            frames: Vec::new(),
+        }
+    }
+
+    /// Start capturing new frame.
+    pub(crate) fn begin_frame(&mut self, frame: u64, start: Instant) {
+        let start = start.duration_since(self.origin);
+        self.frames.push(CapturedFrame {
+            frame,
+            start,
+            nodes: Vec::new(),
+            gpu: None,
+        });
+    }
+
+    /// Capture node run of the current frame.
+    pub(crate) fn node(
+        &mut self,
+        node: usize,
+        family: QueueFamilyId,
+        queue: usize,
+        sync: &SyncData<usize, usize>,
+        start: Instant,
+        cpu: Duration,
+    ) {
+        let start = start.duration_since(self.origin);
+        self.frames.last_mut().unwrap().nodes.push(CapturedNode {
+            node,
+            track: (family, queue),
+            start,
+            cpu,
+            waits: sync.acquire.wait.iter().map(|wait| *wait.semaphore()).collect(),
+            signals: sync
+                .release
+                .signal
+                .iter()
+                .map(|signal| *signal.semaphore())
+                .collect(),
+        });
+    }
+
+    /// Attach GPU timestamps of the frame in nanoseconds.
+    pub(crate) fn gpu(&mut self, frame: u64, intervals: Vec<Range<u64>>) {
+        if let Some(captured) = self.frames.iter_mut().find(|captured| captured.frame == frame) {
+            captured.gpu = Some(intervals);
+        }
+    }
+
+    /// Number of captured frames.
+    pub fn frames(&self) -> usize {
+        self.frames.len()
+    }
+
+    /// Write captured frames as Chrome trace-event JSON.
+    /// CPU recording of nodes and GPU execution are shown as separate processes
+    /// with a thread per queue. Semaphore waits are shown as flow events.
+    /// GPU intervals of a frame are aligned to the start of its `Graph::run`
+    /// since GPU and CPU clocks are not correlated.
+    /// GPU intervals are available only if `GraphBuilder::with_gpu_timings` was used.
+    pub fn write_chrome_trace<W: Write>(&self, mut writer: W) -> io::Result<()> {
+        let tracks = self
+            .frames
+            .iter()
+            .flat_map(|frame| frame.nodes.iter().map(|node| node.track))
+            .collect::<BTreeSet<_>>()
+            .into_iter()
+            .enumerate()
+            .map(|(index, track)| (track, index))
+            .collect::<HashMap<_, _>>();
+
+        let mut events = Vec::new();
+        for (pid, process) in ["CPU", "GPU"].iter().enumerate() {
+            events.push(format!(
+                r#"{{"ph":"M","name":"process_name","pid":{},"args":{{"name":"{}"}}}}"#,
+                pid, process
+            ));
+            for (&(family, queue), &tid) in &tracks {
+                events.push(format!(
+                    r#"{{"ph":"M","name":"thread_name","pid":{},"tid":{},"args":{{"name":"family {} queue {}"}}}}"#,
+                    pid, tid, family.0, queue
+                ));
+            }
+        }
+
+        let mut flow = 0;
+        for frame in &self.frames {
+            // Spans of the frame in microseconds for each process.
+            let cpu = frame
+                .nodes
+                .iter()
+                .map(|node| (micros(node.start), micros(node.start + node.cpu)))
+                .collect::<Vec<_>>();
+            let gpu = frame.gpu.as_ref().map(|intervals| {
+                let base = intervals.iter().map(|interval| interval.start).min().unwrap_or(0);
+                let start = micros(frame.start);
+                let offset = |nanos: u64| start + nanos.saturating_sub(base) as f64 / 1000.0;
+                frame
+                    .nodes
+                    .iter()
+                    .map(|node| {
+                        let ref interval = intervals[node.node];
+                        (offset(interval.start), offset(interval.end.max(interval.start)))
+                    })
+                    .collect::<Vec<_>>()
+            });
+
+            let mut processes = vec![&cpu];
+            processes.extend(gpu.as_ref());
+            for (pid, spans) in processes.into_iter().enumerate() {
+                for (node, &(start, end)) in frame.nodes.iter().zip(spans) {
+                    events.push(format!(
+                        r#"{{"ph":"X","name":"{}","cat":"{}","pid":{},"tid":{},"ts":{:.3},"dur":{:.3},"args":{{"frame":{}}}}}"#,
+                        escape(&self.names[node.node]),
+                        if pid == 0 { "cpu" } else { "gpu" },
+                        pid,
+                        tracks[&node.track],
+                        start,
+                        end - start,
+                        frame.frame
+                    ));
+                }
+            }
+
+            // Flows are drawn between GPU spans when available.
+            let (pid, spans) = match gpu {
+                Some(ref gpu) => (1, gpu),
+                None => (0, &cpu),
+            };
+            for (waiter, node) in frame.nodes.iter().enumerate() {
+                for &semaphore in &node.waits {
+                    let signaler = match frame
+                        .nodes
+                        .iter()
+                        .position(|node| node.signals.contains(&semaphore))
+                    {
+                        Some(signaler) => signaler,
+                        None => continue,
+                    };
+                    events.push(format!(
+                        r#"{{"ph":"s","name":"semaphore {}","cat":"sync","id":{},"pid":{},"tid":{},"ts":{:.3}}}"#,
+                        semaphore,
+                        flow,
+                        pid,
+                        tracks[&frame.nodes[signaler].track],
+                        spans[signaler].1
+                    ));
+                    events.push(format!(
+                        r#"{{"ph":"f","bp":"e","name":"semaphore {}","cat":"sync","id":{},"pid":{},"tid":{},"ts":{:.3}}}"#,
+                        semaphore,
+                        flow,
+                        pid,
+                        tracks[&node.track],
+                        spans[waiter].0
+                    ));
+                    flow += 1;
+                }
+            }
+        }
+
+        writeln!(writer, "{{\"traceEvents\":[")?;
+        for (index, event) in events.iter().enumerate() {
+            let separator = if index + 1 < events.len() { "," } else { "" };
+            writeln!(writer, "{}{}", event, separator)?;
+        }
+        writeln!(writer, "]}}")
+    }
+}
+
+fn micros(duration: Duration) -> f64 {
+    duration.as_secs() as f64 * 1_000_000.0 + duration.subsec_nanos() as f64 / 1000.0
+}
+
+/// Escape string for JSON.
+fn escape(string: &str) -> String {
+    let mut escaped = String::with_capacity(string.len());
+    for c in string.chars() {
+        match c {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            c if (c as u32) < 0x20 => escaped.push_str(&format!("\\u{:04x}", c as u32)),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Two nodes on different queues. Second one waits for the semaphore signaled by the first.
+    fn capture() -> Capture {
+        let millis = Duration::from_millis;
+        Capture {
+            origin: Instant::now(),
+            names: vec!["Clear".to_string(), "Node \"a\"\\b\n".to_string()],
+            frames: vec![CapturedFrame {
+                frame: 3,
+                start: millis(1),
+                nodes: vec![
+                    CapturedNode {
+                        node: 0,
+                        track: (QueueFamilyId(0), 0),
+                        start: millis(1),
+                        cpu: Duration::from_micros(500),
+                        waits: Vec::new(),
+                        signals: vec![7],
+                    },
+                    CapturedNode {
+                        node: 1,
+                        track: (QueueFamilyId(1), 0),
+                        start: millis(2),
+                        cpu: Duration::from_micros(250),
+                        waits: vec![7],
+                        signals: Vec::new(),
+                    },
+                ],
+                gpu: Some(vec![100_000..300_000, 400_000..500_000]),
+            }],
+        }
+    }
+
+    fn trace(capture: &Capture) -> Vec<String> {
+        let mut json = Vec::new();
+        capture.write_chrome_trace(&mut json).unwrap();
+        String::from_utf8(json)
+            .unwrap()
+            .lines()
+            .map(str::to_owned)
+            .collect()
+    }
+
+    #[test]
+    fn names_are_escaped() {
+        assert_eq!(escape("plain"), "plain");
+        assert_eq!(escape("Node \"a\"\\b\n"), "Node \\\"a\\\"\\\\b\\u000a");
+    }
+
+    #[test]
+    fn trace_contains_metadata_spans_and_flows() {
+        let lines = trace(&capture());
+        assert_eq!(lines.first().unwrap(), "{\"traceEvents\":[");
+        assert_eq!(lines.last().unwrap(), "]}");
+
+        // Events are separated with commas except for the last one.
+        let events = &lines[1..lines.len() - 1];
+        assert_eq!(events.len(), 12);
+        for event in &events[..events.len() - 1] {
+            assert!(event.ends_with("},"), "{}", event);
+        }
+        assert!(events.last().unwrap().ends_with('}'));
+        let events = events
+            .iter()
+            .map(|event| &event[..event.len() - event.ends_with(',') as usize])
+            .collect::<Vec<_>>();
+
+        let expected = [
+            r#"{"ph":"M","name":"process_name","pid":0,"args":{"name":"CPU"}}"#,
+            r#"{"ph":"M","name":"process_name","pid":1,"args":{"name":"GPU"}}"#,
+            r#"{"ph":"M","name":"thread_name","pid":0,"tid":0,"args":{"name":"family 0 queue 0"}}"#,
+            r#"{"ph":"M","name":"thread_name","pid":0,"tid":1,"args":{"name":"family 1 queue 0"}}"#,
+            r#"{"ph":"M","name":"thread_name","pid":1,"tid":0,"args":{"name":"family 0 queue 0"}}"#,
+            r#"{"ph":"M","name":"thread_name","pid":1,"tid":1,"args":{"name":"family 1 queue 0"}}"#,
+            r#"{"ph":"X","name":"Clear","cat":"cpu","pid":0,"tid":0,"ts":1000.000,"dur":500.000,"args":{"frame":3}}"#,
+            r#"{"ph":"X","name":"Node \"a\"\\b\u000a","cat":"cpu","pid":0,"tid":1,"ts":2000.000,"dur":250.000,"args":{"frame":3}}"#,
+            // GPU intervals are aligned to the start of the frame.
+            r#"{"ph":"X","name":"Clear","cat":"gpu","pid":1,"tid":0,"ts":1000.000,"dur":200.000,"args":{"frame":3}}"#,
+            r#"{"ph":"X","name":"Node \"a\"\\b\u000a","cat":"gpu","pid":1,"tid":1,"ts":1300.000,"dur":100.000,"args":{"frame":3}}"#,
+            // Flow goes from the end of the signaling span to the start of the waiting one.
+            r#"{"ph":"s","name":"semaphore 7","cat":"sync","id":0,"pid":1,"tid":0,"ts":1200.000}"#,
+            r#"{"ph":"f","bp":"e","name":"semaphore 7","cat":"sync","id":0,"pid":1,"tid":1,"ts":1300.000}"#,
+        ];
+        for event in &expected {
+            assert!(events.contains(event), "{} not found in {:#?}", event, events);
+        }
+    }
+
+    #[test]
+    fn flows_use_cpu_spans_without_gpu_timings() {
+        let mut capture = capture();
+        capture.frames[0].gpu = None;
+        let lines = trace(&capture);
+        assert!(!lines.iter().any(|line| line.contains(r#""cat":"gpu""#)));
+        assert!(lines.iter().any(|line| line.starts_with(
+            r#"{"ph":"s","name":"semaphore 7","cat":"sync","id":0,"pid":0,"tid":0,"ts":1500.000}"#
+        )));
+        assert!(lines.iter().any(|line| line.starts_with(
+            r#"{"ph":"f","bp":"e","name":"semaphore 7","cat":"sync","id":0,"pid":0,"tid":1,"ts":2000.000}"#
+        )));
+    }
+}
@@ -0,0 +1,273 @@
+use std::{borrow::Borrow, collections::HashMap, ops::Range};
+
+use chain::{resource::{Buffer, Id, Image}, schedule::Schedule, sync::SyncData};
+use hal::{
+    buffer,
+    command::{CommandBufferFlags, RawCommandBuffer, RawLevel},
+    image, memory::Barrier, pool::{CommandPoolCreateFlags, RawCommandPool}, pso::PipelineStage,
+    queue::QueueFamilyId, Backend, Device,
+};
+
+use util::*;
+
+/// Barrier of the graph resource.
+#[derive(Clone, Debug)]
+enum SplitBarrier {
+    Buffer(Id<Buffer>, Range<(buffer::State, PipelineStage)>),
+    Image(Id<Image>, Range<(image::State, PipelineStage)>),
+}
+
+/// Barrier moved out of the consumer node and split into an event
+/// set after the producer and waited before the consumer.
+#[derive(Clone, Debug)]
+pub(crate) struct Split {
+    family: QueueFamilyId,
+    producer: usize,
+    consumer: usize,
+    barrier: SplitBarrier,
+}
+
+/// Find acquire barriers worth splitting.
+/// Barrier is split when the consumer's nearest predecessor accessing the resource
+/// is on the same queue and other nodes are submitted in between.
+/// Nodes waiting for semaphores and `excluded` images are left as is.
+pub(crate) fn find_splits(
+    schedule: &Schedule<SyncData<usize, usize>>,
+    pass_buffers: &[Vec<Id<Buffer>>],
+    pass_images: &[Vec<Id<Image>>],
+    excluded: &[Id<Image>],
+) -> Vec<Split> {
+    let mut splits = Vec::new();
+    for family in schedule.iter() {
+        for queue in family.iter() {
+            let family = queue.id().family();
+            let submissions = queue.iter().map(|(_, sub)| sub).collect::<Vec<_>>();
+            for (index, submission) in submissions.iter().enumerate() {
+                let sync = submission.sync();
+                if !sync.acquire.wait.is_empty() {
+                    continue;
+                }
+                let consumer = submission.pass().0;
+                // Nearest predecessor submitted at least two submissions earlier.
+                let producer = |uses: &Fn(usize) -> bool| {
+                    submissions[..index]
+                        .iter()
+                        .rposition(|sub| uses(sub.pass().0))
+                        .and_then(|producer| {
+                            if producer + 1 < index {
+                                Some(submissions[producer].pass().0)
+                            } else {
+                                None
+                            }
+                        })
+                };
+
+                for (&id, barrier) in &sync.acquire.buffers {
+                    if let Some(producer) = producer(&|pass| pass_buffers[pass].contains(&id)) {
+                        let Range { ref start, ref end } = barrier.states;
+                        splits.push(Split {
+                            family,
+                            producer,
+                            consumer,
+                            barrier: SplitBarrier::Buffer(
+                                id,
+                                (start.access, start.stages)..(end.access, end.stages),
+                            ),
+                        });
+                    }
+                }
+                for (&id, barrier) in &sync.acquire.images {
+                    if excluded.contains(&id) {
+                        continue;
+                    }
+                    if let Some(producer) = producer(&|pass| pass_images[pass].contains(&id)) {
+                        let Range { ref start, ref end } = barrier.states;
+                        splits.push(Split {
+                            family,
+                            producer,
+                            consumer,
+                            barrier: SplitBarrier::Image(
+                                id,
+                                ((start.access, start.layout), start.stages)
+                                    ..((end.access, end.layout), end.stages),
+                            ),
+                        });
+                    }
+                }
+            }
+        }
+    }
+    splits
+}
+
+/// Remove split barriers from the synchronization of the consumer node.
+pub(crate) fn strip_splits(
+    splits: &[Split],
+    consumer: usize,
+    sync: &SyncData<usize, usize>,
+) -> SyncData<usize, usize> {
+    let mut sync = sync.clone();
+    for split in splits.iter().filter(|split| split.consumer == consumer) {
+        match split.barrier {
+            SplitBarrier::Buffer(id, _) => {
+                sync.acquire.buffers.remove(&id);
+            }
+            SplitBarrier::Image(id, _) => {
+                sync.acquire.images.remove(&id);
+            }
+        }
+    }
+    sync
+}
+
+/// Events and command buffers setting and waiting them.
+pub(crate) struct Events<B: Backend> {
+    events: Vec<B::Event>,
+    pools: HashMap<QueueFamilyId, B::CommandPool>,
+    set: HashMap<usize, B::CommandBuffer>,
+    /// Command buffers with number of barriers they wait for.
+    wait: HashMap<usize, (B::CommandBuffer, usize)>,
+}
+
+impl<B> Events<B>
+where
+    B: Backend,
+{
+    /// Create event for each split and record command buffers.
+    pub(crate) fn new<D, U, I>(
+        splits: &[Split],
+        buffers: &[BufferResource<U>],
+        images: &[GraphImage<B, I>],
+        device: &mut D,
+    ) -> Self
+    where
+        D: Device<B>,
+        U: Borrow<B::Buffer>,
+        I: Borrow<B::Image>,
+    {
+        let events = splits
+            .iter()
+            .map(|_| device.create_event())
+            .collect::<Vec<_>>();
+
+        let mut pools = HashMap::new();
+        for split in splits {
+            pools.entry(split.family).or_insert_with(|| {
+                device.create_command_pool(split.family, CommandPoolCreateFlags::empty())
+            });
+        }
+
+        let mut set = HashMap::new();
+        let mut wait = HashMap::new();
+        for split in splits {
+            let pool = pools.get_mut(&split.family).unwrap();
+            if !set.contains_key(&split.producer) {
+                let cbuf = pool.allocate(1, RawLevel::Primary).pop().unwrap();
+                set.insert(split.producer, cbuf);
+            }
+            if !wait.contains_key(&split.consumer) {
+                let cbuf = pool.allocate(1, RawLevel::Primary).pop().unwrap();
+                wait.insert(split.consumer, (cbuf, 0));
+            }
+        }
+
+        // Producer sets events once stages of the barriers are complete.
+        for (&producer, cbuf) in &mut set {
+            cbuf.begin(CommandBufferFlags::EMPTY, Default::default());
+            for (index, split) in splits.iter().enumerate() {
+                if split.producer == producer {
+                    cbuf.set_event(&events[index], source_stages(&split.barrier));
+                }
+            }
+            cbuf.finish();
+        }
+
+        // Consumer waits for all its events at once and resets them for the next frame.
+        for (&consumer, &mut (ref mut cbuf, ref mut count)) in &mut wait {
+            let indices = (0..splits.len())
+                .filter(|&index| splits[index].consumer == consumer)
+                .collect::<Vec<_>>();
+            let mut stages = PipelineStage::empty()..PipelineStage::empty();
+            let barriers = indices
+                .iter()
+                .map(|&index| match splits[index].barrier {
+                    SplitBarrier::Buffer(id, ref barrier) => {
+                        stages.start |= barrier.start.1;
+                        stages.end |= barrier.end.1;
+                        Barrier::Buffer {
+                            states: barrier.start.0..barrier.end.0,
+                            target: buffers[id.index() as usize].buffer.borrow(),
+                        }
+                    }
+                    SplitBarrier::Image(id, ref barrier) => {
+                        stages.start |= barrier.start.1;
+                        stages.end |= barrier.end.1;
+                        let resource = images[id.index() as usize].as_ref().left().unwrap();
+                        Barrier::Image {
+                            states: barrier.start.0..barrier.end.0,
+                            target: resource.image.borrow(),
+                            range: image::SubresourceRange {
+                                aspects: resource.format.surface_desc().aspects,
+                                levels: 0..1,
+                                layers: 0..1,
+                            },
+                        }
+                    }
+                })
+                .collect::<Vec<_>>();
+            *count = barriers.len();
+
+            cbuf.begin(CommandBufferFlags::EMPTY, Default::default());
+            cbuf.wait_events(
+                indices.iter().map(|&index| &events[index]),
+                stages.clone(),
+                barriers,
+            );
+            for &index in &indices {
+                cbuf.reset_event(&events[index], stages.end);
+            }
+            cbuf.finish();
+        }
+
+        Events {
+            events,
+            pools,
+            set,
+            wait,
+        }
+    }
+
+    /// Command buffer waiting for events before the node along with number of its barriers.
+    pub(crate) fn wait(&self, node: usize) -> Option<(&B::CommandBuffer, usize)> {
+        self.wait
+            .get(&node)
+            .map(|&(ref cbuf, barriers)| (cbuf, barriers))
+    }
+
+    /// Command buffer setting events after the node.
+    pub(crate) fn set(&self, node: usize) -> Option<&B::CommandBuffer> {
+        self.set.get(&node)
+    }
+
+    pub(crate) fn dispose<D>(self, device: &mut D)
+    where
+        D: Device<B>,
+    {
+        // Command buffers are freed along with their pools.
+        drop(self.set);
+        drop(self.wait);
+        for (_, pool) in self.pools {
+            device.destroy_command_pool(pool);
+        }
+        for event in self.events {
+            device.destroy_event(event);
+        }
+    }
+}
+
+fn source_stages(barrier: &SplitBarrier) -> PipelineStage {
+    match *barrier {
+        SplitBarrier::Buffer(_, ref barrier) => barrier.start.1,
+        SplitBarrier::Image(_, ref barrier) => barrier.start.1,
+    }
+}
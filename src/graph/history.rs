@@ -0,0 +1,223 @@
+use std::{borrow::Borrow, collections::HashMap};
+
+use chain::{
+    chain::ImageChains, resource::{Id, Image}, schedule::{Schedule, Submission}, sync::SyncData,
+};
+use hal::{
+    command::{CommandBufferFlags, RawCommandBuffer, RawLevel}, image,
+    pool::{CommandPoolCreateFlags, RawCommandPool}, pso::PipelineStage, queue::QueueFamilyId,
+    Backend, Device,
+};
+
+use node::barriers::BarrierBatch;
+use util::*;
+
+/// Transition of the history image recorded by the graph
+/// into the submission of the first node using one of the images in a frame.
+#[derive(Clone, Debug)]
+pub(crate) struct Transition {
+    family: QueueFamilyId,
+    node: usize,
+    view: Id<Image>,
+    other: Id<Image>,
+    source: Option<(image::State, PipelineStage)>,
+    target: (image::State, PipelineStage),
+}
+
+/// Find transitions of history images.
+/// The image used through one id was used through the other id in the previous frame.
+/// So the first use of an id in a frame is synchronized with the last use of the other id.
+/// All nodes using a history image must run on the same queue
+/// as transitions are recorded with pipeline barriers.
+pub(crate) fn find_transitions(
+    history: &[(Id<Image>, Id<Image>)],
+    schedule: &Schedule<SyncData<usize, usize>>,
+    pass_images: &[Vec<Id<Image>>],
+    chains: &ImageChains,
+) -> Vec<Transition> {
+    // First node and last link of each id.
+    let mut uses = HashMap::new();
+    let mut queues = HashMap::new();
+    for family in schedule.iter() {
+        for queue in family.iter() {
+            let qid = queue.id();
+            for (_, submission) in queue.iter() {
+                let node = submission.pass().0;
+                for &(current, previous) in history {
+                    for &id in &[current, previous] {
+                        if !pass_images[node].contains(&id) {
+                            continue;
+                        }
+                        let queue = (qid.family(), qid.index());
+                        assert_eq!(
+                            *queues.entry(current).or_insert(queue),
+                            queue,
+                            "Nodes using history image {} must run on the same queue",
+                            current.index()
+                        );
+                        let link = submission.image(id);
+                        let entry = uses.entry(id).or_insert((None, 0));
+                        if link == 0 && entry.0.is_none() {
+                            entry.0 = Some((qid.family(), node));
+                        }
+                        entry.1 = entry.1.max(link);
+                    }
+                }
+            }
+        }
+    }
+
+    let state = |id: Id<Image>, link: usize| {
+        let state = chains[&id].link(link).state();
+        ((state.access, state.layout), state.stages)
+    };
+
+    let mut transitions = Vec::new();
+    for &(current, previous) in history {
+        for &(view, other) in &[(current, previous), (previous, current)] {
+            if let Some(&(Some((family, node)), _)) = uses.get(&view) {
+                transitions.push(Transition {
+                    family,
+                    node,
+                    view,
+                    other,
+                    source: uses.get(&other).map(|&(_, last)| state(other, last)),
+                    target: state(view, 0),
+                });
+            }
+        }
+    }
+    transitions
+}
+
+/// Remove barriers of the first use of history images from the synchronization of the node.
+/// Those barriers are replaced by transitions recorded by the graph.
+pub(crate) fn strip_history(
+    history: &[(Id<Image>, Id<Image>)],
+    images: &[Id<Image>],
+    submission: &Submission<SyncData<usize, usize>>,
+    sync: &mut SyncData<usize, usize>,
+) {
+    for &(current, previous) in history {
+        for id in &[current, previous] {
+            if images.contains(id) && submission.image(*id) == 0 {
+                sync.acquire.images.remove(id);
+            }
+        }
+    }
+}
+
+/// Transitions of history images and the order in which images are used.
+pub(crate) struct History<B: Backend> {
+    pools: HashMap<QueueFamilyId, B::CommandPool>,
+    /// Command buffers for the first run and for both orders of the images.
+    cbufs: HashMap<usize, Vec<B::CommandBuffer>>,
+    /// Number of transitions of each node.
+    counts: HashMap<usize, usize>,
+    runs: u64,
+}
+
+impl<B> History<B>
+where
+    B: Backend,
+{
+    /// Record command buffers with transitions for each node.
+    pub(crate) fn new<D, I>(
+        transitions: &[Transition],
+        images: &[GraphImage<B, I>],
+        device: &mut D,
+    ) -> Self
+    where
+        D: Device<B>,
+        I: Borrow<B::Image>,
+    {
+        let mut pools = HashMap::new();
+        for transition in transitions {
+            pools.entry(transition.family).or_insert_with(|| {
+                device.create_command_pool(transition.family, CommandPoolCreateFlags::empty())
+            });
+        }
+
+        let mut cbufs = HashMap::new();
+        let mut counts = HashMap::new();
+        for transition in transitions {
+            if !cbufs.contains_key(&transition.node) {
+                let pool = pools.get_mut(&transition.family).unwrap();
+                cbufs.insert(transition.node, pool.allocate(3, RawLevel::Primary));
+            }
+            *counts.entry(transition.node).or_insert(0) += 1;
+        }
+
+        let resource = |id: Id<Image>| {
+            images[id.index() as usize]
+                .as_ref()
+                .left()
+                .expect("History images can't be presented directly")
+        };
+
+        for (&node, cbufs) in &mut cbufs {
+            for (variant, cbuf) in cbufs.iter_mut().enumerate() {
+                let mut batch = BarrierBatch::<B>::new();
+                for transition in transitions.iter().filter(|t| t.node == node) {
+                    // Content is undefined on the first run.
+                    let (target, source) = match variant {
+                        0 => (transition.view, None),
+                        1 => (transition.view, transition.source),
+                        _ => (transition.other, transition.source),
+                    };
+                    let source = source.unwrap_or((
+                        (image::Access::empty(), image::Layout::Undefined),
+                        PipelineStage::TOP_OF_PIPE,
+                    ));
+                    let resource = resource(target);
+                    batch.image(
+                        &(source..transition.target),
+                        resource.image.borrow(),
+                        resource.format.surface_desc().aspects,
+                    );
+                }
+                cbuf.begin(CommandBufferFlags::EMPTY, Default::default());
+                batch.record(cbuf);
+                cbuf.finish();
+            }
+        }
+
+        History {
+            pools,
+            cbufs,
+            counts,
+            runs: 0,
+        }
+    }
+
+    /// Order of the images for the current run.
+    /// Images of every pair are swapped for the nodes when `1`.
+    pub(crate) fn parity(&self) -> usize {
+        (self.runs % 2) as usize
+    }
+
+    /// Command buffer with transitions required before the node along with number of barriers.
+    /// Must be submitted before commands of the node within its submission.
+    pub(crate) fn transition(&self, node: usize) -> Option<(&B::CommandBuffer, usize)> {
+        self.cbufs.get(&node).map(|cbufs| {
+            let variant = if self.runs == 0 { 0 } else { 1 + self.parity() };
+            (&cbufs[variant], self.counts[&node])
+        })
+    }
+
+    /// Swap images after the run.
+    pub(crate) fn swap(&mut self) {
+        self.runs += 1;
+    }
+
+    pub(crate) fn dispose<D>(self, device: &mut D)
+    where
+        D: Device<B>,
+    {
+        // Command buffers are freed along with their pools.
+        drop(self.cbufs);
+        for (_, pool) in self.pools {
+            device.destroy_command_pool(pool);
+        }
+    }
+}
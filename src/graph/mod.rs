@@ -1,34 +1,93 @@
 use std::{
-    borrow::Borrow, collections::HashMap, iter::once, marker::PhantomData, ops::AddAssign,
-    sync::{atomic::AtomicUsize, Arc},
+    borrow::Borrow, collections::HashMap, iter::{empty, once}, marker::PhantomData, mem::replace,
+    ops::AddAssign, sync::{atomic::{AtomicUsize, Ordering}, Arc}, time::Instant,
 };
 
 use chain::{
-    collect::collect, pass::{Pass, PassId, StateUsage}, resource::{Id, State}, schedule::Schedule,
-    sync::{sync, SyncData},
+    chain::{BufferChains, ImageChains}, collect::collect, pass::{Pass, PassId, StateUsage},
+    resource::{Access, Id, Image, State}, schedule::Schedule, sync::{sync, SyncData},
 };
 use either::*;
 use hal::{
-    buffer, command::ClearValue, format::Format, image,
-    queue::{QueueFamily, QueueFamilyId, RawCommandQueue, RawSubmission}, window::Backbuffer,
-    Backend, Device,
+    buffer, command::ClearValue, format::{Aspects, Format}, image, pso::PipelineStage,
+    queue::{QueueFamily, QueueFamilyId, RawCommandQueue, RawSubmission},
+    window::{Backbuffer, PresentMode, SurfaceCapabilities}, Backend, Device,
 };
 
 use smallvec::SmallVec;
 
 use node::{
-    build::NodeBuilder, low::{AnyNode, AnyNodeBuilder}, present::{PresentBuilder, PresentNode},
-    Node,
+    build::NodeBuilder, low::{AnyNode, AnyNodeBuilder},
+    present::{PresentBuilder, PresentError, PresentNode, RecreateError},
+    readback::{ReadbackBuilder, Readout}, Node,
 };
 
 use util::*;
 
+#[cfg(feature = "validation")]
+use node::validate::{Hazard, Resource};
+
+use self::{
+    events::{find_splits, strip_splits, Events, Split},
+    history::{find_transitions, strip_history, History, Transition}, stats::sync_barriers,
+    timings::Timestamps,
+};
+pub use self::{capture::Capture, stats::{FrameStats, NodeStats}, timings::NodeTiming};
+
+mod capture;
+mod events;
+mod history;
+mod stats;
+mod timings;
+
+/// Outcome of the `Graph::run`.
+#[derive(Clone, Debug, Default)]
+pub struct FrameStatus {
+    /// Number of fences used.
+    pub fences: usize,
+
+    /// Presents that failed along with errors.
+    /// Indices are the same as in `Graph::recreate_swapchain`.
+    pub errors: SmallVec<[(usize, PresentError); 4]>,
+}
+
+impl FrameStatus {
+    /// Check if any swapchain needs to be recreated.
+    pub fn is_ok(&self) -> bool {
+        self.errors.is_empty()
+    }
+}
+
 pub struct Graph<B: Backend, D, T, U, I> {
     nodes: Vec<Box<AnyNode<B, D, T>>>,
+    presents: Vec<usize>,
+    direct: Vec<(usize, usize)>,
     schedule: Schedule<SyncData<usize, usize>>,
     semaphores: Vec<B::Semaphore>,
     buffers: Vec<BufferResource<U>>,
-    images: Vec<ImageResource<I>>,
+    images: Vec<GraphImage<B, I>>,
+    buffer_names: Vec<Option<String>>,
+    image_names: Vec<Option<String>>,
+    names: Vec<String>,
+    /// Number of graph barriers each node records itself.
+    barriers: Vec<usize>,
+    stats: FrameStats,
+    frame: u64,
+    frames: usize,
+    timestamps: Option<Timestamps<B>>,
+    events: Option<Events<B>>,
+    history: Option<History<B>>,
+    capture: Option<Capture>,
+
+    // Kept to rebuild nodes when images are recreated.
+    builders: Vec<Option<Box<AnyNodeBuilder<B, D, T, U, I>>>>,
+    buffer_chains: BufferChains,
+    image_chains: ImageChains,
+    pass_images: Vec<Vec<Id<Image>>>,
+    image_usage: Vec<image::Usage>,
+    history_pairs: HistoryPairs,
+    splits: Vec<Split>,
+    transitions: Vec<Transition>,
 }
 
 impl<B, D, T, U, I> Graph<B, D, T, U, I>
@@ -39,10 +98,10 @@ where
     /// Perform graph execution.
     /// Run every node of the graph and submit resulting command buffers to the queues.
     ///
-    /// # Parameters
+    /// Caller must wait for all `fences` of the call made `frames` calls ago
+    /// before calling this function. See `GraphBuilder::with_frames_in_flight`.
     ///
-    /// `frame`     - frame index. This index must be less than `frames` specified in `GraphBuilder::build`
-    ///               Caller must wait for all `fences` from last time this function was called with same `frame` index.
+    /// # Parameters
     ///
     /// `cqueueus`  - function to get `CommandQueue` by `QueueFamilyId` and index.
     ///               `Graph` guarantees that it will submit only command buffers
@@ -57,16 +116,111 @@ where
     ///               This function may not use all fences. Unused fences are left in signalled state.
     ///               If this function needs more fences they will be allocated from `device` and pushed to this `Vec`.
     ///               So it's OK to start with empty `Vec`.
+    ///
+    /// Returns number of fences used and presents that failed.
+    /// Failed presents still signal their fences. Their swapchains should be recreated
+    /// with `recreate_swapchain` before next call.
+    /// If swapchain image of a direct present can't be acquired no node is run
+    /// and images acquired for other direct presents are presented unrendered.
+    /// Graph must be rebuilt then. See `RecreateError::Direct`.
     pub fn run<'a>(
         &mut self,
         command_queues: &mut HashMap<QueueFamilyId, Vec<B::CommandQueue>>,
         device: &mut D,
         aux: &mut T,
         fences: &mut Vec<B::Fence>,
-    ) -> usize {
+    ) -> FrameStatus
+    where
+        U: Borrow<B::Buffer>,
+        I: Borrow<B::Image>,
+    {
         let mut fence_index = 0;
+        let mut errors = SmallVec::new();
 
         profile!("Graph::run");
+        span!("run", frame = self.frame);
+        let start = Instant::now();
+        self.stats.clear();
+
+        // Acquire swapchain images of direct presents before nodes write them.
+        let mut acquired = SmallVec::<[_; 4]>::new();
+        for &(present, writer) in &self.direct {
+            match self.nodes[present].present().unwrap().acquire() {
+                Ok(semaphore) => acquired.push((present, writer, semaphore)),
+                Err(err) => {
+                    let index = self.presents.iter().position(|&p| p == present).unwrap();
+                    errors.push((index, err));
+                }
+            }
+        }
+
+        if !errors.is_empty() {
+            // Graph with direct presents must be rebuilt anyway so nodes aren't run.
+            // Images acquired for other presents are presented unrendered
+            // or their swapchains would run out of images.
+            for (present, _, semaphore) in acquired {
+                let qid = self
+                    .schedule
+                    .iter()
+                    .flat_map(|family| family.iter())
+                    .find(|queue| queue.iter().any(|(_, sub)| sub.pass().0 == present))
+                    .unwrap()
+                    .id();
+                let queue = command_queues
+                    .get_mut(&qid.family())
+                    .unwrap()
+                    .get_mut(qid.index())
+                    .unwrap();
+                let node = self.nodes[present].present().unwrap();
+                if let Err(err) = node.present_unrendered(semaphore, queue) {
+                    let index = self.presents.iter().position(|&p| p == present).unwrap();
+                    errors.push((index, err));
+                }
+            }
+            self.stats.cpu = start.elapsed();
+            self.frame += 1;
+            return FrameStatus { fences: 0, errors };
+        }
+
+        // Nodes see history images in the order of the run.
+        let parity = self.history.as_ref().map_or(0, History::parity);
+        self.history_pairs.parity.store(parity, Ordering::Release);
+
+        // Resources nodes see this frame. Swapchain images are the ones just acquired.
+        let buffers = self
+            .buffers
+            .iter()
+            .map(|resource| resource.buffer.borrow())
+            .collect::<Vec<&B::Buffer>>();
+        let mut images = self
+            .images
+            .iter()
+            .map(|image| match *image {
+                Left(ref resource) => resource.image.borrow(),
+                Right(ref backbuffer) => {
+                    &backbuffer.images[backbuffer.index.load(Ordering::Acquire)]
+                }
+            })
+            .collect::<Vec<&B::Image>>();
+        if parity == 1 {
+            for &(current, previous) in &self.history_pairs.pairs {
+                images.swap(current.index() as usize, previous.index() as usize);
+            }
+        }
+        let slot = (self.frame % self.frames as u64) as usize;
+
+        let frame = self.frame;
+        if let Some(ref mut capture) = self.capture {
+            let timestamps = self.timestamps.as_mut();
+            if let Some((done, intervals)) = timestamps.and_then(|t| t.poll_intervals(device)) {
+                capture.gpu(done, intervals);
+            }
+            capture.begin_frame(frame, start);
+        }
+        let query = self
+            .timestamps
+            .as_mut()
+            .map(|timestamps| timestamps.next_frame(frame));
 
         for family in self.schedule.iter() {
             profile!("Family");
@@ -75,6 +229,7 @@ where
                 profile!("Queue");
 
                 let qid = queue.id();
+                span!("queue", family = ?qid.family(), queue = qid.index());
                 let command_queue = command_queues
                     .get_mut(&qid.family())
                     .unwrap()
@@ -82,8 +237,22 @@ where
                     .unwrap();
                 for (sid, submission) in queue.iter() {
                     profile!("Node");
+                    span!(
+                        "node",
+                        node = self.names[submission.pass().0].as_str(),
+                        family = ?qid.family(),
+                        queue = qid.index(),
+                        submission = sid.index(),
+                        frame = self.frame
+                    );
 
                     let ref mut node = self.nodes[submission.pass().0];
+                    let acquire = acquired
+                        .iter()
+                        .find(|&&(_, writer, _)| writer == submission.pass().0)
+                        .map(|&(_, _, ref semaphore)| {
+                            (semaphore, PipelineStage::COLOR_ATTACHMENT_OUTPUT)
+                        });
 
                     let fence = if sid.index() == queue.len() - 1 {
                         while fences.len() <= fence_index {
@@ -96,19 +265,411 @@ where
                         None
                     };
 
-                    node.run(
+                    // Split barriers are waited and set within the node's submission
+                    // and history images are transitioned there too.
+                    // Timestamps are written right around the node's own commands.
+                    let mut before = SmallVec::<[&B::CommandBuffer; 4]>::new();
+                    let mut after = SmallVec::<[&B::CommandBuffer; 4]>::new();
+                    let mut barriers = self.barriers[submission.pass().0];
+                    if let Some(ref events) = self.events {
+                        if let Some((cbuf, count)) = events.wait(submission.pass().0) {
+                            before.push(cbuf);
+                            barriers += count;
+                        }
+                    }
+                    if let Some(ref history) = self.history {
+                        if let Some((cbuf, count)) = history.transition(submission.pass().0) {
+                            before.push(cbuf);
+                            barriers += count;
+                        }
+                    }
+                    if let (Some(timestamps), Some(query)) = (self.timestamps.as_ref(), query) {
+                        before.push(timestamps.begin(query, submission.pass().0));
+                        after.push(timestamps.end(query, submission.pass().0));
+                    }
+                    if let Some(ref events) = self.events {
+                        after.extend(events.set(submission.pass().0));
+                    }
+                    let resources = FrameInfo::new(slot, &buffers, &images, &before, &after);
+
+                    // Presents wait for acquired swapchain image unless presenting directly
+                    // and signal semaphore the presentation waits for.
+                    let direct = node.present().map(|present| present.is_direct());
+
+                    let node_start = Instant::now();
+                    let result = node.run(
+                        &resources,
                         submission.sync(),
                         command_queue,
                         &mut self.semaphores,
+                        acquire,
                         fence,
                         device,
                         aux,
                     );
+                    let cpu = node_start.elapsed();
+                    self.stats.submit(barriers);
+                    if fence.is_some() && node.readback().is_some() {
+                        // Readback signals the graph fence with separate submission.
+                        self.stats.submit(0);
+                    }
+                    let (waits, signals) = match (direct, &result) {
+                        (_, &Err(PresentError::Acquire)) => (0, 0),
+                        (Some(false), _) => (1, 1),
+                        (Some(true), _) => (0, 1),
+                        (None, _) => (acquire.is_some() as usize, 0),
+                    };
+                    self.stats.node(
+                        NodeId(submission.pass()),
+                        submission.sync(),
+                        waits,
+                        signals,
+                        cpu,
+                    );
+                    if let Some(ref mut capture) = self.capture {
+                        capture.node(
+                            submission.pass().0,
+                            qid.family(),
+                            qid.index(),
+                            submission.sync(),
+                            node_start,
+                            cpu,
+                        );
+                    }
+
+                    let index = submission.pass().0;
+                    if let Some(present) = self.presents.iter().position(|&p| p == index) {
+                        if let Err(err) = result {
+                            errors.push((present, err));
+                        }
+                    }
+                }
+            }
+        }
+
+        for (present, _, semaphore) in acquired {
+            self.nodes[present].present().unwrap().acquired(semaphore);
+        }
+
+        if let Some(ref mut history) = self.history {
+            history.swap();
+        }
+
+        self.stats.fences = fence_index;
+        self.stats.cpu = start.elapsed();
+        self.frame += 1;
+
+        FrameStatus {
+            fences: fence_index,
+            errors,
+        }
+    }
+
+    /// Recreate swapchain of the present.
+    /// Only the swapchain of the present is recreated. The rest of the graph is left intact.
+    /// Returns `RecreateError::Direct` for direct presents. Graph must be rebuilt instead.
+    /// Presented image is scaled to the new backbuffer extent. See `PresentBuilder::with_scaling`.
+    ///
+    /// # Parameters
+    ///
+    /// `present`       - index of the `PresentBuilder` passed to `GraphBuilder::build`.
+    ///
+    /// `surface`       - surface the present was built with.
+    ///
+    /// `capabilities`  - current capabilities of the surface.
+    ///                   Extent of the backbuffer is `current_extent` if reported.
+    ///                   Otherwise extent of the surface clamped to `extents`.
+    ///
+    /// `device`        - `Device<B>` implementation. `B::Device` or wrapper.
+    ///
+    /// Caller must wait for all fences from previous `run` calls.
+    pub fn recreate_swapchain(
+        &mut self,
+        present: usize,
+        surface: &mut B::Surface,
+        capabilities: &SurfaceCapabilities,
+        device: &mut D,
+    ) -> Result<(), RecreateError>
+    where
+        I: Borrow<B::Image>,
+    {
+        let node = self.nodes[self.presents[present]].present().unwrap();
+        let index = node.image().0.index() as usize;
+        let resource = match self.images[index] {
+            Left(ref resource) => resource,
+            Right(_) => return Err(RecreateError::Direct),
+        };
+        node.recreate_swapchain(surface, capabilities, resource, device)
+    }
+
+    /// Change present mode of the swapchain.
+    /// Only the swapchain of the present is recreated. The rest of the graph is left intact.
+    /// Returns `RecreateError::Direct` for direct presents.
+    /// Returns present mode actually used, which may differ from requested
+    /// if the surface doesn't support it. `PresentMode::Fifo` is used unless
+    /// supported modes were given with `PresentBuilder::with_present_modes`.
+    ///
+    /// # Parameters
+    ///
+    /// `present`   - index of the `PresentBuilder` passed to `GraphBuilder::build`.
+    ///
+    /// `surface`   - surface the present was built with.
+    ///
+    /// `mode`      - requested present mode.
+    ///
+    /// `device`    - `Device<B>` implementation. `B::Device` or wrapper.
+    ///
+    /// Caller must wait for all fences from previous `run` calls.
+    pub fn set_present_mode(
+        &mut self,
+        present: usize,
+        surface: &mut B::Surface,
+        mode: PresentMode,
+        device: &mut D,
+    ) -> Result<PresentMode, RecreateError>
+    where
+        I: Borrow<B::Image>,
+    {
+        let node = self.nodes[self.presents[present]].present().unwrap();
+        let index = node.image().0.index() as usize;
+        let resource = match self.images[index] {
+            Left(ref resource) => resource,
+            Right(_) => return Err(RecreateError::Direct),
+        };
+        node.set_present_mode(mode, surface, resource, device)
+    }
+
+    /// Recreate graph images with new kinds. E.g. to follow the size of the window.
+    /// Nodes using the images are rebuilt so their views, framebuffers and descriptor sets
+    /// refer to the new images. Presents copying the images record their commands again.
+    /// Content of recreated images is undefined.
+    /// Directly presented images can't be recreated. Graph must be rebuilt instead.
+    ///
+    /// # Parameters
+    ///
+    /// `images`    - ids of the images along with their new kinds.
+    ///
+    /// `families`  - `Iterator` of `B::QueueFamily`s the graph was built with.
+    ///
+    /// `image`     - function to create images. Same as passed to `GraphBuilder::build`.
+    ///
+    /// `device`    - `Device<B>` implementation. `B::Device` or wrapper.
+    ///
+    /// `aux`       - auxiliary data that `Node`s use.
+    ///
+    /// Returns replaced images. Caller must wait for all fences from previous `run` calls.
+    pub fn recreate_images<F, Y>(
+        &mut self,
+        images: &[(ImageId, image::Kind)],
+        families: F,
+        mut image: Y,
+        device: &mut D,
+        aux: &mut T,
+    ) -> Vec<(ImageId, I)>
+    where
+        F: IntoIterator,
+        F::Item: Borrow<B::QueueFamily>,
+        Y: FnMut(image::Kind, Format, image::Usage, &mut D, &mut T) -> I,
+        U: Borrow<B::Buffer>,
+        I: Borrow<B::Image>,
+    {
+        let families = families.into_iter().collect::<Vec<_>>();
+        let families = families.iter().map(Borrow::borrow).collect::<Vec<_>>();
+
+        let mut replaced = Vec::new();
+        for &(id, kind) in images {
+            let index = id.0.index() as usize;
+            let name = label("image", index, &self.image_names[index]);
+            let (format, clear) = match self.images[index] {
+                Left(ref resource) => (resource.format, resource.clear),
+                Right(_) => panic!("Directly presented {} can't be recreated", name),
+            };
+            let usage = self.image_usage[index];
+            trace!("Recreate {} {:?} {:?} with {:?}", name, kind, format, usage);
+            let resource = Left(ImageResource {
+                kind,
+                format,
+                clear,
+                image: image(kind, format, usage, device, aux),
+            });
+            if let Left(old) = replace(&mut self.images[index], resource) {
+                replaced.push((id, old.image));
+            }
+        }
+
+        // Nodes using either image of the history pair see both.
+        let mut ids = images.iter().map(|&(id, _)| id.0).collect::<Vec<_>>();
+        for &(current, previous) in &self.history_pairs.pairs {
+            if ids.contains(&current) || ids.contains(&previous) {
+                ids.push(current);
+                ids.push(previous);
+            }
+        }
+
+        {
+            let image_refs = self.images.iter().collect::<Vec<_>>();
+
+            for family in self.schedule.iter() {
+                for queue in family.iter() {
+                    for (sid, submission) in queue.iter() {
+                        let index = submission.pass().0;
+                        let ref node_images = self.pass_images[index];
+                        if !node_images.iter().any(|id| ids.contains(id)) {
+                            continue;
+                        }
+                        trace!("Rebuild node {}", self.names[index]);
+
+                        if let Some(present) = self.nodes[index].present() {
+                            let image = present.image().0.index() as usize;
+                            let resource = self.images[image].as_ref().left().unwrap();
+                            present.image_recreated(resource, device);
+                            continue;
+                        }
+
+                        let builder = self.builders[index]
+                            .as_ref()
+                            .and_then(|builder| builder.alternate())
+                            .unwrap_or_else(|| {
+                                panic!("Node {} can't be rebuilt", self.names[index])
+                            });
+                        let mut sync = strip_splits(&self.splits, index, submission.sync());
+                        strip_history(
+                            &self.history_pairs.pairs,
+                            node_images,
+                            submission,
+                            &mut sync,
+                        );
+                        let family = find_family::<B, _>(families.iter().cloned(), sid.family());
+
+                        let node = builder.build(
+                            submission,
+                            &sync,
+                            &self.buffer_chains,
+                            &self.buffers,
+                            &self.image_chains,
+                            &image_refs,
+                            &self.history_pairs,
+                            family,
+                            device,
+                            aux,
+                        );
+                        replace(&mut self.nodes[index], node).dispose(device, aux);
+                    }
                 }
             }
         }
 
-        fence_index
+        // Commands of the graph itself refer to images too.
+        if let Some(events) = self.events.take() {
+            events.dispose(device);
+            self.events = Some(Events::new(&self.splits, &self.buffers, &self.images, device));
+        }
+        if let Some(history) = self.history.take() {
+            history.dispose(device);
+            self.history = Some(History::new(&self.transitions, &self.images, device));
+        }
+
+        replaced
+    }
+
+    /// Take latest data copied by the readback node.
+    /// Data of a frame becomes available once its commands complete.
+    /// Data older than returned is discarded.
+    /// Returns `None` if no new data is available yet.
+    ///
+    /// # Parameters
+    ///
+    /// `node`      - id returned by `GraphBuilder::add_readback`.
+    ///
+    /// `device`    - `Device<B>` implementation. `B::Device` or wrapper.
+    pub fn readback(&mut self, node: NodeId, device: &mut D) -> Option<Readout> {
+        self.nodes[(node.0).0]
+            .readback()
+            .expect("Not a readback node")
+            .poll(device)
+    }
+
+    /// Statistics of the last `run` call.
+    pub fn last_frame_stats(&self) -> &FrameStats {
+        &self.stats
+    }
+
+    /// Hazards found by validators of the nodes.
+    /// Hazards are labeled with names of the node builders and names of the resources.
+    /// See `validate::Validator`.
+    #[cfg(feature = "validation")]
+    pub fn hazards(&self) -> Vec<Hazard> {
+        self.nodes
+            .iter()
+            .zip(&self.names)
+            .flat_map(|(node, name)| {
+                node.hazards().iter().map(move |hazard| {
+                    let resource_name = match hazard.resource {
+                        Resource::Buffer(id) => self.buffer_name(id),
+                        Resource::Image(id) => self.image_name(id),
+                        Resource::UnknownBuffer | Resource::UnknownImage => None,
+                    };
+                    Hazard {
+                        node: name.clone(),
+                        resource_name: resource_name.map(str::to_owned),
+                        ..hazard.clone()
+                    }
+                })
+            })
+            .collect()
+    }
+
+    /// Name given to the buffer at creation.
+    /// See `GraphBuilder::create_named_buffer`.
+    pub fn buffer_name(&self, id: BufferId) -> Option<&str> {
+        self.buffer_names[id.0.index() as usize]
+            .as_ref()
+            .map(String::as_str)
+    }
+
+    /// Name given to the image at creation.
+    /// See `GraphBuilder::create_named_image`.
+    pub fn image_name(&self, id: ImageId) -> Option<&str> {
+        self.image_names[id.0.index() as usize]
+            .as_ref()
+            .map(String::as_str)
+    }
+
+    /// Name of the node. See `NodeDesc::name`.
+    pub fn node_name(&self, node: NodeId) -> &str {
+        &self.names[(node.0).0]
+    }
+
+    /// Get GPU time spent by each node in the latest frame which commands completed.
+    /// Timings of older frames are discarded.
+    /// Returns `None` if timings are not collected or no new frame completed yet.
+    /// See `GraphBuilder::with_gpu_timings`.
+    pub fn gpu_timings(&mut self, device: &mut D) -> Option<Vec<NodeTiming>> {
+        self.timestamps
+            .as_mut()
+            .and_then(|timestamps| timestamps.poll(device))
+    }
+
+    /// Start capturing frames for trace export.
+    /// Previous capture is discarded.
+    /// GPU timings are captured only if enabled with `GraphBuilder::with_gpu_timings`.
+    /// `gpu_timings` should not be called while capturing as it discards timings
+    /// the capture would receive.
+    pub fn start_capture(&mut self) {
+        self.capture = Some(Capture::new(self.names.clone()));
+    }
+
+    /// Stop capturing and return captured frames.
+    /// GPU timings are attached to frames which commands completed.
+    /// Wait for the fences of the last frame before calling this to get timings of every frame.
+    /// Returns `None` if capture wasn't started.
+    pub fn stop_capture(&mut self, device: &mut D) -> Option<Capture> {
+        let mut capture = self.capture.take()?;
+        let timestamps = self.timestamps.as_mut();
+        if let Some((frame, intervals)) = timestamps.and_then(|t| t.poll_intervals(device)) {
+            capture.gpu(frame, intervals);
+        }
+        Some(capture)
     }
 
     /// Dispose of the graph.
@@ -116,6 +677,15 @@ where
         for node in self.nodes {
             node.dispose(device, aux);
         }
+        if let Some(timestamps) = self.timestamps {
+            timestamps.dispose(device);
+        }
+        if let Some(events) = self.events {
+            events.dispose(device);
+        }
+        if let Some(history) = self.history {
+            history.dispose(device);
+        }
     }
 }
 
@@ -123,6 +693,14 @@ pub struct GraphBuilder<B: Backend, D, T, U, I> {
     nodes: Vec<Option<Box<AnyNodeBuilder<B, D, T, U, I>>>>,
     buffers: Vec<u64>,
     images: Vec<(image::Kind, Format, Option<ClearValue>)>,
+    buffer_names: Vec<Option<String>>,
+    image_names: Vec<Option<String>>,
+    history: Vec<(ImageId, ImageId)>,
+    /// Readback nodes along with resources they read back.
+    readbacks: Vec<(PassId, Either<ImageId, BufferId>)>,
+    frames: usize,
+    timings: Option<(f32, usize)>,
+    split_barriers: bool,
 }
 
 impl<B, D, T, U, I> GraphBuilder<B, D, T, U, I>
@@ -138,12 +716,59 @@ where
             nodes: Vec::new(),
             buffers: Vec::new(),
             images: Vec::new(),
+            buffer_names: Vec::new(),
+            image_names: Vec::new(),
+            history: Vec::new(),
+            readbacks: Vec::new(),
+            frames: 3,
+            timings: None,
+            split_barriers: false,
         }
     }
 
+    /// Set maximum number of frames in flight. `3` by default.
+    /// Caller of `Graph::run` must wait for the fences of the call made `frames` calls ago.
+    /// Nodes reuse per-frame resources after that many frames. See `FrameInfo::slot`.
+    pub fn with_frames_in_flight(mut self, frames: usize) -> Self {
+        assert!(frames > 0);
+        self.frames = frames;
+        self
+    }
+
+    /// Collect GPU time spent by each node with timestamp query.
+    /// See `Graph::gpu_timings`.
+    ///
+    /// # Parameters
+    ///
+    /// `timestamp_period`  - nanoseconds per timestamp tick as reported by the device limits.
+    ///
+    /// `frames`            - number of frames in flight to keep query for.
+    pub fn with_gpu_timings(mut self, timestamp_period: f32, frames: usize) -> Self {
+        assert!(frames > 0);
+        self.timings = Some((timestamp_period, frames));
+        self
+    }
+
+    /// Split barriers between nodes on the same queue with events
+    /// when other nodes are submitted in between.
+    /// The graph sets an event after the producer and waits for it before the consumer
+    /// so that the GPU may overlap unrelated nodes instead of draining at the barrier.
+    /// Such barriers are not passed to the consumer node.
+    pub fn with_split_barriers(mut self) -> Self {
+        self.split_barriers = true;
+        self
+    }
+
     /// Create new buffer owned by graph.
     pub fn create_buffer(&mut self, size: u64) -> BufferId {
+        self.create_named_buffer(None, size)
+    }
+
+    /// Create new buffer owned by graph.
+    /// Name is used in logs and error messages.
+    pub fn create_named_buffer(&mut self, name: Option<&str>, size: u64) -> BufferId {
         self.buffers.push(size);
+        self.buffer_names.push(name.map(str::to_owned));
         BufferId(Id::new(self.buffers.len() as u32 - 1))
     }
 
@@ -153,11 +778,62 @@ where
         kind: image::Kind,
         format: Format,
         clear: Option<ClearValue>,
+    ) -> ImageId {
+        self.create_named_image(None, kind, format, clear)
+    }
+
+    /// Create new image owned by graph.
+    /// Name is used in logs and error messages.
+    pub fn create_named_image(
+        &mut self,
+        name: Option<&str>,
+        kind: image::Kind,
+        format: Format,
+        clear: Option<ClearValue>,
     ) -> ImageId {
         self.images.push((kind, format, clear));
+        self.image_names.push(name.map(str::to_owned));
         ImageId(Id::new(self.images.len() as u32 - 1))
     }
 
+    /// Create double-buffered image owned by graph for temporal effects.
+    /// Returns id of the image for the current frame and id of the image for the previous frame.
+    /// The graph swaps the images after each `Graph::run`, so the previous frame image
+    /// contains what was written to the current frame image during the last run.
+    /// Nodes using the images are built once and get both images of the pair
+    /// through `ImageInfo::frames`, same as for directly presented images.
+    /// Those nodes must run on the same queue. Presents and readbacks can't use the images.
+    pub fn create_history_image(
+        &mut self,
+        kind: image::Kind,
+        format: Format,
+        clear: Option<ClearValue>,
+    ) -> (ImageId, ImageId) {
+        self.create_named_history_image(None, kind, format, clear)
+    }
+
+    /// Create double-buffered image owned by graph for temporal effects.
+    /// Name is used in logs and error messages.
+    /// See `GraphBuilder::create_history_image`.
+    pub fn create_named_history_image(
+        &mut self,
+        name: Option<&str>,
+        kind: image::Kind,
+        format: Format,
+        clear: Option<ClearValue>,
+    ) -> (ImageId, ImageId) {
+        let current = self.create_named_image(name, kind, format, clear);
+        let previous_name = name.map(|name| format!("{} (previous)", name));
+        let previous = self.create_named_image(
+            previous_name.as_ref().map(String::as_str),
+            kind,
+            format,
+            None,
+        );
+        self.history.push((current, previous));
+        (current, previous)
+    }
+
     /// Add node to the graph.
     pub fn add_node<N>(&mut self, builder: NodeBuilder<N>) -> NodeId
     where
@@ -167,11 +843,47 @@ where
         NodeId(PassId(self.nodes.len() - 1))
     }
 
-    /// Build `Graph`.
+    /// Add node that reads image or buffer back to the host.
+    /// Readback depends on nodes added before it that write the resource.
+    /// See `Graph::readback`.
+    pub fn add_readback(&mut self, builder: ReadbackBuilder) -> NodeId {
+        let id = PassId(self.nodes.len());
+        self.readbacks.push((id, builder.source()));
+        self.nodes.push(Some(Box::new(builder)));
+        NodeId(id)
+    }
+
+    /// Build `Graph` without presents.
+    /// Results are exposed through readback nodes or images and buffers created by callbacks.
+    /// See `GraphBuilder::add_readback`.
     ///
     /// # Parameters
     ///
-    /// `frames`        - maximum number of frames `Graph` will render simultaneously.
+    /// `families`      - `Iterator` of `B::QueueFamily`s.
+    ///
+    /// `device`    - `Device<B>` implementation. `B::Device` or wrapper.
+    ///
+    /// `aux`       - auxiliary data that `Node`s use.
+    pub fn build_headless<F, X, Y>(
+        self,
+        families: F,
+        buffer: X,
+        image: Y,
+        device: &mut D,
+        aux: &mut T,
+    ) -> Graph<B, D, T, U, I>
+    where
+        F: IntoIterator,
+        F::Item: Borrow<B::QueueFamily>,
+        X: FnMut(u64, buffer::Usage, &mut D, &mut T) -> U,
+        Y: FnMut(image::Kind, Format, image::Usage, &mut D, &mut T) -> I,
+    {
+        self.build(families, buffer, image, empty::<PresentBuilder<B>>(), device, aux)
+    }
+
+    /// Build `Graph`.
+    ///
+    /// # Parameters
     ///
     /// `families`      - `Iterator` of `B::QueueFamily`s.
     ///
@@ -195,33 +907,79 @@ where
         P: IntoIterator<Item = PresentBuilder<'a, B>>,
     {
         trace!("Build Graph");
+        span!("build");
         use chain::{build, pass::Pass};
 
         let families = families.into_iter().collect::<Vec<_>>();
         let families = families.iter().map(Borrow::borrow).collect::<Vec<_>>();
 
         let mut nodes: Vec<Option<Box<AnyNodeBuilder<B, D, T, U, I> + 'a>>> = self.nodes;
-        let present_dependencies: Vec<_> = (0..nodes.len()).map(PassId).collect();
-
-        for present in presents {
-            nodes.push(Some(Box::new(
-                present.with_dependencies(present_dependencies.clone()),
-            )));
-        }
+        let timings = self.timings;
+        let frames = self.frames;
+        let split_barriers = self.split_barriers;
+        let buffer_names = self.buffer_names;
+        let image_names = self.image_names;
+        let readbacks = self.readbacks;
+        let history = self
+            .history
+            .iter()
+            .map(|&(current, previous)| (current.0, previous.0))
+            .collect::<Vec<_>>();
 
         trace!("Schedule nodes execution");
-        let passes: Vec<Pass> = nodes
+        let mut passes: Vec<Pass> = nodes
             .iter()
             .enumerate()
             .map(|(i, b)| b.as_ref().unwrap().pass(PassId(i), &families))
             .collect();
 
+        // Readbacks wait for nodes writing their resources before them.
+        for &(id, source) in &readbacks {
+            let dependencies = match source {
+                Left(image) => producers(&passes[..id.0], image),
+                Right(buffer) => buffer_producers(&passes[..id.0], buffer),
+            };
+            for dependency in dependencies {
+                if !passes[id.0].dependencies.contains(&dependency) {
+                    passes[id.0].dependencies.push(dependency);
+                }
+            }
+        }
+
+        // Presents wait only for nodes producing their images.
+        let mut present_nodes = Vec::new();
+        for present in presents {
+            let dependencies = producers(&passes, present.id);
+            let index = nodes.len();
+            let mut present = present.with_dependencies(dependencies);
+            present.prepare_swapchain(device);
+            let builder: Box<AnyNodeBuilder<B, D, T, U, I> + 'a> = Box::new(present);
+            passes.push(builder.pass(PassId(index), &families));
+            present_nodes.push(index);
+            nodes.push(Some(builder));
+        }
+
+        let pass_buffers = passes
+            .iter()
+            .map(|pass| pass.buffers.keys().cloned().collect::<Vec<_>>())
+            .collect::<Vec<_>>();
+        let pass_images = passes
+            .iter()
+            .map(|pass| pass.images.keys().cloned().collect::<Vec<_>>())
+            .collect::<Vec<_>>();
+
         let chains = collect(passes, |qid| {
             find_family::<B, _>(families.iter().cloned(), qid).max_queues()
         });
 
         trace!("Scheduled nodes execution {:#?}", chains);
 
+        trace!("Create backbuffers");
+        let mut backbuffers = nodes
+            .iter_mut()
+            .filter_map(|node| node.as_mut().unwrap().backbuffer(&chains.images, device))
+            .collect::<HashMap<_, _>>();
+
         trace!("Allocate buffers");
         let buffers = self
             .buffers
@@ -232,6 +990,12 @@ where
                     .buffers
                     .get(&Id::new(index as u32))
                     .map_or(buffer::Usage::empty(), |chain| chain.usage());
+                trace!(
+                    "Allocate {} of size {} with {:?}",
+                    label("buffer", index, &buffer_names[index]),
+                    size,
+                    usage
+                );
                 BufferResource {
                     size,
                     buffer: buffer(size, usage, device, aux),
@@ -240,26 +1004,85 @@ where
             .collect::<Vec<_>>();
 
         trace!("Allocate images");
+        let mut image_usage = vec![image::Usage::empty(); self.images.len()];
         let images = self
             .images
             .iter()
             .enumerate()
             .map(|(index, &(kind, format, clear))| {
-                let usage = chains
-                    .images
-                    .get(&Id::new(index as u32))
-                    .map_or(image::Usage::empty(), |chain| chain.usage());
-                ImageResource {
+                let name = label("image", index, &image_names[index]);
+                let id = Id::new(index as u32);
+                let is_history = history
+                    .iter()
+                    .any(|&(current, previous)| current == id || previous == id);
+                let read_back = readbacks
+                    .iter()
+                    .any(|&(_, source)| source == Left(ImageId(id)));
+                if let Some(mut backbuffer) = backbuffers.remove(&ImageId(id)) {
+                    trace!("Use swapchain images for directly presented {}", name);
+                    assert!(!is_history, "History {} can't be presented directly", name);
+                    assert!(!read_back, "Directly presented {} can't be read back", name);
+                    assert_eq!(
+                        backbuffer.kind.extent(),
+                        kind.extent(),
+                        "Directly presented {} must have extent of the surface",
+                        name
+                    );
+                    assert_eq!(
+                        backbuffer.format, format,
+                        "Directly presented {} must have format of the swapchain",
+                        name
+                    );
+                    backbuffer.clear = clear;
+                    return Right(backbuffer);
+                }
+
+                assert!(!(is_history && read_back), "History {} can't be read back", name);
+                let aspects = format.surface_desc().aspects;
+                assert!(
+                    !(read_back && aspects.contains(Aspects::DEPTH | Aspects::STENCIL)),
+                    "Depth stencil {} can't be read back",
+                    name
+                );
+
+                // History images are used through both ids.
+                let usage = history
+                    .iter()
+                    .filter(|&&(current, previous)| current == id || previous == id)
+                    .flat_map(|&(current, previous)| vec![current, previous])
+                    .chain(once(id))
+                    .filter_map(|id| chains.images.get(&id))
+                    .fold(image::Usage::empty(), |usage, chain| usage | chain.usage());
+                trace!("Allocate {} {:?} {:?} with {:?}", name, kind, format, usage);
+                image_usage[index] = usage;
+                Left(ImageResource {
                     kind,
                     format,
                     clear,
                     image: image(kind, format, usage, device, aux),
-                }
+                })
             })
             .collect::<Vec<_>>();
 
         let mut built_nodes: Vec<Option<Box<AnyNode<B, D, T>>>> =
             (0..nodes.len()).map(|_| None).collect();
+        let mut builders: Vec<Option<Box<AnyNodeBuilder<B, D, T, U, I>>>> =
+            (0..nodes.len()).map(|_| None).collect();
+        let mut barriers = vec![0; nodes.len()];
+
+        // Nodes get history images along with their partners and swap them by the parity.
+        let image_refs = images.iter().collect::<Vec<_>>();
+        let history_pairs = HistoryPairs {
+            pairs: history.clone(),
+            parity: Arc::new(AtomicUsize::new(0)),
+        };
+
+        // First nodes to write directly presented images.
+        let mut writers = HashMap::new();
+
+        // Names and queue families of nodes.
+        let mut node_info: Vec<Option<(String, QueueFamilyId)>> =
+            (0..nodes.len()).map(|_| None).collect();
 
         trace!("Synchronize");
         let mut semaphores = GenId::new();
@@ -269,6 +1092,26 @@ where
         });
         trace!("Schedule: {:#?}", schedule);
 
+        let splits = if split_barriers {
+            // Barriers of history images are recorded by the graph.
+            let excluded = (0..images.len())
+                .filter(|&index| images[index].is_right())
+                .map(|index| Id::new(index as u32))
+                .chain(
+                    history
+                        .iter()
+                        .flat_map(|&(current, previous)| vec![current, previous]),
+                )
+                .collect::<Vec<_>>();
+            find_splits(&schedule, &pass_buffers, &pass_images, &excluded)
+        } else {
+            Vec::new()
+        };
+        trace!("Split barriers: {:#?}", splits);
+
+        let transitions = find_transitions(&history, &schedule, &pass_images, &chains.images);
+        trace!("History transitions: {:#?}", transitions);
+
         trace!("Build nodes");
         for family in schedule.iter() {
             trace!("For family {:#?}", family);
@@ -276,15 +1119,38 @@ where
                 trace!("For queue {:#?}", queue.id());
                 for (sid, submission) in queue.iter() {
                     trace!("For submission {:#?}", sid);
+                    for &id in &pass_images[submission.pass().0] {
+                        if images[id.index() as usize].is_right() && submission.image(id) == 0 {
+                            writers.insert(id, submission.pass().0);
+                        }
+                    }
+
                     let builder = nodes[submission.pass().0].take().unwrap();
                     trace!("Build node {}", builder.name());
+                    span!(
+                        "build_node",
+                        node = builder.name(),
+                        family = ?sid.family(),
+                        submission = sid.index()
+                    );
+                    node_info[submission.pass().0] =
+                        Some((builder.name().to_owned(), sid.family()));
+                    let ref node_images = pass_images[submission.pass().0];
+                    let mut sync = strip_splits(&splits, submission.pass().0, submission.sync());
+                    strip_history(&history, node_images, submission, &mut sync);
+                    barriers[submission.pass().0] = sync_barriers(&sync);
+                    let family = find_family::<B, _>(families.iter().cloned(), sid.family());
+
+                    builders[submission.pass().0] = builder.alternate();
                     let node = builder.build(
                         submission,
+                        &sync,
                         &chains.buffers,
                         &buffers,
                         &chains.images,
-                        &images,
-                        find_family::<B, _>(families.iter().cloned(), sid.family()),
+                        &image_refs,
+                        &history_pairs,
+                        family,
                         device,
                         aux,
                     );
@@ -293,18 +1159,113 @@ where
             }
         }
 
+        let mut built_nodes = built_nodes
+            .into_iter()
+            .map(|node| node.unwrap())
+            .collect::<Vec<_>>();
+
+        let direct = present_nodes
+            .iter()
+            .filter_map(|&index| {
+                let present = built_nodes[index].present().unwrap();
+                if present.is_direct() {
+                    let writer = writers[&present.image().0];
+                    Some((index, writer))
+                } else {
+                    None
+                }
+            })
+            .collect();
+
+        let node_info = node_info
+            .into_iter()
+            .map(|info| info.unwrap())
+            .collect::<Vec<_>>();
+        let names = node_info.iter().map(|&(ref name, _)| name.clone()).collect();
+        let timestamps = timings
+            .map(|(period, frames)| Timestamps::new(period, frames, node_info, device));
+
+        let events = if splits.is_empty() {
+            None
+        } else {
+            Some(Events::new(&splits, &buffers, &images, device))
+        };
+
+        let history = if transitions.is_empty() {
+            None
+        } else {
+            Some(History::new(&transitions, &images, device))
+        };
+
+        let semaphores = semaphores.total();
+
         Graph {
-            nodes: built_nodes.into_iter().map(|node| node.unwrap()).collect(),
+            nodes: built_nodes,
+            presents: present_nodes,
+            direct,
             schedule,
-            semaphores: (0..semaphores.total())
+            semaphores: (0..semaphores)
                 .map(|_| device.create_semaphore())
                 .collect(),
             buffers,
             images,
+            buffer_names,
+            image_names,
+            names,
+            barriers,
+            stats: FrameStats::default(),
+            frame: 0,
+            frames,
+            timestamps,
+            events,
+            history,
+            capture: None,
+            builders,
+            buffer_chains: chains.buffers,
+            image_chains: chains.images,
+            pass_images,
+            image_usage,
+            history_pairs,
+            splits,
+            transitions,
         }
     }
 }
 
+/// Label of the resource for logs and error messages.
+fn label(kind: &str, index: usize, name: &Option<String>) -> String {
+    match *name {
+        Some(ref name) => format!("{} {} '{}'", kind, index, name),
+        None => format!("{} {}", kind, index),
+    }
+}
+
+/// Find passes that write the image.
+fn producers(passes: &[Pass], id: ImageId) -> Vec<PassId> {
+    passes
+        .iter()
+        .filter(|pass| {
+            pass.images
+                .get(&id.0)
+                .map_or(false, |usage| usage.state.access.is_write())
+        })
+        .map(|pass| pass.id)
+        .collect()
+}
+
+/// Find passes that write the buffer.
+fn buffer_producers(passes: &[Pass], id: BufferId) -> Vec<PassId> {
+    passes
+        .iter()
+        .filter(|pass| {
+            pass.buffers
+                .get(&id.0)
+                .map_or(false, |usage| usage.state.access.is_write())
+        })
+        .map(|pass| pass.id)
+        .collect()
+}
+
 struct GenId<T> {
     next: T,
 }
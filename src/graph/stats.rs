@@ -0,0 +1,86 @@
+use std::time::Duration;
+
+use chain::sync::SyncData;
+
+use util::NodeId;
+
+/// CPU time spent by the node in `Graph::run`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct NodeStats {
+    /// Id of the node. See `Graph::node_name`.
+    pub node: NodeId,
+
+    /// Time spent recording and submitting commands of the node.
+    pub cpu: Duration,
+}
+
+/// Statistics of the last `Graph::run`.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct FrameStats {
+    /// Stats of nodes in order they were run.
+    pub nodes: Vec<NodeStats>,
+
+    /// Total time spent in `Graph::run`.
+    pub cpu: Duration,
+
+    /// Number of submissions to the queues.
+    /// Includes submissions readbacks make to signal the graph fence.
+    pub submissions: usize,
+
+    /// Number of semaphores waited by nodes.
+    /// Includes swapchain image acquisition semaphores.
+    pub waits: usize,
+
+    /// Number of semaphores signaled by nodes.
+    /// Includes semaphores presents wait for.
+    pub signals: usize,
+
+    /// Number of buffer and image barriers recorded between graph resources.
+    /// Includes barriers recorded by the graph itself with events and history transitions.
+    pub barriers: usize,
+
+    /// Number of fences used.
+    pub fences: usize,
+}
+
+impl FrameStats {
+    /// Reset stats keeping allocated memory.
+    pub(crate) fn clear(&mut self) {
+        self.nodes.clear();
+        self.cpu = Duration::default();
+        self.submissions = 0;
+        self.waits = 0;
+        self.signals = 0;
+        self.barriers = 0;
+        self.fences = 0;
+    }
+
+    /// Account node run.
+    /// `waits` and `signals` are semaphores used besides ones in `sync`.
+    pub(crate) fn node(
+        &mut self,
+        node: NodeId,
+        sync: &SyncData<usize, usize>,
+        waits: usize,
+        signals: usize,
+        cpu: Duration,
+    ) {
+        self.nodes.push(NodeStats { node, cpu });
+        self.waits += sync.acquire.wait.len() + waits;
+        self.signals += sync.release.signal.len() + signals;
+    }
+
+    /// Account submission with number of barriers recorded in it.
+    pub(crate) fn submit(&mut self, barriers: usize) {
+        self.submissions += 1;
+        self.barriers += barriers;
+    }
+}
+
+/// Number of barriers in the synchronization of the node.
+pub(crate) fn sync_barriers(sync: &SyncData<usize, usize>) -> usize {
+    sync.acquire.buffers.len()
+        + sync.acquire.images.len()
+        + sync.release.buffers.len()
+        + sync.release.images.len()
+}
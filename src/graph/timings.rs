@@ -0,0 +1,241 @@
+use std::{collections::HashMap, ops::Range, time::Duration};
+
+use hal::{
+    command::{CommandBufferFlags, RawCommandBuffer, RawLevel},
+    pool::{CommandPoolCreateFlags, RawCommandPool}, pso::PipelineStage,
+    query::{Query, QueryId, QueryType, ResultFlags},
+    queue::QueueFamilyId, Backend, Device,
+};
+
+use chain::pass::PassId;
+
+use util::NodeId;
+
+/// GPU time spent by the node.
+#[derive(Clone, Debug, PartialEq)]
+pub struct NodeTiming {
+    /// Id of the node.
+    pub node: NodeId,
+
+    /// Name of the node. See `NodeDesc::name`.
+    pub name: String,
+
+    /// Time between start and end of the node's commands.
+    /// Excludes waits for split barriers and history transitions recorded by the graph.
+    pub duration: Duration,
+}
+
+/// Timestamp queries written around commands of every node within its submission.
+pub(crate) struct Timestamps<B: Backend> {
+    period: f32,
+    names: Vec<String>,
+    pool: B::QueryPool,
+    command_pools: HashMap<QueueFamilyId, B::CommandPool>,
+    slots: Vec<Slot<B>>,
+}
+
+/// Command buffers writing timestamps for single frame.
+struct Slot<B: Backend> {
+    begin: Vec<B::CommandBuffer>,
+    end: Vec<B::CommandBuffer>,
+    frame: Option<u64>,
+}
+
+impl<B> Timestamps<B>
+where
+    B: Backend,
+{
+    /// Create queries and command buffers writing them.
+    ///
+    /// # Parameters
+    ///
+    /// `period`    - nanoseconds per timestamp tick.
+    ///
+    /// `frames`    - number of frames timestamps are kept for.
+    ///
+    /// `nodes`     - name and queue family of each node.
+    pub(crate) fn new<D>(
+        period: f32,
+        frames: usize,
+        nodes: Vec<(String, QueueFamilyId)>,
+        device: &mut D,
+    ) -> Self
+    where
+        D: Device<B>,
+    {
+        let count = nodes.len();
+        let pool = device.create_query_pool(QueryType::Timestamp, (frames * count * 2) as u32);
+
+        let mut command_pools = HashMap::new();
+        for &(_, family) in &nodes {
+            command_pools.entry(family).or_insert_with(|| {
+                device.create_command_pool(family, CommandPoolCreateFlags::empty())
+            });
+        }
+
+        let slots = (0..frames)
+            .map(|frame| {
+                let mut begin = Vec::with_capacity(count);
+                let mut end = Vec::with_capacity(count);
+                for (index, &(_, family)) in nodes.iter().enumerate() {
+                    let command_pool = command_pools.get_mut(&family).unwrap();
+                    let mut cbufs = command_pool.allocate(2, RawLevel::Primary);
+                    let start = ((frame * count + index) * 2) as QueryId;
+
+                    let mut cbuf = cbufs.pop().unwrap();
+                    cbuf.begin(CommandBufferFlags::EMPTY, Default::default());
+                    cbuf.reset_query_pool(&pool, start..start + 2);
+                    cbuf.write_timestamp(
+                        PipelineStage::TOP_OF_PIPE,
+                        Query {
+                            pool: &pool,
+                            id: start,
+                        },
+                    );
+                    cbuf.finish();
+                    begin.push(cbuf);
+
+                    let mut cbuf = cbufs.pop().unwrap();
+                    cbuf.begin(CommandBufferFlags::EMPTY, Default::default());
+                    cbuf.write_timestamp(
+                        PipelineStage::BOTTOM_OF_PIPE,
+                        Query {
+                            pool: &pool,
+                            id: start + 1,
+                        },
+                    );
+                    cbuf.finish();
+                    end.push(cbuf);
+                }
+                Slot {
+                    begin,
+                    end,
+                    frame: None,
+                }
+            })
+            .collect();
+
+        Timestamps {
+            period,
+            names: nodes.into_iter().map(|(name, _)| name).collect(),
+            pool,
+            command_pools,
+            slots,
+        }
+    }
+
+    /// Start new frame. Returns slot used for the frame.
+    pub(crate) fn next_frame(&mut self, frame: u64) -> usize {
+        let slot = (frame % self.slots.len() as u64) as usize;
+        self.slots[slot].frame = Some(frame);
+        slot
+    }
+
+    /// Command buffer writing timestamp before commands of the node.
+    pub(crate) fn begin(&self, slot: usize, node: usize) -> &B::CommandBuffer {
+        &self.slots[slot].begin[node]
+    }
+
+    /// Command buffer writing timestamp after commands of the node.
+    pub(crate) fn end(&self, slot: usize, node: usize) -> &B::CommandBuffer {
+        &self.slots[slot].end[node]
+    }
+
+    /// Get timings of the latest frame which queries are available.
+    /// Older frames are discarded.
+    pub(crate) fn poll<D>(&mut self, device: &mut D) -> Option<Vec<NodeTiming>>
+    where
+        D: Device<B>,
+    {
+        let (_, intervals) = self.poll_intervals(device)?;
+        Some(
+            self.names
+                .iter()
+                .zip(intervals)
+                .enumerate()
+                .map(|(node, (name, interval))| {
+                    let nanos = interval.end.saturating_sub(interval.start);
+                    NodeTiming {
+                        node: NodeId(PassId(node)),
+                        name: name.clone(),
+                        duration: Duration::new(
+                            nanos / 1_000_000_000,
+                            (nanos % 1_000_000_000) as u32,
+                        ),
+                    }
+                })
+                .collect(),
+        )
+    }
+
+    /// Get index and per-node timestamps in nanoseconds
+    /// of the latest frame which queries are available.
+    /// Older frames are discarded.
+    pub(crate) fn poll_intervals<D>(&mut self, device: &mut D) -> Option<(u64, Vec<Range<u64>>)>
+    where
+        D: Device<B>,
+    {
+        let count = self.names.len();
+        let mut slots = (0..self.slots.len())
+            .filter(|&slot| self.slots[slot].frame.is_some())
+            .collect::<Vec<_>>();
+        slots.sort_by_key(|&slot| !self.slots[slot].frame.unwrap());
+
+        for (index, &slot) in slots.iter().enumerate() {
+            let start = (slot * count * 2) as QueryId;
+            let mut data = vec![0u8; count * 2 * 8];
+            let available = device
+                .get_query_pool_results(
+                    &self.pool,
+                    start..start + (count * 2) as QueryId,
+                    &mut data,
+                    8,
+                    ResultFlags::BITS_64,
+                )
+                .unwrap_or(false);
+            if !available {
+                continue;
+            }
+
+            let frame = self.slots[slot].frame.unwrap();
+            for &older in &slots[index..] {
+                self.slots[older].frame = None;
+            }
+
+            // Results are read as little endian.
+            let period = self.period as f64;
+            let nanos = data
+                .chunks(8)
+                .map(|bytes| {
+                    let ticks = bytes
+                        .iter()
+                        .rev()
+                        .fold(0u64, |value, &byte| (value << 8) | byte as u64);
+                    (ticks as f64 * period) as u64
+                })
+                .collect::<Vec<_>>();
+
+            return Some((
+                frame,
+                nanos
+                    .chunks(2)
+                    .map(|pair| pair[0]..pair[1])
+                    .collect(),
+            ));
+        }
+
+        None
+    }
+
+    pub(crate) fn dispose<D>(self, device: &mut D)
+    where
+        D: Device<B>,
+    {
+        // Command buffers are freed along with their pools.
+        drop(self.slots);
+        for (_, pool) in self.command_pools {
+            device.destroy_command_pool(pool);
+        }
+        device.destroy_query_pool(self.pool);
+    }
+}
@@ -13,6 +13,12 @@ extern crate smallvec;
 #[cfg(feature = "profile")]
 extern crate flame;
 
+#[cfg(feature = "tracing")]
+extern crate tracing;
+
+#[cfg(feature = "testing")]
+extern crate png;
+
 #[cfg(feature = "profile")]
 macro_rules! profile {
     ($name:tt) => {
@@ -26,10 +32,34 @@ macro_rules! profile {
     ($name:tt) => {};
 }
 
+#[cfg(feature = "tracing")]
+macro_rules! span {
+    ($name:tt $($fields:tt)*) => {
+        let span = ::tracing::trace_span!(target: "xfg", $name $($fields)*);
+        let _guard = span.enter();
+    };
+}
+
+#[cfg(not(feature = "tracing"))]
+macro_rules! span {
+    ($name:tt $($fields:tt)*) => {};
+}
+
+mod descriptors;
 mod graph;
 mod node;
 mod util;
 
-pub use graph::{Graph, GraphBuilder};
-pub use node::{build::NodeBuilder, present, render, Node, NodeDesc};
-pub use util::{Barriers, BufferId, BufferInfo, BufferResource, ImageId, ImageInfo, ImageResource};
+#[cfg(feature = "testing")]
+pub mod testing;
+
+pub use descriptors::{DescriptorPool, Descriptors};
+pub use graph::{Capture, FrameStats, FrameStatus, Graph, GraphBuilder, NodeStats, NodeTiming};
+pub use node::{barriers, build::NodeBuilder, present, readback, render, Node, NodeDesc};
+pub use util::{
+    BackbufferResource, Barriers, BufferId, BufferInfo, BufferResource, FrameInfo, Frames, ImageId,
+    ImageInfo, ImageResource, NodeId, StorageAccess,
+};
+
+#[cfg(feature = "validation")]
+pub use node::validate;
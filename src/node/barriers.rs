@@ -0,0 +1,161 @@
+//! Recording of pipeline barriers batched by stages.
+
+use std::{borrow::Borrow, ops::Range};
+
+use hal::{
+    buffer, command::RawCommandBuffer, format::Aspects, image, memory::{Barrier, Dependencies},
+    pso::PipelineStage, Backend,
+};
+
+use smallvec::SmallVec;
+
+use util::*;
+
+/// Barriers grouped by source and destination stages.
+/// Each group is recorded with single `pipeline_barrier` call.
+/// Barriers of the same resource must not be pushed twice into one batch
+/// as barriers within single call are not ordered.
+pub struct BarrierBatch<'a, B: Backend> {
+    groups: SmallVec<[(Range<PipelineStage>, Vec<Barrier<'a, B>>); 4]>,
+}
+
+impl<'a, B> BarrierBatch<'a, B>
+where
+    B: Backend,
+{
+    /// Create empty batch.
+    pub fn new() -> Self {
+        BarrierBatch {
+            groups: SmallVec::new(),
+        }
+    }
+
+    /// Add barrier for the stages.
+    pub fn push(&mut self, stages: Range<PipelineStage>, barrier: Barrier<'a, B>) {
+        match self.groups.iter().position(|group| group.0 == stages) {
+            Some(index) => self.groups[index].1.push(barrier),
+            None => self.groups.push((stages, vec![barrier])),
+        }
+    }
+
+    /// Add buffer barrier.
+    pub fn buffer(
+        &mut self,
+        barrier: &Range<(buffer::State, PipelineStage)>,
+        target: &'a B::Buffer,
+    ) {
+        self.push(
+            barrier.start.1..barrier.end.1,
+            Barrier::Buffer {
+                states: barrier.start.0..barrier.end.0,
+                target,
+            },
+        );
+    }
+
+    /// Add image barrier for the first mip level and layer.
+    pub fn image(
+        &mut self,
+        barrier: &Range<(image::State, PipelineStage)>,
+        target: &'a B::Image,
+        aspects: Aspects,
+    ) {
+        self.push(
+            barrier.start.1..barrier.end.1,
+            Barrier::Image {
+                states: barrier.start.0..barrier.end.0,
+                target,
+                range: image::SubresourceRange {
+                    aspects,
+                    levels: 0..1,
+                    layers: 0..1,
+                },
+            },
+        );
+    }
+
+    /// Check if batch has no barriers.
+    pub fn is_empty(&self) -> bool {
+        self.groups.is_empty()
+    }
+
+    /// Record barriers into the command buffer. One call per group.
+    pub fn record<C>(self, cbuf: &mut C)
+    where
+        C: RawCommandBuffer<B>,
+    {
+        for (stages, barriers) in self.groups {
+            cbuf.pipeline_barrier(stages, Dependencies::empty(), barriers);
+        }
+    }
+}
+
+impl<'a, B> IntoIterator for BarrierBatch<'a, B>
+where
+    B: Backend,
+{
+    type Item = (Range<PipelineStage>, Vec<Barrier<'a, B>>);
+    type IntoIter = ::smallvec::IntoIter<[Self::Item; 4]>;
+
+    /// Iterate over groups. Useful to record barriers into typed command buffers.
+    fn into_iter(self) -> Self::IntoIter {
+        self.groups.into_iter()
+    }
+}
+
+impl<'a, B> Default for BarrierBatch<'a, B>
+where
+    B: Backend,
+{
+    fn default() -> Self {
+        BarrierBatch::new()
+    }
+}
+
+/// Record either acquire or release barriers of the node for the frame.
+/// Barriers with the same stages are recorded with single call.
+///
+/// # Parameters
+///
+/// `buffers`   - infos of the buffers passed to `Node::build`.
+///
+/// `images`    - infos of the images passed to `Node::build`.
+///
+/// `frame`     - index of the swapchain image for directly presented images.
+///               See `ImageInfo::frame_image`.
+///
+/// `release`   - record release barriers instead of acquire.
+pub fn record_barriers<B, C, U, I>(
+    cbuf: &mut C,
+    buffers: &[BufferInfo<U>],
+    images: &[ImageInfo<I>],
+    frame: usize,
+    release: bool,
+) where
+    B: Backend,
+    C: RawCommandBuffer<B>,
+    U: Borrow<B::Buffer>,
+    I: Borrow<B::Image>,
+{
+    fn pick<S>(barriers: &Barriers<S>, release: bool) -> &Option<Range<(S, PipelineStage)>> {
+        if release {
+            &barriers.release
+        } else {
+            &barriers.acquire
+        }
+    }
+
+    let mut batch = BarrierBatch::<B>::new();
+    for info in buffers {
+        if let Some(ref barrier) = *pick(&info.barriers, release) {
+            batch.buffer(barrier, info.buffer.borrow());
+        }
+    }
+    for info in images {
+        if let Some(ref barrier) = *pick(&info.barriers, release) {
+            let aspects = info.format.surface_desc().aspects;
+            batch.image(barrier, info.frame_image(frame).borrow(), aspects);
+        }
+    }
+    batch.record(cbuf);
+}
@@ -8,9 +8,12 @@ use hal::{
 
 use chain::{
     chain::{BufferChains, ImageChains}, pass::{Pass, PassId, StateUsage},
-    resource::{Access, Buffer, BufferLayout, Image, State}, schedule::Submission, sync::SyncData,
+    resource::{Access, Buffer, BufferLayout, Id, Image, State}, schedule::Submission,
+    sync::SyncData,
 };
 
+use either::{Left, Right};
+
 use node::{low::*, Barriers, BufferInfo, ImageInfo, Node, NodeDesc};
 use util::*;
 
@@ -112,19 +115,59 @@ where
         pass
     }
 
+    fn alternate(&self) -> Option<Box<AnyNodeBuilder<B, D, T, U, I>>> {
+        Some(Box::new(NodeBuilder::<N> {
+            buffers: self.buffers.clone(),
+            images: self.images.clone(),
+            dependencies: self.dependencies.clone(),
+            _pd: PhantomData,
+        }))
+    }
+
     fn build(
         self: Box<Self>,
         submission: &Submission<SyncData<usize, usize>>,
+        sync: &SyncData<usize, usize>,
         _buffer_chains: &BufferChains,
         buffers: &[BufferResource<U>],
         image_chains: &ImageChains,
-        images: &[ImageResource<I>],
+        images: &[&GraphImage<B, I>],
+        history: &HistoryPairs,
         family: &B::QueueFamily,
         device: &mut D,
         aux: &mut T,
     ) -> Box<AnyNode<B, D, T>> {
-        let buffer_info = buffer_info(&self.buffers, &*buffers, submission);
-        let image_info = image_info(&self.images, &*images, image_chains, submission);
+        let refs = images
+            .iter()
+            .map(|image| match **image {
+                Left(ref resource) => resource.image.borrow(),
+                Right(ref backbuffer) => &backbuffer.images[0],
+            })
+            .collect::<Vec<&B::Image>>();
+        // History images change with their partners in the order of the pair.
+        let frames = images
+            .iter()
+            .enumerate()
+            .map(|(index, image)| match **image {
+                Left(_) => match history.partner(Id::new(index as u32)) {
+                    Some(partner) => vec![refs[index], refs[partner.index() as usize]],
+                    None => Vec::new(),
+                },
+                Right(ref backbuffer) => backbuffer.images.iter().collect(),
+            })
+            .collect::<Vec<Vec<&B::Image>>>();
+
+        let buffer_info = buffer_info(&self.buffers, &*buffers, sync);
+        let image_info = image_info(
+            &self.images,
+            images,
+            &refs,
+            &frames,
+            history,
+            image_chains,
+            submission,
+            sync,
+        );
 
         let pools = |device: &mut _, flags| create_typed_pool(family, flags, device);
         let node = N::build(buffer_info, image_info, pools, device, aux);
@@ -176,7 +219,7 @@ fn image_state_usage(
     }
 }
 
-fn pick_queue_family<B, C, F>(families: F) -> QueueFamilyId
+pub(crate) fn pick_queue_family<B, C, F>(families: F) -> QueueFamilyId
 where
     B: Backend,
     C: Capability,
@@ -202,7 +245,7 @@ where
 fn buffer_info<'a, U, S, W>(
     buffers: &[BufferId],
     resources: &'a [BufferResource<U>],
-    submission: &Submission<SyncData<S, W>>,
+    sync: &SyncData<S, W>,
 ) -> Vec<BufferInfo<'a, U>> {
     buffers
         .iter()
@@ -211,11 +254,11 @@ fn buffer_info<'a, U, S, W>(
             BufferInfo {
                 id,
                 barriers: Barriers {
-                    acquire: submission.sync().acquire.buffers.get(&id.0).map(|barrier| {
+                    acquire: sync.acquire.buffers.get(&id.0).map(|barrier| {
                         let Range { ref start, ref end } = barrier.states;
                         (start.access, start.stages)..(end.access, end.stages)
                     }),
-                    release: submission.sync().release.buffers.get(&id.0).map(|barrier| {
+                    release: sync.release.buffers.get(&id.0).map(|barrier| {
                         let Range { ref start, ref end } = barrier.states;
                         (start.access, start.stages)..(end.access, end.stages)
                     }),
@@ -227,35 +270,62 @@ fn buffer_info<'a, U, S, W>(
         .collect()
 }
 
-fn image_info<'a, I, S, W>(
+fn image_info<'a, 'b, B, I, S, W>(
     images: &[ImageId],
-    resources: &'a [ImageResource<I>],
+    resources: &[&'b GraphImage<B, I>],
+    refs: &'a [&'b B::Image],
+    frames: &'a [Vec<&'b B::Image>],
+    history: &HistoryPairs,
     chains: &ImageChains,
     submission: &Submission<SyncData<S, W>>,
-) -> Vec<ImageInfo<'a, I>> {
+    sync: &SyncData<S, W>,
+) -> Vec<ImageInfo<'a, &'b B::Image>>
+where
+    B: Backend,
+{
     images
         .iter()
         .map(|&id| {
-            let ref resource = resources[id.0.index() as usize];
+            let index = id.0.index() as usize;
+            let (kind, format, clear, frames) = match *resources[index] {
+                Left(ref resource) => (
+                    resource.kind,
+                    resource.format,
+                    resource.clear,
+                    history.partner(id.0).map(|_| Frames {
+                        images: &frames[index],
+                        index: history.parity.clone(),
+                    }),
+                ),
+                Right(ref backbuffer) => (
+                    backbuffer.kind,
+                    backbuffer.format,
+                    backbuffer.clear,
+                    Some(Frames {
+                        images: &frames[index],
+                        index: backbuffer.index.clone(),
+                    }),
+                ),
+            };
             let link = chains[&id.0].link(submission.image(id.0));
             ImageInfo {
                 id,
                 barriers: Barriers {
-                    acquire: submission.sync().acquire.images.get(&id.0).map(|barrier| {
+                    acquire: sync.acquire.images.get(&id.0).map(|barrier| {
                         let Range { ref start, ref end } = barrier.states;
                         ((start.access, start.layout), start.stages)
                             ..((end.access, end.layout), end.stages)
                     }),
-                    release: submission.sync().release.images.get(&id.0).map(|barrier| {
+                    release: sync.release.images.get(&id.0).map(|barrier| {
                         let Range { ref start, ref end } = barrier.states;
                         ((start.access, start.layout), start.stages)
                             ..((end.access, end.layout), end.stages)
                     }),
                 },
                 layout: link.state().layout,
-                kind: resource.kind,
-                format: resource.format,
-                clear: resource.clear.and_then(|clear| {
+                kind,
+                format,
+                clear: clear.and_then(|clear| {
                     if submission.image(id.0) == 0 {
                         assert!(
                             link.state().access.is_write(),
@@ -266,7 +336,8 @@ fn image_info<'a, I, S, W>(
                         None
                     }
                 }),
-                image: &resource.image,
+                image: &refs[index],
+                frames,
             }
         })
         .collect()
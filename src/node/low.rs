@@ -11,9 +11,15 @@ use chain::{
     resource::{Buffer, BufferLayout, Image, State}, schedule::Submission, sync::SyncData,
 };
 
-use node::{Barriers, BufferInfo, ImageInfo, Node, NodeDesc};
+use node::{
+    present::{PresentError, PresentNode}, readback::ReadbackNode, Barriers, BufferInfo, ImageInfo,
+    Node, NodeDesc,
+};
 use util::*;
 
+#[cfg(feature = "validation")]
+use node::validate::Hazard;
+
 pub trait AnyNodeBuilder<B, D, T, U, I>: Send + Sync
 where
     B: Backend,
@@ -24,13 +30,38 @@ where
 
     fn pass(&self, id: PassId, families: &[&B::QueueFamily]) -> Pass;
 
+    /// Create swapchain images for the image presented directly.
+    /// Called before any node is built.
+    fn backbuffer(
+        &mut self,
+        _image_chains: &ImageChains,
+        _device: &mut D,
+    ) -> Option<(ImageId, BackbufferResource<B>)> {
+        None
+    }
+
+    /// Create builder of another instance of the node.
+    /// Nodes are built again when their images are recreated.
+    /// `None` if the node can't be rebuilt.
+    /// See `Graph::recreate_images`.
+    fn alternate(&self) -> Option<Box<AnyNodeBuilder<B, D, T, U, I>>> {
+        None
+    }
+
+    /// Build the node.
+    /// `sync` is the same as `submission.sync()` unless the graph records
+    /// some of the barriers itself. See `GraphBuilder::with_split_barriers`.
+    /// History images are given to the node along with their partners.
+    /// See `GraphBuilder::create_history_image`.
     fn build(
         self: Box<Self>,
         submission: &Submission<SyncData<usize, usize>>,
+        sync: &SyncData<usize, usize>,
         buffer_chains: &BufferChains,
         buffers: &[BufferResource<U>],
         image_chains: &ImageChains,
-        images: &[ImageResource<I>],
+        images: &[&GraphImage<B, I>],
+        history: &HistoryPairs,
         family: &B::QueueFamily,
         device: &mut D,
         aux: &mut T,
@@ -42,15 +73,34 @@ where
     B: Backend,
     D: Device<B>,
 {
+    /// Run the node.
+    /// `frame` holds graph resources for the frame.
+    /// `acquire` is swapchain image acquisition semaphore the node must wait for
+    /// if it is the first to write directly presented image.
     fn run<'a>(
         &'a mut self,
+        frame: &FrameInfo<B>,
         sync: &SyncData<usize, usize>,
         queue: &mut B::CommandQueue,
         semaphores: &mut [B::Semaphore],
+        acquire: Option<(&B::Semaphore, PipelineStage)>,
         fence: Option<&B::Fence>,
         device: &mut D,
         aux: &'a T,
-    );
+    ) -> Result<(), PresentError>;
+
+    fn present(&mut self) -> Option<&mut PresentNode<B>> {
+        None
+    }
+
+    fn readback(&mut self) -> Option<&mut ReadbackNode<B>> {
+        None
+    }
+
+    #[cfg(feature = "validation")]
+    fn hazards(&self) -> &[Hazard] {
+        &[]
+    }
 
     fn dispose(self: Box<Self>, device: &mut D, aux: &mut T);
 }
@@ -63,13 +113,15 @@ where
 {
     fn run<'a>(
         &'a mut self,
+        frame: &FrameInfo<B>,
         sync: &SyncData<usize, usize>,
         queue: &mut B::CommandQueue,
         semaphores: &mut [B::Semaphore],
+        acquire: Option<(&B::Semaphore, PipelineStage)>,
         fence: Option<&B::Fence>,
         device: &mut D,
         aux: &'a T,
-    ) {
+    ) -> Result<(), PresentError> {
         let queue: &mut CommandQueue<B, N::Capability> = unsafe { ::std::mem::transmute(queue) };
 
         assert!(sync.acquire.signal.is_empty());
@@ -79,14 +131,21 @@ where
             .acquire
             .wait
             .iter()
-            .map(|wait| (&semaphores[*wait.semaphore()], wait.stage()));
+            .map(|wait| (&semaphores[*wait.semaphore()], wait.stage()))
+            .chain(acquire);
         let signal = sync
             .release
             .signal
             .iter()
             .map(|signal| &semaphores[*signal.semaphore()]);
 
-        N::run(&mut self.0, wait, queue, signal, fence, device, aux)
+        N::run(&mut self.0, frame, wait, queue, signal, fence, device, aux);
+        Ok(())
+    }
+
+    #[cfg(feature = "validation")]
+    fn hazards(&self) -> &[Hazard] {
+        self.0.hazards()
     }
 
     fn dispose(self: Box<Self>, device: &mut D, aux: &mut T) {
@@ -8,11 +8,16 @@ use hal::{
 use self::build::NodeBuilder;
 use util::*;
 
+pub mod barriers;
 pub mod build;
 pub mod low;
 pub mod present;
+pub mod readback;
 pub mod render;
 
+#[cfg(feature = "validation")]
+pub mod validate;
+
 /// Overall description for node.
 pub trait NodeDesc: Send + Sync + Sized + 'static {
     /// Iterator of image state, usage and stages where image is used.
@@ -52,6 +57,8 @@ where
     /// `buffers`   - Information about buffers. One for each returned by `buffers` function.
     ///
     /// `images`    - Information about images. One for each returned by `images` function.
+    ///               Directly presented and history images change between frames.
+    ///               See `ImageInfo::frames`.
     ///
     /// `frames`     - number of frames in swapchain. All non-read-only resources must be allocated per frame.
     ///               `frame` argument of `run` method will be always in `0 .. frames`.
@@ -76,8 +83,9 @@ where
         I: Borrow<B::Image>;
 
     /// Record commands for the node and return them as `Submit` object.
-    /// `frame`     - index of the frame for which commands are recorded.
-    ///               Node can safely reuse resources used with same frame last time.
+    /// `frame`     - graph resources for the frame. Nodes must look up graph buffers and images
+    ///               here instead of keeping references from `build`
+    ///               and submit their command buffers with `FrameInfo::command_buffers`.
     ///
     /// `device`    - `Device<B>` implementation. `B::Device` or wrapper.
     ///
@@ -86,6 +94,7 @@ where
     /// This method returns iterator of submittables with primary level and capabilities declared by node.
     fn run<'a, W, S>(
         &'a mut self,
+        frame: &FrameInfo<B>,
         wait: W,
         queue: &mut CommandQueue<B, Self::Capability>,
         signal: S,
@@ -96,6 +105,13 @@ where
         W: IntoIterator<Item = (&'a B::Semaphore, PipelineStage)>,
         S: IntoIterator<Item = &'a B::Semaphore>;
 
+    /// Hazards found by the validator of the node.
+    /// See `validate::Validator`.
+    #[cfg(feature = "validation")]
+    fn hazards(&self) -> &[validate::Hazard] {
+        &[]
+    }
+
     /// Dispose of the node.
     fn dispose(self, device: &mut D, aux: &mut T);
 }
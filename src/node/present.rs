@@ -3,30 +3,95 @@ use {
         chain::{BufferChains, ImageChains}, pass::{Pass, PassId, StateUsage}, resource::State,
         schedule::Submission, sync::SyncData,
     },
+    descriptors::DescriptorPool,
     hal::{
         buffer,
         command::{
-            ClearColorRaw, ClearDepthStencilRaw, CommandBufferFlags, ImageCopy, RawCommandBuffer,
-            RawLevel,
+            ClearColorRaw, ClearDepthStencilRaw, ClearValueRaw, CommandBufferFlags,
+            DescriptorSetOffset, ImageBlit, ImageCopy, RawCommandBuffer, RawLevel, SubpassContents,
+        },
+        format::{ChannelType, Format, Swizzle}, image, memory::{Barrier, Dependencies},
+        pass::{
+            Attachment, AttachmentLoadOp, AttachmentOps, AttachmentStoreOp, Subpass,
+            SubpassDependency, SubpassDesc,
+        },
+        pool::{CommandPoolCreateFlags, RawCommandPool},
+        pso::{
+            BakedStates, BasePipeline, BlendDesc, BlendState, ColorBlendDesc, ColorMask,
+            DepthStencilDesc, Descriptor, DescriptorSetLayoutBinding, DescriptorSetWrite,
+            DescriptorType, EntryPoint, GraphicsPipelineDesc, GraphicsShaderSet,
+            InputAssemblerDesc, PipelineCreationFlags, PipelineStage, PrimitiveRestart, Rasterizer,
+            Rect, ShaderStageFlags, Viewport,
         },
-        format::{ChannelType, Format}, image, memory::{Barrier, Dependencies},
-        pool::{CommandPoolCreateFlags, RawCommandPool}, pso::PipelineStage,
         queue::{QueueFamily, RawCommandQueue, RawSubmission},
         window::{
             Backbuffer, FrameSync, PresentMode, Surface, SurfaceCapabilities, Swapchain,
             SwapchainConfig,
         },
-        Backend, Device,
+        Backend, Device, Primitive,
+    },
+    either::{Left, Right},
+    node::{barriers::BarrierBatch, low::{AnyNode, AnyNodeBuilder}},
+    smallvec::SmallVec,
+    std::{
+        borrow::Borrow, collections::HashMap, iter::{empty, once}, mem::replace, ops::Range,
+        sync::{atomic::{AtomicUsize, Ordering}, Arc},
     },
-    node::low::{AnyNode, AnyNodeBuilder}, smallvec::SmallVec,
-    std::{borrow::Borrow, collections::HashMap, iter::once, mem::replace}, util::*,
+    util::*,
 };
 
+#[cfg(feature = "validation")]
+use node::validate::{Hazard, Validator};
+
+/// Name of the present node builder. Hazards found by the node are reported under it.
+const NAME: &str = "PresentNode";
+
+/// Error that may occur when presenting an image.
+/// The swapchain has to be recreated before the present node can succeed again.
+/// See `Graph::recreate_swapchain`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum PresentError {
+    /// Failed to acquire next image from the swapchain.
+    /// Usually happens when the surface was resized or became out of date.
+    Acquire,
+
+    /// Failed to present image.
+    /// The image was rendered but may not appear on the surface.
+    Present,
+}
+
+/// Error returned when the swapchain of the present can't be recreated in place.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum RecreateError {
+    /// Image is rendered directly into swapchain images. See `PresentBuilder::with_direct`.
+    /// Graph must be rebuilt for the new surface instead.
+    Direct,
+}
+
+/// Specifies how the presented image is fit into the backbuffer of different extent.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Scaling {
+    /// Stretch the image to cover the whole backbuffer.
+    Stretch,
+
+    /// Scale the image preserving aspect ratio and center it in the backbuffer.
+    /// Uncovered area is cleared to black.
+    Letterbox,
+}
+
 pub struct PresentBuilder<'a, B: Backend> {
     pub(crate) id: ImageId,
     pub(crate) format: Format,
     pub(crate) surface: &'a mut B::Surface,
     pub(crate) capabilities: SurfaceCapabilities,
+    pub(crate) present_mode: PresentMode,
+    pub(crate) present_modes: Vec<PresentMode>,
+    pub(crate) image_count: Option<u32>,
+    pub(crate) scaling: Scaling,
+    pub(crate) filter: image::Filter,
+    pub(crate) direct: bool,
+    pub(crate) swapchain: Option<(B::Swapchain, Arc<AtomicUsize>)>,
+    pub(crate) backbuffer: Option<(B::Swapchain, Backbuffer<B>)>,
     pub(crate) dependencies: Vec<PassId>,
 }
 
@@ -46,10 +111,70 @@ where
             format,
             surface,
             capabilities,
+            present_mode: PresentMode::Fifo,
+            present_modes: Vec::new(),
+            image_count: None,
+            scaling: Scaling::Stretch,
+            filter: image::Filter::Linear,
+            direct: false,
+            swapchain: None,
+            backbuffer: None,
             dependencies: Vec::new(),
         }
     }
 
+    /// Request present mode for the swapchain. `PresentMode::Fifo` is used by default.
+    /// If the surface doesn't support requested mode the closest supported one is used instead.
+    /// Only `PresentMode::Fifo` is guaranteed to be supported, so other modes require
+    /// the list of supported modes. See `with_present_modes`.
+    pub fn with_present_mode(mut self, present_mode: PresentMode) -> Self {
+        self.present_mode = present_mode;
+        self
+    }
+
+    /// Specify present modes supported by the surface as reported by `Surface::compatibility`.
+    /// The graph can't query them as it has no access to the physical device.
+    /// Without this `PresentMode::Fifo` is used regardless of the requested mode.
+    pub fn with_present_modes<I>(mut self, present_modes: I) -> Self
+    where
+        I: IntoIterator<Item = PresentMode>,
+    {
+        self.present_modes.extend(present_modes);
+        self
+    }
+
+    /// Request number of images in the swapchain.
+    /// The number is clamped to the range supported by the surface.
+    /// By default 3 images are requested.
+    pub fn with_image_count(mut self, image_count: u32) -> Self {
+        self.image_count = Some(image_count);
+        self
+    }
+
+    /// Specify how the image is fit into the backbuffer if their extents differ.
+    /// `Scaling::Stretch` is used by default.
+    pub fn with_scaling(mut self, scaling: Scaling) -> Self {
+        self.scaling = scaling;
+        self
+    }
+
+    /// Specify filter used when the image is scaled or converted to the backbuffer format.
+    /// `Filter::Linear` is used by default.
+    pub fn with_filter(mut self, filter: image::Filter) -> Self {
+        self.filter = filter;
+        self
+    }
+
+    /// Render directly into swapchain images instead of copying the image into them.
+    /// Nodes that use the image get swapchain images through `ImageInfo::frames`.
+    /// Image must have the same extent as the surface and format of the swapchain.
+    /// Swapchain of direct present can't be recreated in place. See `RecreateError::Direct`.
+    /// `Backbuffer::Framebuffer` is not supported.
+    pub fn with_direct(mut self, direct: bool) -> Self {
+        self.direct = direct;
+        self
+    }
+
     /// Add dependencies to the present builder.
     pub fn with_dependencies<I>(mut self, deps: I) -> Self
     where
@@ -59,11 +184,38 @@ where
         self
     }
 
+    /// Create swapchain if the image is copied into the backbuffer.
+    /// Kind of the backbuffer must be known before scheduling
+    /// as the image is sampled instead of copied for `Backbuffer::Framebuffer`.
+    pub(crate) fn prepare_swapchain<D>(&mut self, device: &mut D)
+    where
+        D: Device<B>,
+    {
+        if self.direct || self.backbuffer.is_some() {
+            return;
+        }
+
+        let present_mode = pick_present_mode(self.present_mode, &self.present_modes);
+        let image_count = pick_image_count(self.image_count, &self.capabilities);
+        let extent = pick_extent(&self.capabilities, self.surface.kind().extent());
+        self.backbuffer = Some(create_swapchain::<B, _>(
+            self.surface,
+            extent,
+            image::Usage::TRANSFER_DST,
+            self.format,
+            present_mode,
+            image_count,
+            None,
+            device,
+        ));
+    }
+
     fn build_node<I, D>(
-        self,
+        mut self,
         submission: &Submission<SyncData<usize, usize>>,
+        sync: &SyncData<usize, usize>,
         chains: &ImageChains,
-        resources: &[ImageResource<I>],
+        resources: &[&GraphImage<B, I>],
         family: &B::QueueFamily,
         device: &mut D,
     ) -> PresentNode<B>
@@ -71,156 +223,65 @@ where
         D: Device<B>,
         I: Borrow<B::Image>,
     {
-        let extent = self.surface.kind().extent().into();
-        let (swapchain, backbuffer) = device.create_swapchain(
-            self.surface,
-            SwapchainConfig {
-                present_mode: PresentMode::Fifo,
-                color_format: self.format,
-                depth_stencil_format: None,
-                image_count: if self.capabilities.image_count.start <= 3 {
-                    if self.capabilities.image_count.end >= 3 {
-                        3
-                    } else {
-                        self.capabilities.image_count.end
-                    }
-                } else {
-                    self.capabilities.image_count.start
-                },
-                image_usage: image::Usage::TRANSFER_DST,
-            },
-            None,
-            &extent,
-        );
-
-        let mut pool = device.create_command_pool(family.id(), CommandPoolCreateFlags::empty());
-
         let ref chain = chains[&self.id.0];
         let link = chain.link(submission.image(self.id.0));
-        let ref resource = resources[self.id.0.index() as usize];
-
-        let ref acquire = submission.sync().acquire.images.get(&self.id.0);
-        let ref release = submission.sync().release.images.get(&self.id.0);
+        let resource = resources[self.id.0.index() as usize];
 
-        let per_frame = match backbuffer {
-            Backbuffer::Images(ref backbuffer_images) => {
-                let cbufs = pool.allocate(backbuffer_images.len(), RawLevel::Primary);
-                cbufs
-                    .into_iter()
-                    .enumerate()
-                    .map(|(index, mut cbuf)| {
-                        let ref backbuffer_image = backbuffer_images[index];
-                        cbuf.begin(CommandBufferFlags::EMPTY, Default::default());
-                        acquire.map(|acquire| {
-                            cbuf.pipeline_barrier(
-                                acquire.states.start.stages..acquire.states.end.stages,
-                                Dependencies::empty(),
-                                Some(Barrier::Image {
-                                    states: (
-                                        acquire.states.start.access,
-                                        acquire.states.start.layout,
-                                    )
-                                        ..(acquire.states.end.access, acquire.states.end.layout),
-                                    target: resource.image.borrow(),
-                                    range: image::SubresourceRange {
-                                        aspects: resource.format.surface_desc().aspects,
-                                        levels: 0..1,
-                                        layers: 0..1,
-                                    },
-                                }),
-                            );
-                        });
-                        cbuf.pipeline_barrier(
-                            PipelineStage::BOTTOM_OF_PIPE..PipelineStage::TRANSFER,
-                            Dependencies::empty(),
-                            Some(Barrier::Image {
-                                states: (image::Access::empty(), image::Layout::Present)
-                                    ..(
-                                        image::Access::TRANSFER_READ,
-                                        image::Layout::TransferDstOptimal,
-                                    ),
-                                target: backbuffer_image,
-                                range: image::SubresourceRange {
-                                    aspects: self.format.surface_desc().aspects,
-                                    levels: 0..1,
-                                    layers: 0..1,
-                                },
-                            }),
-                        );
-                        cbuf.copy_image(
-                            resource.image.borrow(),
-                            link.state().layout,
-                            backbuffer_image,
-                            image::Layout::TransferDstOptimal,
-                            Some(ImageCopy {
-                                src_subresource: image::SubresourceLayers {
-                                    aspects: resource.format.surface_desc().aspects,
-                                    level: 0,
-                                    layers: 0..1,
-                                },
-                                src_offset: image::Offset { x: 0, y: 0, z: 0 },
-                                dst_subresource: image::SubresourceLayers {
-                                    aspects: self.format.surface_desc().aspects,
-                                    level: 0,
-                                    layers: 0..1,
-                                },
-                                dst_offset: image::Offset { x: 0, y: 0, z: 0 },
-                                extent: resource.kind.extent(),
-                            }),
-                        );
-                        cbuf.pipeline_barrier(
-                            PipelineStage::TRANSFER..PipelineStage::TOP_OF_PIPE,
-                            Dependencies::empty(),
-                            Some(Barrier::Image {
-                                states: (
-                                    image::Access::TRANSFER_READ,
-                                    image::Layout::TransferDstOptimal,
-                                )
-                                    ..(image::Access::empty(), image::Layout::Present),
-                                target: backbuffer_image,
-                                range: image::SubresourceRange {
-                                    aspects: self.format.surface_desc().aspects,
-                                    levels: 0..1,
-                                    layers: 0..1,
-                                },
-                            }),
-                        );
-                        release.map(|release| {
-                            cbuf.pipeline_barrier(
-                                release.states.start.stages..release.states.end.stages,
-                                Dependencies::empty(),
-                                Some(Barrier::Image {
-                                    states: (
-                                        release.states.start.access,
-                                        release.states.start.layout,
-                                    )
-                                        ..(release.states.end.access, release.states.end.layout),
-                                    target: resource.image.borrow(),
-                                    range: image::SubresourceRange {
-                                        aspects: resource.format.surface_desc().aspects,
-                                        levels: 0..1,
-                                        layers: 0..1,
-                                    },
-                                }),
-                            );
-                        });
-
-                        cbuf.finish();
+        let barriers = Barriers {
+            acquire: sync.acquire.images.get(&self.id.0).map(|barrier| {
+                let Range { ref start, ref end } = barrier.states;
+                ((start.access, start.layout), start.stages)..((end.access, end.layout), end.stages)
+            }),
+            release: sync.release.images.get(&self.id.0).map(|barrier| {
+                let Range { ref start, ref end } = barrier.states;
+                ((start.access, start.layout), start.stages)..((end.access, end.layout), end.stages)
+            }),
+        };
 
-                        (device.create_semaphore(), device.create_semaphore(), cbuf)
-                    })
-                    .collect()
+        let present_mode = pick_present_mode(self.present_mode, &self.present_modes);
+        let image_count = pick_image_count(self.image_count, &self.capabilities);
+        let extent = pick_extent(&self.capabilities, self.surface.kind().extent());
+        let (swapchain, backbuffer, direct) = match self.swapchain.take() {
+            Some((swapchain, index)) => (swapchain, Backbuffer::Images(Vec::new()), Some(index)),
+            None => {
+                let (swapchain, backbuffer) = self
+                    .backbuffer
+                    .take()
+                    .expect("Swapchain must be created before scheduling");
+                (swapchain, backbuffer, None)
             }
-            Backbuffer::Framebuffer(_) => unimplemented!(),
         };
 
-        PresentNode {
-            per_frame,
+        let mut node = PresentNode {
+            id: self.id,
+            format: self.format,
+            layout: link.state().layout,
+            barriers,
+            present_modes: self.present_modes,
+            present_mode,
+            image_count,
+            capabilities: self.capabilities,
+            extent,
+            scaling: self.scaling,
+            filter: self.filter,
+            per_frame: Vec::new(),
+            unrendered: Vec::new(),
             free: Some(device.create_semaphore()),
-            swapchain: swapchain,
-            backbuffer: backbuffer,
-            pool,
+            swapchain: Some(swapchain),
+            backbuffer,
+            quad: None,
+            direct,
+            pool: device.create_command_pool(family.id(), CommandPoolCreateFlags::empty()),
+            #[cfg(feature = "validation")]
+            declared: (link.state().access, link.state().stages),
+            #[cfg(feature = "validation")]
+            validator: Validator::new(NAME),
+        };
+        match *resource {
+            Left(ref resource) => node.record_frames(resource, device),
+            Right(ref backbuffer) => node.record_direct_frames(backbuffer, device),
         }
+        node
     }
 }
 
@@ -232,7 +293,7 @@ where
     I: Borrow<B::Image>,
 {
     fn name(&self) -> &str {
-        "PresentNode"
+        NAME
     }
 
     fn pass(&self, id: PassId, families: &[&B::QueueFamily]) -> Pass {
@@ -248,39 +309,504 @@ where
             buffers: HashMap::new(),
             images: once((
                 self.id.0,
-                StateUsage {
-                    state: State {
-                        access: image::Access::TRANSFER_READ,
-                        layout: image::Layout::TransferSrcOptimal,
-                        stages: PipelineStage::TRANSFER,
-                    },
-                    usage: image::Usage::TRANSFER_SRC,
+                if self.direct {
+                    StateUsage {
+                        state: State {
+                            access: image::Access::empty(),
+                            layout: image::Layout::Present,
+                            stages: PipelineStage::BOTTOM_OF_PIPE,
+                        },
+                        usage: image::Usage::empty(),
+                    }
+                } else if let Some((_, Backbuffer::Framebuffer(_))) = self.backbuffer {
+                    StateUsage {
+                        state: State {
+                            access: image::Access::SHADER_READ,
+                            layout: image::Layout::ShaderReadOnlyOptimal,
+                            stages: PipelineStage::FRAGMENT_SHADER,
+                        },
+                        usage: image::Usage::SAMPLED,
+                    }
+                } else {
+                    StateUsage {
+                        state: State {
+                            access: image::Access::TRANSFER_READ,
+                            layout: image::Layout::TransferSrcOptimal,
+                            stages: PipelineStage::TRANSFER,
+                        },
+                        usage: image::Usage::TRANSFER_SRC,
+                    }
                 },
             )).collect(),
         }
     }
 
+    fn backbuffer(
+        &mut self,
+        chains: &ImageChains,
+        device: &mut D,
+    ) -> Option<(ImageId, BackbufferResource<B>)> {
+        if !self.direct {
+            return None;
+        }
+
+        let present_mode = pick_present_mode(self.present_mode, &self.present_modes);
+        let image_count = pick_image_count(self.image_count, &self.capabilities);
+        let extent = pick_extent(&self.capabilities, self.surface.kind().extent());
+        let (swapchain, backbuffer) = create_swapchain::<B, _>(
+            self.surface,
+            extent,
+            chains[&self.id.0].usage(),
+            self.format,
+            present_mode,
+            image_count,
+            None,
+            device,
+        );
+
+        let images = match backbuffer {
+            Backbuffer::Images(images) => images,
+            Backbuffer::Framebuffer(_) => panic!("Direct present requires swapchain images"),
+        };
+
+        let index = Arc::new(AtomicUsize::new(0));
+        self.swapchain = Some((swapchain, index.clone()));
+        Some((
+            self.id,
+            BackbufferResource {
+                kind: image::Kind::D2(extent.width, extent.height, 1, 1),
+                format: self.format,
+                clear: None,
+                images,
+                index,
+            },
+        ))
+    }
+
     fn build(
         self: Box<Self>,
         submission: &Submission<SyncData<usize, usize>>,
+        sync: &SyncData<usize, usize>,
         _: &BufferChains,
         _: &[BufferResource<U>],
         chains: &ImageChains,
-        resources: &[ImageResource<I>],
+        resources: &[&GraphImage<B, I>],
+        _: &HistoryPairs,
         family: &B::QueueFamily,
         device: &mut D,
         _aux: &mut T,
     ) -> Box<AnyNode<B, D, T>> {
-        Box::new(self.build_node(submission, chains, resources, family, device))
+        Box::new(self.build_node(submission, sync, chains, resources, family, device))
     }
 }
 
 pub struct PresentNode<B: Backend> {
+    id: ImageId,
+    format: Format,
+    layout: image::Layout,
+    barriers: Barriers<image::State>,
+    present_modes: Vec<PresentMode>,
+    present_mode: PresentMode,
+    image_count: u32,
+    /// Capabilities the swapchain was last created with.
+    capabilities: SurfaceCapabilities,
+    extent: image::Extent,
+    scaling: Scaling,
+    filter: image::Filter,
     per_frame: Vec<(B::Semaphore, B::Semaphore, B::CommandBuffer)>,
+    /// Command buffers making swapchain images of direct present presentable unrendered.
+    unrendered: Vec<B::CommandBuffer>,
     free: Option<B::Semaphore>,
-    swapchain: B::Swapchain,
+    swapchain: Option<B::Swapchain>,
     backbuffer: Backbuffer<B>,
+    quad: Option<Quad<B>>,
+    direct: Option<Arc<AtomicUsize>>,
     pool: B::CommandPool,
+
+    /// Access and stages of the image declared by the node.
+    #[cfg(feature = "validation")]
+    declared: (image::Access, PipelineStage),
+    #[cfg(feature = "validation")]
+    validator: Validator<B>,
+}
+
+impl<B> PresentNode<B>
+where
+    B: Backend,
+{
+    /// Id of the presented image.
+    pub fn image(&self) -> ImageId {
+        self.id
+    }
+
+    /// Present mode of the swapchain.
+    pub fn present_mode(&self) -> PresentMode {
+        self.present_mode
+    }
+
+    /// Check if the image is rendered directly into swapchain images.
+    /// See `PresentBuilder::with_direct`.
+    pub fn is_direct(&self) -> bool {
+        self.direct.is_some()
+    }
+
+    /// Acquire swapchain image for direct present.
+    /// Returned semaphore must be waited by the first node writing the image
+    /// and given back with `acquired` afterwards.
+    pub(crate) fn acquire(&mut self) -> Result<B::Semaphore, PresentError> {
+        profile!("Acquire frame");
+        let semaphore = self.free.take().unwrap();
+        let frame = self
+            .swapchain
+            .as_mut()
+            .unwrap()
+            .acquire_image(FrameSync::Semaphore(&semaphore));
+
+        match frame {
+            Ok(frame) => {
+                let index = self.direct.as_ref().unwrap();
+                index.store(frame as usize, Ordering::Release);
+                Ok(semaphore)
+            }
+            Err(err) => {
+                warn!("Failed to acquire swapchain image: {:?}", err);
+                self.free = Some(semaphore);
+                Err(PresentError::Acquire)
+            }
+        }
+    }
+
+    /// Give back semaphore returned by `acquire`.
+    pub(crate) fn acquired(&mut self, semaphore: B::Semaphore) {
+        let frame = self.direct.as_ref().unwrap().load(Ordering::Acquire);
+        self.free = Some(replace(&mut self.per_frame[frame].0, semaphore));
+    }
+
+    /// Present swapchain image acquired with `acquire` without rendering into it
+    /// and give back the semaphore. Used when the graph doesn't run the frame.
+    /// Content of the presented image is undefined.
+    pub(crate) fn present_unrendered(
+        &mut self,
+        semaphore: B::Semaphore,
+        queue: &mut B::CommandQueue,
+    ) -> Result<(), PresentError> {
+        let frame = self.direct.as_ref().unwrap().load(Ordering::Acquire);
+        let result = {
+            let (_, ref release, _) = self.per_frame[frame];
+            let submission = RawSubmission {
+                wait_semaphores: &[(&semaphore, PipelineStage::COLOR_ATTACHMENT_OUTPUT)],
+                signal_semaphores: &[release],
+                cmd_buffers: once(&self.unrendered[frame]),
+            };
+
+            trace!("Presenting unrendered image");
+            unsafe {
+                queue.submit_raw(submission, None);
+                queue
+                    .present(
+                        Some((self.swapchain.as_mut().unwrap(), frame as u32)),
+                        Some(release),
+                    )
+                    .map_err(|err| {
+                        warn!("Failed to present swapchain image: {:?}", err);
+                        PresentError::Present
+                    })
+            }
+        };
+        self.acquired(semaphore);
+        result
+    }
+
+    /// Change present mode and recreate the swapchain.
+    /// Returns present mode actually used.
+    pub(crate) fn set_present_mode<D, I>(
+        &mut self,
+        present_mode: PresentMode,
+        surface: &mut B::Surface,
+        resource: &ImageResource<I>,
+        device: &mut D,
+    ) -> Result<PresentMode, RecreateError>
+    where
+        D: Device<B>,
+        I: Borrow<B::Image>,
+    {
+        if self.is_direct() {
+            return Err(RecreateError::Direct);
+        }
+        self.present_mode = pick_present_mode(present_mode, &self.present_modes);
+        self.recreate(surface, resource, device);
+        Ok(self.present_mode)
+    }
+
+    /// Recreate swapchain for new surface capabilities.
+    /// Extent of the swapchain is picked from the capabilities.
+    /// Should be called after `PresentError` was reported for this node.
+    pub(crate) fn recreate_swapchain<D, I>(
+        &mut self,
+        surface: &mut B::Surface,
+        capabilities: &SurfaceCapabilities,
+        resource: &ImageResource<I>,
+        device: &mut D,
+    ) -> Result<(), RecreateError>
+    where
+        D: Device<B>,
+        I: Borrow<B::Image>,
+    {
+        if self.is_direct() {
+            return Err(RecreateError::Direct);
+        }
+        self.image_count = pick_image_count(Some(self.image_count), capabilities);
+        self.capabilities = capabilities.clone();
+        self.recreate(surface, resource, device);
+        Ok(())
+    }
+
+    /// Record command buffers again after the presented image was recreated.
+    pub(crate) fn image_recreated<D, I>(&mut self, resource: &ImageResource<I>, device: &mut D)
+    where
+        D: Device<B>,
+        I: Borrow<B::Image>,
+    {
+        self.dispose_frames(device);
+        self.record_frames(resource, device);
+    }
+
+    /// Recreate swapchain and per-frame command buffers of present that isn't direct.
+    fn recreate<D, I>(
+        &mut self,
+        surface: &mut B::Surface,
+        resource: &ImageResource<I>,
+        device: &mut D,
+    ) where
+        D: Device<B>,
+        I: Borrow<B::Image>,
+    {
+        trace!("Recreate swapchain with {:?}", self.present_mode);
+        self.dispose_frames(device);
+        self.extent = pick_extent(&self.capabilities, surface.kind().extent());
+        let (swapchain, backbuffer) = create_swapchain::<B, _>(
+            surface,
+            self.extent,
+            image::Usage::TRANSFER_DST,
+            self.format,
+            self.present_mode,
+            self.image_count,
+            self.swapchain.take(),
+            device,
+        );
+        self.swapchain = Some(swapchain);
+        self.backbuffer = backbuffer;
+        self.record_frames(resource, device);
+    }
+
+    /// Record command buffers for each backbuffer image.
+    fn record_frames<D, I>(&mut self, resource: &ImageResource<I>, device: &mut D)
+    where
+        D: Device<B>,
+        I: Borrow<B::Image>,
+    {
+        let ref acquire = self.barriers.acquire;
+        let ref release = self.barriers.release;
+        let layout = self.layout;
+        let format = self.format;
+        let extent = self.extent;
+        let scaling = self.scaling;
+        let filter = self.filter.clone();
+        let aspects = resource.format.surface_desc().aspects;
+        let src_extent = resource.kind.extent();
+
+        #[cfg(feature = "validation")]
+        {
+            let mut validator = Validator::new(NAME);
+            let info = ImageInfo {
+                id: self.id,
+                barriers: self.barriers.clone(),
+                layout,
+                kind: resource.kind,
+                format: resource.format,
+                clear: None,
+                image: &resource.image,
+                frames: None,
+            };
+            let (access, stages) = self.declared;
+            validator.declare_image(&info, (access, layout), stages);
+            let (command, access, stages) = match self.backbuffer {
+                Backbuffer::Images(_) => (
+                    "blit_image",
+                    image::Access::TRANSFER_READ,
+                    PipelineStage::TRANSFER,
+                ),
+                Backbuffer::Framebuffer(_) => (
+                    "write_descriptor_sets",
+                    image::Access::SHADER_READ,
+                    PipelineStage::FRAGMENT_SHADER,
+                ),
+            };
+            validator.access_image(command, resource.image.borrow(), (access, layout), stages);
+            self.validator = validator;
+        }
+
+        self.per_frame = match self.backbuffer {
+            Backbuffer::Images(ref backbuffer_images) => {
+                let cbufs = self.pool.allocate(backbuffer_images.len(), RawLevel::Primary);
+                cbufs
+                    .into_iter()
+                    .enumerate()
+                    .map(|(index, mut cbuf)| {
+                        let ref backbuffer_image = backbuffer_images[index];
+                        cbuf.begin(CommandBufferFlags::EMPTY, Default::default());
+                        let mut batch = BarrierBatch::<B>::new();
+                        if let Some(ref acquire) = *acquire {
+                            batch.image(acquire, resource.image.borrow(), aspects);
+                        }
+                        batch.push(
+                            PipelineStage::BOTTOM_OF_PIPE..PipelineStage::TRANSFER,
+                            Barrier::Image {
+                                states: (image::Access::empty(), image::Layout::Present)
+                                    ..(
+                                        image::Access::TRANSFER_WRITE,
+                                        image::Layout::TransferDstOptimal,
+                                    ),
+                                target: backbuffer_image,
+                                range: image::SubresourceRange {
+                                    aspects: format.surface_desc().aspects,
+                                    levels: 0..1,
+                                    layers: 0..1,
+                                },
+                            },
+                        );
+                        batch.record(&mut cbuf);
+                        record_transfer::<B>(
+                            &mut cbuf,
+                            (resource.image.borrow(), layout, resource.format, src_extent),
+                            (backbuffer_image, format, extent),
+                            scaling,
+                            filter.clone(),
+                        );
+                        let mut batch = BarrierBatch::<B>::new();
+                        batch.push(
+                            PipelineStage::TRANSFER..PipelineStage::TOP_OF_PIPE,
+                            Barrier::Image {
+                                states: (
+                                    image::Access::TRANSFER_WRITE,
+                                    image::Layout::TransferDstOptimal,
+                                )
+                                    ..(image::Access::empty(), image::Layout::Present),
+                                target: backbuffer_image,
+                                range: image::SubresourceRange {
+                                    aspects: format.surface_desc().aspects,
+                                    levels: 0..1,
+                                    layers: 0..1,
+                                },
+                            },
+                        );
+                        if let Some(ref release) = *release {
+                            batch.image(release, resource.image.borrow(), aspects);
+                        }
+                        batch.record(&mut cbuf);
+
+                        cbuf.finish();
+
+                        (device.create_semaphore(), device.create_semaphore(), cbuf)
+                    })
+                    .collect()
+            }
+            Backbuffer::Framebuffer(ref framebuffer) => {
+                let quad = Quad::new(resource, format, extent, scaling, filter, device);
+                let mut cbuf = self.pool.allocate(1, RawLevel::Primary).pop().unwrap();
+                cbuf.begin(CommandBufferFlags::EMPTY, Default::default());
+                if let Some(ref acquire) = *acquire {
+                    let mut batch = BarrierBatch::<B>::new();
+                    batch.image(acquire, resource.image.borrow(), aspects);
+                    batch.record(&mut cbuf);
+                }
+                quad.record(&mut cbuf, framebuffer, extent);
+                if let Some(ref release) = *release {
+                    let mut batch = BarrierBatch::<B>::new();
+                    batch.image(release, resource.image.borrow(), aspects);
+                    batch.record(&mut cbuf);
+                }
+                cbuf.finish();
+                self.quad = Some(quad);
+
+                // Framebuffer backbuffer has single image.
+                vec![(device.create_semaphore(), device.create_semaphore(), cbuf)]
+            }
+        };
+    }
+
+    /// Record command buffers with barriers for each swapchain image of direct present.
+    fn record_direct_frames<D>(&mut self, backbuffer: &BackbufferResource<B>, device: &mut D)
+    where
+        D: Device<B>,
+    {
+        let ref acquire = self.barriers.acquire;
+        let ref release = self.barriers.release;
+        let aspects = backbuffer.format.surface_desc().aspects;
+
+        let cbufs = self.pool.allocate(backbuffer.images.len(), RawLevel::Primary);
+        self.per_frame = cbufs
+            .into_iter()
+            .zip(&backbuffer.images)
+            .map(|(mut cbuf, image)| {
+                cbuf.begin(CommandBufferFlags::EMPTY, Default::default());
+                // Both barriers are of the same image and must not share a batch.
+                for barrier in acquire.iter().chain(release) {
+                    let mut batch = BarrierBatch::<B>::new();
+                    batch.image(barrier, image, aspects);
+                    batch.record(&mut cbuf);
+                }
+                cbuf.finish();
+
+                (device.create_semaphore(), device.create_semaphore(), cbuf)
+            })
+            .collect();
+
+        // Freshly acquired image is transitioned right into presentable layout.
+        let undefined = (image::Access::empty(), image::Layout::Undefined);
+        let present = (image::Access::empty(), image::Layout::Present);
+        let unrendered = (undefined, PipelineStage::COLOR_ATTACHMENT_OUTPUT)
+            ..(present, PipelineStage::BOTTOM_OF_PIPE);
+        let cbufs = self.pool.allocate(backbuffer.images.len(), RawLevel::Primary);
+        self.unrendered = cbufs
+            .into_iter()
+            .zip(&backbuffer.images)
+            .map(|(mut cbuf, image)| {
+                cbuf.begin(CommandBufferFlags::EMPTY, Default::default());
+                let mut batch = BarrierBatch::<B>::new();
+                batch.image(&unrendered, image, aspects);
+                batch.record(&mut cbuf);
+                cbuf.finish();
+                cbuf
+            })
+            .collect();
+    }
+
+    /// Stage where backbuffer image is first accessed.
+    fn backbuffer_stage(&self) -> PipelineStage {
+        match self.backbuffer {
+            Backbuffer::Images(_) => PipelineStage::TRANSFER,
+            Backbuffer::Framebuffer(_) => PipelineStage::COLOR_ATTACHMENT_OUTPUT,
+        }
+    }
+
+    /// Destroy per-frame semaphores and command buffers.
+    fn dispose_frames<D>(&mut self, device: &mut D)
+    where
+        D: Device<B>,
+    {
+        let mut cbufs = Vec::new();
+        for (acquire, release, cbuf) in self.per_frame.drain(..) {
+            device.destroy_semaphore(acquire);
+            device.destroy_semaphore(release);
+            cbufs.push(cbuf);
+        }
+        cbufs.extend(self.unrendered.drain(..));
+        self.pool.free(cbufs);
+        if let Some(quad) = self.quad.take() {
+            quad.dispose(device);
+        }
+    }
 }
 
 impl<B, D, T> AnyNode<B, D, T> for PresentNode<B>
@@ -290,13 +816,15 @@ where
 {
     fn run<'a>(
         &'a mut self,
+        frame: &FrameInfo<B>,
         sync: &SyncData<usize, usize>,
         queue: &mut B::CommandQueue,
         semaphores: &mut [B::Semaphore],
+        _acquire: Option<(&B::Semaphore, PipelineStage)>,
         fence: Option<&B::Fence>,
         _device: &mut D,
         _aux: &'a T,
-    ) {
+    ) -> Result<(), PresentError> {
         profile!("Present::run");
         assert!(sync.acquire.signal.is_empty());
         assert!(sync.release.signal.is_empty());
@@ -307,24 +835,72 @@ where
             .wait
             .iter()
             .map(|wait| (&semaphores[*wait.semaphore()], wait.stage()));
+
+        if let Some(ref index) = self.direct {
+            // Image was acquired by `Graph` before the writing node ran.
+            let index = index.load(Ordering::Acquire);
+            let (_, ref release, ref cbuf) = self.per_frame[index];
+            let submission = RawSubmission {
+                wait_semaphores: &wait.collect::<SmallVec<[_; 8]>>(),
+                signal_semaphores: &[release],
+                cmd_buffers: frame.command_buffers(Some(cbuf)),
+            };
+
+            trace!("Presenting directly");
+            return unsafe {
+                profile!("Submit");
+                queue.submit_raw(submission, fence);
+                queue
+                    .present(
+                        Some((self.swapchain.as_mut().unwrap(), index as u32)),
+                        Some(release),
+                    )
+                    .map_err(|err| {
+                        warn!("Failed to present swapchain image: {:?}", err);
+                        PresentError::Present
+                    })
+            };
+        }
+
         let acquire = self.free.take().unwrap();
 
-        let frame = {
+        let index = {
             profile!("Acquire frame");
             self.swapchain
-                .acquire_image(FrameSync::Semaphore(&acquire))
+                .as_mut()
                 .unwrap()
+                .acquire_image(FrameSync::Semaphore(&acquire))
+        };
+
+        let index = match index {
+            Ok(index) => index,
+            Err(err) => {
+                warn!("Failed to acquire swapchain image: {:?}", err);
+                self.free = Some(acquire);
+
+                // Consume wait semaphores, submit graph commands and signal the fence anyway.
+                let submission = RawSubmission {
+                    wait_semaphores: &wait.collect::<SmallVec<[_; 8]>>(),
+                    signal_semaphores: &[],
+                    cmd_buffers: frame.command_buffers(None),
+                };
+                unsafe {
+                    queue.submit_raw(submission, fence);
+                }
+                return Err(PresentError::Acquire);
+            }
         };
 
-        self.free = Some(replace(&mut self.per_frame[frame as usize].0, acquire));
-        let (ref acquire, ref release, ref cbuf) = self.per_frame[frame as usize];
+        let stage = self.backbuffer_stage();
+        self.free = Some(replace(&mut self.per_frame[index as usize].0, acquire));
+        let (ref acquire, ref release, ref cbuf) = self.per_frame[index as usize];
 
         let submission = RawSubmission {
             wait_semaphores: &wait
-                .chain(Some((acquire, PipelineStage::TRANSFER)))
+                .chain(Some((acquire, stage)))
                 .collect::<SmallVec<[_; 8]>>(),
             signal_semaphores: &[release],
-            cmd_buffers: Some(cbuf),
+            cmd_buffers: frame.command_buffers(Some(cbuf)),
         };
 
         trace!("Presenting");
@@ -332,12 +908,594 @@ where
             profile!("Submit");
             queue.submit_raw(submission, fence);
             queue
-                .present(Some((&mut self.swapchain, frame)), Some(release))
-                .unwrap();
+                .present(Some((self.swapchain.as_mut().unwrap(), index)), Some(release))
+                .map_err(|err| {
+                    warn!("Failed to present swapchain image: {:?}", err);
+                    PresentError::Present
+                })
+        }
+    }
+
+    fn present(&mut self) -> Option<&mut PresentNode<B>> {
+        Some(self)
+    }
+
+    #[cfg(feature = "validation")]
+    fn hazards(&self) -> &[Hazard] {
+        self.validator.hazards()
+    }
+
+    fn dispose(mut self: Box<Self>, device: &mut D, _aux: &mut T) {
+        self.dispose_frames(device);
+        device.destroy_semaphore(self.free.take().unwrap());
+        device.destroy_swapchain(self.swapchain.take().unwrap());
+        device.destroy_command_pool(self.pool);
+    }
+}
+
+/// Resources to draw the image into framebuffer backbuffer with fullscreen triangle.
+struct Quad<B: Backend> {
+    render_pass: B::RenderPass,
+    set_layout: B::DescriptorSetLayout,
+    pool: DescriptorPool<B>,
+    set: B::DescriptorSet,
+    layout: B::PipelineLayout,
+    pipeline: B::GraphicsPipeline,
+    view: B::ImageView,
+    sampler: B::Sampler,
+}
+
+impl<B> Quad<B>
+where
+    B: Backend,
+{
+    fn new<D, I>(
+        resource: &ImageResource<I>,
+        format: Format,
+        extent: image::Extent,
+        scaling: Scaling,
+        filter: image::Filter,
+        device: &mut D,
+    ) -> Self
+    where
+        D: Device<B>,
+        I: Borrow<B::Image>,
+    {
+        trace!("Create fullscreen quad for framebuffer backbuffer");
+
+        let render_pass = device.create_render_pass(
+            Some(Attachment {
+                format: Some(format),
+                ops: AttachmentOps {
+                    load: AttachmentLoadOp::Clear,
+                    store: AttachmentStoreOp::Store,
+                },
+                stencil_ops: AttachmentOps::DONT_CARE,
+                layouts: image::Layout::Undefined..image::Layout::Present,
+                samples: 1,
+            }),
+            Some(SubpassDesc {
+                colors: &[(0, image::Layout::ColorAttachmentOptimal)],
+                depth_stencil: None,
+                inputs: &[],
+                resolves: &[],
+                preserves: &[],
+            }),
+            empty::<SubpassDependency>(),
+        );
+
+        let bindings = vec![DescriptorSetLayoutBinding {
+            binding: 0,
+            ty: DescriptorType::CombinedImageSampler,
+            count: 1,
+            stage_flags: ShaderStageFlags::FRAGMENT,
+        }];
+        let set_layout =
+            device.create_descriptor_set_layout(bindings.iter().cloned(), empty::<B::Sampler>());
+        let mut pool = DescriptorPool::new(bindings);
+        let set = pool.allocate(device, &set_layout).unwrap();
+        let layout = device.create_pipeline_layout(
+            Some(&set_layout),
+            empty::<(ShaderStageFlags, Range<u32>)>(),
+        );
+
+        let view = device
+            .create_image_view(
+                resource.image.borrow(),
+                image::ViewKind::D2,
+                resource.format,
+                Swizzle::NO,
+                image::SubresourceRange {
+                    aspects: resource.format.surface_desc().aspects,
+                    levels: 0..1,
+                    layers: 0..1,
+                },
+            )
+            .unwrap();
+        let sampler = device.create_sampler(image::SamplerInfo::new(
+            filter,
+            image::WrapMode::Clamp,
+        ));
+        device.write_descriptor_sets(Some(DescriptorSetWrite {
+            set: &set,
+            binding: 0,
+            array_offset: 0,
+            descriptors: Some(Descriptor::CombinedImageSampler(
+                &view,
+                image::Layout::ShaderReadOnlyOptimal,
+                &sampler,
+            )),
+        }));
+
+        let (x, y, width, height) = match scaling {
+            Scaling::Stretch => (0, 0, extent.width, extent.height),
+            Scaling::Letterbox => letterbox(resource.kind.extent(), extent),
+        };
+
+        // Shaders are compiled with `shaders/shaders.sh`.
+        let vertex = device
+            .create_shader_module(include_bytes!("shaders/present.vert.spv"))
+            .unwrap();
+        let fragment = device
+            .create_shader_module(include_bytes!("shaders/present.frag.spv"))
+            .unwrap();
+
+        let pipeline = device
+            .create_graphics_pipeline(&GraphicsPipelineDesc {
+                shaders: GraphicsShaderSet {
+                    vertex: EntryPoint {
+                        entry: "main",
+                        module: &vertex,
+                        specialization: &[],
+                    },
+                    hull: None,
+                    domain: None,
+                    geometry: None,
+                    fragment: Some(EntryPoint {
+                        entry: "main",
+                        module: &fragment,
+                        specialization: &[],
+                    }),
+                },
+                rasterizer: Rasterizer::FILL,
+                vertex_buffers: Vec::new(),
+                attributes: Vec::new(),
+                input_assembler: InputAssemblerDesc {
+                    primitive: Primitive::TriangleList,
+                    primitive_restart: PrimitiveRestart::Disabled,
+                },
+                blender: BlendDesc {
+                    logic_op: None,
+                    targets: vec![ColorBlendDesc(ColorMask::ALL, BlendState::Off)],
+                },
+                depth_stencil: DepthStencilDesc::default(),
+                multisampling: None,
+                baked_states: BakedStates {
+                    viewport: Some(Viewport {
+                        rect: Rect {
+                            x: x as _,
+                            y: y as _,
+                            w: width as _,
+                            h: height as _,
+                        },
+                        depth: 0.0..1.0,
+                    }),
+                    scissor: Some(Rect {
+                        x: 0,
+                        y: 0,
+                        w: extent.width as _,
+                        h: extent.height as _,
+                    }),
+                    blend_color: None,
+                    depth_bounds: None,
+                },
+                layout: &layout,
+                subpass: Subpass {
+                    index: 0,
+                    main_pass: &render_pass,
+                },
+                flags: PipelineCreationFlags::empty(),
+                parent: BasePipeline::None,
+            })
+            .unwrap();
+
+        device.destroy_shader_module(vertex);
+        device.destroy_shader_module(fragment);
+
+        Quad {
+            render_pass,
+            set_layout,
+            pool,
+            set,
+            layout,
+            pipeline,
+            view,
+            sampler,
+        }
+    }
+
+    /// Record drawing of the image into the framebuffer.
+    /// The image is in `ShaderReadOnlyOptimal` layout as declared by the node.
+    fn record(
+        &self,
+        cbuf: &mut B::CommandBuffer,
+        framebuffer: &B::Framebuffer,
+        extent: image::Extent,
+    ) {
+        cbuf.begin_render_pass(
+            &self.render_pass,
+            framebuffer,
+            Rect {
+                x: 0,
+                y: 0,
+                w: extent.width as _,
+                h: extent.height as _,
+            },
+            Some(ClearValueRaw {
+                color: ClearColorRaw {
+                    float32: [0.0, 0.0, 0.0, 1.0],
+                },
+            }),
+            SubpassContents::Inline,
+        );
+        cbuf.bind_graphics_pipeline(&self.pipeline);
+        cbuf.bind_graphics_descriptor_sets(
+            &self.layout,
+            0,
+            Some(&self.set),
+            empty::<DescriptorSetOffset>(),
+        );
+        cbuf.draw(0..3, 0..1);
+        cbuf.end_render_pass();
+    }
+
+    fn dispose<D>(self, device: &mut D)
+    where
+        D: Device<B>,
+    {
+        device.destroy_graphics_pipeline(self.pipeline);
+        device.destroy_pipeline_layout(self.layout);
+        drop(self.set);
+        self.pool.dispose(device);
+        device.destroy_descriptor_set_layout(self.set_layout);
+        device.destroy_sampler(self.sampler);
+        device.destroy_image_view(self.view);
+        device.destroy_render_pass(self.render_pass);
+    }
+}
+
+/// Record transfer of the image into the backbuffer image.
+/// Images are copied as is if formats and extents match. Otherwise image is blitted.
+fn record_transfer<B>(
+    cbuf: &mut B::CommandBuffer,
+    (src, src_layout, src_format, src_extent): (&B::Image, image::Layout, Format, image::Extent),
+    (dst, dst_format, dst_extent): (&B::Image, Format, image::Extent),
+    scaling: Scaling,
+    filter: image::Filter,
+) where
+    B: Backend,
+{
+    let src_subresource = image::SubresourceLayers {
+        aspects: src_format.surface_desc().aspects,
+        level: 0,
+        layers: 0..1,
+    };
+    let dst_subresource = image::SubresourceLayers {
+        aspects: dst_format.surface_desc().aspects,
+        level: 0,
+        layers: 0..1,
+    };
+
+    if src_format == dst_format && src_extent == dst_extent {
+        cbuf.copy_image(
+            src,
+            src_layout,
+            dst,
+            image::Layout::TransferDstOptimal,
+            Some(ImageCopy {
+                src_subresource,
+                src_offset: image::Offset { x: 0, y: 0, z: 0 },
+                dst_subresource,
+                dst_offset: image::Offset { x: 0, y: 0, z: 0 },
+                extent: src_extent,
+            }),
+        );
+        return;
+    }
+
+    let dst_bounds = match scaling {
+        Scaling::Stretch => (0, 0, dst_extent.width, dst_extent.height),
+        Scaling::Letterbox => {
+            cbuf.clear_image(
+                dst,
+                image::Layout::TransferDstOptimal,
+                ClearColorRaw {
+                    float32: [0.0, 0.0, 0.0, 1.0],
+                },
+                ClearDepthStencilRaw {
+                    depth: 0.0,
+                    stencil: 0,
+                },
+                Some(image::SubresourceRange {
+                    aspects: dst_format.surface_desc().aspects,
+                    levels: 0..1,
+                    layers: 0..1,
+                }),
+            );
+            cbuf.pipeline_barrier(
+                PipelineStage::TRANSFER..PipelineStage::TRANSFER,
+                Dependencies::empty(),
+                Some(Barrier::Image {
+                    states: (
+                        image::Access::TRANSFER_WRITE,
+                        image::Layout::TransferDstOptimal,
+                    )
+                        ..(
+                            image::Access::TRANSFER_WRITE,
+                            image::Layout::TransferDstOptimal,
+                        ),
+                    target: dst,
+                    range: image::SubresourceRange {
+                        aspects: dst_format.surface_desc().aspects,
+                        levels: 0..1,
+                        layers: 0..1,
+                    },
+                }),
+            );
+            letterbox(src_extent, dst_extent)
+        }
+    };
+
+    let (x, y, width, height) = dst_bounds;
+    cbuf.blit_image(
+        src,
+        src_layout,
+        dst,
+        image::Layout::TransferDstOptimal,
+        filter,
+        Some(ImageBlit {
+            src_subresource,
+            src_bounds: image::Offset { x: 0, y: 0, z: 0 }..image::Offset {
+                x: src_extent.width as i32,
+                y: src_extent.height as i32,
+                z: 1,
+            },
+            dst_subresource,
+            dst_bounds: image::Offset {
+                x: x as i32,
+                y: y as i32,
+                z: 0,
+            }..image::Offset {
+                x: (x + width) as i32,
+                y: (y + height) as i32,
+                z: 1,
+            },
+        }),
+    );
+}
+
+/// Find largest rectangle with aspect ratio of `src` centered in `dst`.
+/// Returns `(x, y, width, height)`.
+fn letterbox(src: image::Extent, dst: image::Extent) -> (u32, u32, u32, u32) {
+    let (src_w, src_h) = (src.width as u64, src.height as u64);
+    let (dst_w, dst_h) = (dst.width as u64, dst.height as u64);
+    if src_w == 0 || src_h == 0 {
+        return (0, 0, 0, 0);
+    }
+
+    let (width, height) = if dst_w * src_h <= dst_h * src_w {
+        (dst_w, src_h * dst_w / src_w)
+    } else {
+        (src_w * dst_h / src_h, dst_h)
+    };
+
+    (
+        ((dst_w - width) / 2) as u32,
+        ((dst_h - height) / 2) as u32,
+        width as u32,
+        height as u32,
+    )
+}
+
+fn create_swapchain<B, D>(
+    surface: &mut B::Surface,
+    extent: image::Extent,
+    usage: image::Usage,
+    format: Format,
+    present_mode: PresentMode,
+    image_count: u32,
+    old_swapchain: Option<B::Swapchain>,
+    device: &mut D,
+) -> (B::Swapchain, Backbuffer<B>)
+where
+    B: Backend,
+    D: Device<B>,
+{
+    let extent = extent.into();
+    device.create_swapchain(
+        surface,
+        SwapchainConfig {
+            present_mode,
+            color_format: format,
+            depth_stencil_format: None,
+            image_count,
+            image_usage: usage,
+        },
+        old_swapchain,
+        &extent,
+    )
+}
+
+/// Pick requested present mode if supported or closest supported one.
+/// `PresentMode::Fifo` is always supported and picked if supported modes are unknown.
+fn pick_present_mode(requested: PresentMode, supported: &[PresentMode]) -> PresentMode {
+    if requested == PresentMode::Fifo || supported.contains(&requested) {
+        return requested;
+    }
+    if supported.is_empty() {
+        warn!(
+            "Present mode {:?} requested without supported modes. Fallback to {:?}",
+            requested,
+            PresentMode::Fifo
+        );
+        return PresentMode::Fifo;
+    }
+
+    let fallbacks: &[PresentMode] = match requested {
+        PresentMode::Immediate => &[PresentMode::Mailbox, PresentMode::Relaxed],
+        PresentMode::Mailbox => &[PresentMode::Immediate, PresentMode::Relaxed],
+        _ => &[],
+    };
+
+    let picked = fallbacks
+        .iter()
+        .cloned()
+        .find(|mode| supported.contains(mode))
+        .unwrap_or(PresentMode::Fifo);
+    warn!(
+        "Present mode {:?} is not supported. Fallback to {:?}",
+        requested, picked
+    );
+    picked
+}
+
+/// Pick extent of the swapchain.
+/// Current extent of the surface is used if reported.
+/// Otherwise the swapchain defines the extent and `extent` is clamped to the supported range.
+fn pick_extent(capabilities: &SurfaceCapabilities, extent: image::Extent) -> image::Extent {
+    match capabilities.current_extent {
+        Some(current) => image::Extent {
+            width: current.width,
+            height: current.height,
+            depth: 1,
+        },
+        None => {
+            let Range { ref start, ref end } = capabilities.extents;
+            image::Extent {
+                width: extent.width.max(start.width).min(end.width),
+                height: extent.height.max(start.height).min(end.height),
+                depth: 1,
+            }
+        }
+    }
+}
+
+/// Pick requested number of images clamped to supported range. 3 images are preferred by default.
+fn pick_image_count(requested: Option<u32>, capabilities: &SurfaceCapabilities) -> u32 {
+    let Range { start, end } = capabilities.image_count.clone();
+    requested.unwrap_or(3).max(start).min(end)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use hal::window::Extent2D;
+
+    fn capabilities(image_count: Range<u32>) -> SurfaceCapabilities {
+        let extent = Extent2D {
+            width: 640,
+            height: 480,
+        };
+        SurfaceCapabilities {
+            image_count,
+            current_extent: Some(extent),
+            extents: extent..extent,
+            max_image_layers: 1,
         }
     }
 
-    fn dispose(self: Box<Self>, _device: &mut D, _aux: &mut T) {
-        unimplemented!()
+    #[test]
+    fn supported_present_mode_is_picked() {
+        let supported = [PresentMode::Fifo, PresentMode::Mailbox];
+        assert_eq!(pick_present_mode(PresentMode::Mailbox, &supported), PresentMode::Mailbox);
+        assert_eq!(pick_present_mode(PresentMode::Fifo, &supported), PresentMode::Fifo);
+    }
+
+    #[test]
+    fn fifo_is_picked_for_unknown_surface() {
+        assert_eq!(pick_present_mode(PresentMode::Immediate, &[]), PresentMode::Fifo);
+        assert_eq!(pick_present_mode(PresentMode::Fifo, &[]), PresentMode::Fifo);
+    }
+
+    #[test]
+    fn unsupported_present_mode_falls_back() {
+        let fifo = [PresentMode::Fifo];
+        let relaxed = [PresentMode::Fifo, PresentMode::Relaxed];
+        let mailbox = [PresentMode::Fifo, PresentMode::Mailbox];
+        let immediate = [PresentMode::Fifo, PresentMode::Immediate, PresentMode::Relaxed];
+
+        assert_eq!(pick_present_mode(PresentMode::Immediate, &mailbox), PresentMode::Mailbox);
+        assert_eq!(pick_present_mode(PresentMode::Immediate, &relaxed), PresentMode::Relaxed);
+        assert_eq!(pick_present_mode(PresentMode::Immediate, &fifo), PresentMode::Fifo);
+        assert_eq!(pick_present_mode(PresentMode::Mailbox, &immediate), PresentMode::Immediate);
+        assert_eq!(pick_present_mode(PresentMode::Mailbox, &relaxed), PresentMode::Relaxed);
+        assert_eq!(pick_present_mode(PresentMode::Mailbox, &fifo), PresentMode::Fifo);
+        assert_eq!(pick_present_mode(PresentMode::Relaxed, &mailbox), PresentMode::Fifo);
+    }
+
+    #[test]
+    fn extent_is_picked_from_capabilities() {
+        let mut caps = capabilities(2..4);
+        assert_eq!(pick_extent(&caps, extent(800, 600)), extent(640, 480));
+
+        caps.current_extent = None;
+        caps.extents = Extent2D {
+            width: 1,
+            height: 1,
+        }..Extent2D {
+            width: 1024,
+            height: 512,
+        };
+        assert_eq!(pick_extent(&caps, extent(800, 600)), extent(800, 512));
+        assert_eq!(pick_extent(&caps, extent(0, 0)), extent(1, 1));
+    }
+
+    #[test]
+    fn image_count_is_clamped() {
+        let caps = capabilities(2..4);
+        assert_eq!(pick_image_count(None, &caps), 3);
+        assert_eq!(pick_image_count(Some(2), &caps), 2);
+        assert_eq!(pick_image_count(Some(4), &caps), 4);
+        assert_eq!(pick_image_count(Some(1), &caps), 2);
+        assert_eq!(pick_image_count(Some(8), &caps), 4);
+
+        // Default count is clamped too.
+        assert_eq!(pick_image_count(None, &capabilities(1..2)), 2);
+        assert_eq!(pick_image_count(None, &capabilities(4..8)), 4);
+    }
+
+    fn extent(width: u32, height: u32) -> image::Extent {
+        image::Extent {
+            width,
+            height,
+            depth: 1,
+        }
+    }
+
+    #[test]
+    fn letterbox_fills_matching_aspect_ratio() {
+        assert_eq!(letterbox(extent(4, 3), extent(800, 600)), (0, 0, 800, 600));
+        assert_eq!(letterbox(extent(800, 600), extent(800, 600)), (0, 0, 800, 600));
+    }
+
+    #[test]
+    fn letterbox_centers_non_matching_aspect_ratio() {
+        // Wider source leaves bars at the top and bottom.
+        assert_eq!(letterbox(extent(16, 9), extent(800, 600)), (0, 75, 800, 450));
+
+        // Taller source leaves bars at the left and right.
+        assert_eq!(letterbox(extent(3, 4), extent(800, 600)), (175, 0, 450, 600));
+
+        // Odd remainder is rounded down.
+        assert_eq!(letterbox(extent(1, 1), extent(5, 2)), (1, 0, 2, 2));
+
+        // Products don't overflow `u32`.
+        assert_eq!(letterbox(extent(65536, 1), extent(65536, 65536)), (0, 32767, 65536, 1));
+    }
+
+    #[test]
+    fn letterbox_of_empty_extent_is_empty() {
+        assert_eq!(letterbox(extent(0, 3), extent(800, 600)), (0, 0, 0, 0));
+        assert_eq!(letterbox(extent(4, 3), extent(0, 0)), (0, 0, 0, 0));
     }
 }
@@ -0,0 +1,629 @@
+use {
+    chain::{
+        chain::{BufferChains, ImageChains}, pass::{Pass, PassId, StateUsage},
+        resource::{Buffer, BufferLayout, Image, State}, schedule::Submission, sync::SyncData,
+    },
+    either::Either,
+    hal::{
+        adapter::MemoryType, buffer,
+        command::{BufferCopy, BufferImageCopy, CommandBufferFlags, RawCommandBuffer, RawLevel},
+        format::Format, image, memory::Properties,
+        pool::{CommandPoolCreateFlags, RawCommandPool}, pso::PipelineStage,
+        queue::{QueueFamily, RawCommandQueue, RawSubmission, Transfer}, Backend, Device,
+        MemoryTypeId,
+    },
+    node::{
+        barriers::BarrierBatch, build::pick_queue_family, low::{AnyNode, AnyNodeBuilder},
+        present::PresentError,
+    },
+    smallvec::SmallVec, std::{borrow::Borrow, collections::HashMap, iter::once, ops::Range, slice},
+    util::*,
+};
+
+/// Data read back from the graph resource.
+#[derive(Clone, Debug)]
+pub struct Readout {
+    /// Index of the frame the data was copied at. Counted by the node from `0`.
+    pub frame: u64,
+
+    /// Copied bytes.
+    pub data: Vec<u8>,
+
+    /// Number of bytes between rows of the image.
+    /// Rows of texel blocks for block-compressed formats. Size of the data for buffers.
+    pub row_pitch: usize,
+
+    /// Format of the image. `None` for buffers.
+    pub format: Option<Format>,
+
+    /// Extent of the image. `None` for buffers.
+    pub extent: Option<image::Extent>,
+}
+
+/// Builder for `ReadbackNode`.
+/// Added to the graph with `GraphBuilder::add_readback`.
+#[derive(Clone)]
+pub struct ReadbackBuilder {
+    source: Either<ImageId, BufferId>,
+    memory_types: Vec<MemoryType>,
+    frames: usize,
+    dependencies: Vec<PassId>,
+}
+
+impl ReadbackBuilder {
+    /// Create builder for the node reading back the image.
+    ///
+    /// # Parameters
+    ///
+    /// `id`            - image to read back. Directly presented, history and combined
+    ///                   depth stencil images are not supported.
+    ///
+    /// `memory_types`  - memory types of the physical device.
+    ///                   Staging buffers are allocated from the host visible one.
+    pub fn image<M>(id: ImageId, memory_types: M) -> Self
+    where
+        M: IntoIterator<Item = MemoryType>,
+    {
+        ReadbackBuilder::new(Either::Left(id), memory_types)
+    }
+
+    /// Create builder for the node reading back the buffer.
+    ///
+    /// # Parameters
+    ///
+    /// `id`            - buffer to read back.
+    ///
+    /// `memory_types`  - memory types of the physical device.
+    ///                   Staging buffers are allocated from the host visible one.
+    pub fn buffer<M>(id: BufferId, memory_types: M) -> Self
+    where
+        M: IntoIterator<Item = MemoryType>,
+    {
+        ReadbackBuilder::new(Either::Right(id), memory_types)
+    }
+
+    fn new<M>(source: Either<ImageId, BufferId>, memory_types: M) -> Self
+    where
+        M: IntoIterator<Item = MemoryType>,
+    {
+        ReadbackBuilder {
+            source,
+            memory_types: memory_types.into_iter().collect(),
+            frames: 2,
+            dependencies: Vec::new(),
+        }
+    }
+
+    /// Number of staging buffers the node cycles through.
+    /// Copy is skipped for the frame if the copy into the next buffer hasn't completed yet.
+    /// Completed data that wasn't read is overwritten. 2 buffers are used by default.
+    pub fn with_frames(mut self, frames: usize) -> Self {
+        assert!(frames > 0);
+        self.frames = frames;
+        self
+    }
+
+    /// Add dependency to another node.
+    /// Nodes added before the readback that write the resource are dependencies already.
+    pub fn with_dependency(mut self, id: NodeId) -> Self {
+        self.dependencies.push(id.0);
+        self
+    }
+
+    /// Add dependencies to the readback builder.
+    pub fn with_dependencies<I>(mut self, deps: I) -> Self
+    where
+        I: IntoIterator<Item = PassId>,
+    {
+        self.dependencies.extend(deps);
+        self
+    }
+
+    /// Image or buffer read back by the node.
+    pub(crate) fn source(&self) -> Either<ImageId, BufferId> {
+        self.source
+    }
+
+    /// Pick host visible memory type compatible with the requirements.
+    /// Coherent memory is preferred.
+    fn memory_type(&self, type_mask: u64) -> (MemoryTypeId, Properties) {
+        let compatible = |&(id, ref memory_type): &(usize, &MemoryType)| {
+            type_mask & (1u64 << id) != 0
+                && memory_type.properties.contains(Properties::CPU_VISIBLE)
+        };
+        self.memory_types
+            .iter()
+            .enumerate()
+            .filter(&compatible)
+            .find(|&(_, memory_type)| memory_type.properties.contains(Properties::COHERENT))
+            .or_else(|| self.memory_types.iter().enumerate().find(&compatible))
+            .map(|(id, memory_type)| (MemoryTypeId(id), memory_type.properties))
+            .expect("Host visible memory is required for readback")
+    }
+}
+
+impl<B, D, T, U, I> AnyNodeBuilder<B, D, T, U, I> for ReadbackBuilder
+where
+    B: Backend,
+    D: Device<B>,
+    U: Borrow<B::Buffer>,
+    I: Borrow<B::Image>,
+{
+    fn name(&self) -> &str {
+        "ReadbackNode"
+    }
+
+    fn pass(&self, id: PassId, families: &[&B::QueueFamily]) -> Pass {
+        let (buffers, images) = match self.source {
+            Either::Left(image) => (
+                HashMap::new(),
+                once((
+                    image.0,
+                    StateUsage {
+                        state: State {
+                            access: image::Access::TRANSFER_READ,
+                            layout: image::Layout::TransferSrcOptimal,
+                            stages: PipelineStage::TRANSFER,
+                        },
+                        usage: image::Usage::TRANSFER_SRC,
+                    },
+                )).collect(),
+            ),
+            Either::Right(buffer) => (
+                once((
+                    buffer.0,
+                    StateUsage {
+                        state: State {
+                            access: buffer::Access::TRANSFER_READ,
+                            layout: BufferLayout,
+                            stages: PipelineStage::TRANSFER,
+                        },
+                        usage: buffer::Usage::TRANSFER_SRC,
+                    },
+                )).collect(),
+                HashMap::new(),
+            ),
+        };
+
+        Pass {
+            id,
+            family: pick_queue_family::<B, Transfer, _>(families.iter().cloned()),
+            queue: None,
+            dependencies: self.dependencies.clone(),
+            buffers,
+            images,
+        }
+    }
+
+    fn alternate(&self) -> Option<Box<AnyNodeBuilder<B, D, T, U, I>>> {
+        Some(Box::new(self.clone()))
+    }
+
+    fn build(
+        self: Box<Self>,
+        _: &Submission<SyncData<usize, usize>>,
+        sync: &SyncData<usize, usize>,
+        _: &BufferChains,
+        buffers: &[BufferResource<U>],
+        _: &ImageChains,
+        images: &[&GraphImage<B, I>],
+        _: &HistoryPairs,
+        family: &B::QueueFamily,
+        device: &mut D,
+        _aux: &mut T,
+    ) -> Box<AnyNode<B, D, T>> {
+        let layout = match self.source {
+            Either::Left(id) => {
+                let resource = images[id.0.index() as usize]
+                    .as_ref()
+                    .left()
+                    .expect("Directly presented images can't be read back");
+                let extent = resource.kind.extent();
+                let (row_pitch, rows) = pitch(resource.format, extent);
+                ReadbackLayout {
+                    size: (row_pitch * rows * extent.depth as usize) as u64,
+                    row_pitch,
+                    format: Some(resource.format),
+                    extent: Some(extent),
+                }
+            }
+            Either::Right(id) => {
+                let size = buffers[id.0.index() as usize].size;
+                ReadbackLayout {
+                    size,
+                    row_pitch: size as usize,
+                    format: None,
+                    extent: None,
+                }
+            }
+        };
+
+        let mut pool = device.create_command_pool(family.id(), CommandPoolCreateFlags::empty());
+        let mut cbufs = pool.allocate(self.frames + 1, RawLevel::Primary);
+
+        // Graph barriers alone are submitted when the copy is skipped.
+        let mut skip = cbufs.pop().unwrap();
+        skip.begin(CommandBufferFlags::EMPTY, Default::default());
+        match self.source {
+            Either::Left(id) => {
+                let resource = images[id.0.index() as usize].as_ref().left().unwrap();
+                record_image_copy::<B, _>(&mut skip, sync, id, resource, None);
+            }
+            Either::Right(id) => {
+                let resource = &buffers[id.0.index() as usize];
+                record_buffer_copy::<B, _>(&mut skip, sync, id, resource, None);
+            }
+        }
+        skip.finish();
+
+        let slots = cbufs
+            .into_iter()
+            .map(|mut cbuf| {
+                let unbound = device
+                    .create_buffer(layout.size, buffer::Usage::TRANSFER_DST)
+                    .unwrap();
+                let requirements = device.get_buffer_requirements(&unbound);
+                let (memory_type, properties) = self.memory_type(requirements.type_mask);
+                let memory = device
+                    .allocate_memory(memory_type, requirements.size)
+                    .unwrap();
+                let staging = device.bind_buffer_memory(&memory, 0, unbound).unwrap();
+
+                cbuf.begin(CommandBufferFlags::EMPTY, Default::default());
+                match self.source {
+                    Either::Left(id) => {
+                        let resource = images[id.0.index() as usize].as_ref().left().unwrap();
+                        record_image_copy::<B, _>(&mut cbuf, sync, id, resource, Some(&staging));
+                    }
+                    Either::Right(id) => {
+                        let resource = &buffers[id.0.index() as usize];
+                        record_buffer_copy::<B, _>(&mut cbuf, sync, id, resource, Some(&staging));
+                    }
+                }
+                cbuf.finish();
+
+                Slot {
+                    buffer: staging,
+                    memory,
+                    coherent: properties.contains(Properties::COHERENT),
+                    fence: device.create_fence(false),
+                    cbuf,
+                    frame: None,
+                }
+            })
+            .collect();
+
+        Box::new(ReadbackNode {
+            source: self.source,
+            layout,
+            slots,
+            skip,
+            frame: 0,
+            pool,
+        })
+    }
+}
+
+/// Node that copies graph image or buffer into host visible memory.
+/// Data becomes available once commands of the frame complete.
+/// See `Graph::readback`.
+pub struct ReadbackNode<B: Backend> {
+    source: Either<ImageId, BufferId>,
+    layout: ReadbackLayout,
+    slots: Vec<Slot<B>>,
+    /// Command buffer with graph barriers alone.
+    skip: B::CommandBuffer,
+    frame: u64,
+    pool: B::CommandPool,
+}
+
+/// Layout of the data in staging buffers.
+struct ReadbackLayout {
+    size: u64,
+    row_pitch: usize,
+    format: Option<Format>,
+    extent: Option<image::Extent>,
+}
+
+/// Staging buffer along with command buffer that fills it.
+struct Slot<B: Backend> {
+    buffer: B::Buffer,
+    memory: B::Memory,
+    coherent: bool,
+    fence: B::Fence,
+    cbuf: B::CommandBuffer,
+    frame: Option<u64>,
+}
+
+impl<B> ReadbackNode<B>
+where
+    B: Backend,
+{
+    /// Id of the image read back by the node.
+    pub fn image(&self) -> Option<ImageId> {
+        self.source.left()
+    }
+
+    /// Id of the buffer read back by the node.
+    pub fn buffer(&self) -> Option<BufferId> {
+        self.source.right()
+    }
+
+    /// Take latest data which copy has completed.
+    /// Older completed data is discarded.
+    /// Returns `None` if no new data is available.
+    pub(crate) fn poll<D>(&mut self, device: &mut D) -> Option<Readout>
+    where
+        D: Device<B>,
+    {
+        let completed = (0..self.slots.len())
+            .filter(|&index| {
+                let ref slot = self.slots[index];
+                slot.frame.is_some() && device.get_fence_status(&slot.fence)
+            })
+            .collect::<SmallVec<[_; 4]>>();
+        let latest = completed
+            .iter()
+            .cloned()
+            .max_by_key(|&index| self.slots[index].frame);
+        for &index in &completed {
+            if Some(index) != latest {
+                self.slots[index].frame = None;
+            }
+        }
+
+        latest.map(|index| {
+            let size = self.layout.size;
+            let ref mut slot = self.slots[index];
+            let data = unsafe {
+                let ptr = device.map_memory(&slot.memory, 0..size).unwrap();
+                if !slot.coherent {
+                    device.invalidate_mapped_memory_ranges(Some((&slot.memory, 0..size)));
+                }
+                let data = slice::from_raw_parts(ptr as *const u8, size as usize).to_vec();
+                device.unmap_memory(&slot.memory);
+                data
+            };
+
+            Readout {
+                frame: slot.frame.take().unwrap(),
+                data,
+                row_pitch: self.layout.row_pitch,
+                format: self.layout.format,
+                extent: self.layout.extent,
+            }
+        })
+    }
+}
+
+impl<B, D, T> AnyNode<B, D, T> for ReadbackNode<B>
+where
+    B: Backend,
+    D: Device<B>,
+{
+    fn run<'a>(
+        &'a mut self,
+        frame: &FrameInfo<B>,
+        sync: &SyncData<usize, usize>,
+        queue: &mut B::CommandQueue,
+        semaphores: &mut [B::Semaphore],
+        acquire: Option<(&B::Semaphore, PipelineStage)>,
+        fence: Option<&B::Fence>,
+        device: &mut D,
+        _aux: &'a T,
+    ) -> Result<(), PresentError> {
+        profile!("Readback::run");
+        assert!(sync.acquire.signal.is_empty());
+        assert!(sync.release.wait.is_empty());
+
+        let index = (self.frame % self.slots.len() as u64) as usize;
+        let ref mut slot = self.slots[index];
+
+        // Completed data that wasn't read is overwritten.
+        // Copy is skipped while the staging buffer is still being written.
+        let copy = slot.frame.is_none() || device.get_fence_status(&slot.fence);
+        let (cbuf, slot_fence) = if copy {
+            trace!("Read back frame {}", self.frame);
+            device.reset_fence(&slot.fence);
+            (&slot.cbuf, Some(&slot.fence))
+        } else {
+            trace!("Skip readback of frame {}. Staging buffer is in use", self.frame);
+            (&self.skip, None)
+        };
+
+        let wait = sync
+            .acquire
+            .wait
+            .iter()
+            .map(|wait| (&semaphores[*wait.semaphore()], wait.stage()))
+            .chain(acquire)
+            .collect::<SmallVec<[_; 8]>>();
+        let signal = sync
+            .release
+            .signal
+            .iter()
+            .map(|signal| &semaphores[*signal.semaphore()])
+            .collect::<SmallVec<[_; 8]>>();
+
+        unsafe {
+            queue.submit_raw(
+                RawSubmission {
+                    wait_semaphores: &wait,
+                    signal_semaphores: &signal,
+                    cmd_buffers: frame.command_buffers(Some(cbuf)),
+                },
+                slot_fence,
+            );
+
+            if fence.is_some() {
+                // Graph fence is signaled after all work submitted to the queue.
+                queue.submit_raw(
+                    RawSubmission {
+                        wait_semaphores: &[],
+                        signal_semaphores: &[],
+                        cmd_buffers: None::<&B::CommandBuffer>,
+                    },
+                    fence,
+                );
+            }
+        }
+
+        if copy {
+            slot.frame = Some(self.frame);
+        }
+        self.frame += 1;
+        Ok(())
+    }
+
+    fn readback(&mut self) -> Option<&mut ReadbackNode<B>> {
+        Some(self)
+    }
+
+    fn dispose(mut self: Box<Self>, device: &mut D, _aux: &mut T) {
+        let mut cbufs = Vec::new();
+        for slot in self.slots.drain(..) {
+            if slot.frame.is_some() {
+                device.wait_for_fence(&slot.fence, !0);
+            }
+            device.destroy_fence(slot.fence);
+            device.destroy_buffer(slot.buffer);
+            device.free_memory(slot.memory);
+            cbufs.push(slot.cbuf);
+        }
+        cbufs.push(self.skip);
+        self.pool.free(cbufs);
+        device.destroy_command_pool(self.pool);
+    }
+}
+
+/// Barrier that makes staging buffer written by the copy visible to the host.
+fn staging_barrier() -> Range<(buffer::State, PipelineStage)> {
+    (buffer::Access::TRANSFER_WRITE, PipelineStage::TRANSFER)
+        ..(buffer::Access::HOST_READ, PipelineStage::HOST)
+}
+
+/// Number of bytes between rows of texel blocks and number of block rows of the image.
+fn pitch(format: Format, extent: image::Extent) -> (usize, usize) {
+    let desc = format.surface_desc();
+    let (width, height) = (desc.dim.0 as u32, desc.dim.1 as u32);
+    let columns = (extent.width + width - 1) / width;
+    let rows = (extent.height + height - 1) / height;
+    (columns as usize * desc.bits as usize / 8, rows as usize)
+}
+
+/// Record copy of the image into staging buffer surrounded by graph barriers.
+/// Only barriers are recorded without staging buffer.
+fn record_image_copy<B, I>(
+    cbuf: &mut B::CommandBuffer,
+    sync: &SyncData<usize, usize>,
+    id: ImageId,
+    resource: &ImageResource<I>,
+    staging: Option<&B::Buffer>,
+) where
+    B: Backend,
+    I: Borrow<B::Image>,
+{
+    let desc = resource.format.surface_desc();
+    let aspects = desc.aspects;
+    let extent = resource.kind.extent();
+    let state = |state: &State<Image>| ((state.access, state.layout), state.stages);
+
+    let mut acquire = BarrierBatch::new();
+    if let Some(barrier) = sync.acquire.images.get(&id.0) {
+        let Range { ref start, ref end } = barrier.states;
+        acquire.image(&(state(start)..state(end)), resource.image.borrow(), aspects);
+    }
+    acquire.record(cbuf);
+
+    if let Some(staging) = staging {
+        // Rows of the buffer are whole texel blocks.
+        let (width, height) = (desc.dim.0 as u32, desc.dim.1 as u32);
+        cbuf.copy_image_to_buffer(
+            resource.image.borrow(),
+            image::Layout::TransferSrcOptimal,
+            staging,
+            Some(BufferImageCopy {
+                buffer_offset: 0,
+                buffer_width: (extent.width + width - 1) / width * width,
+                buffer_height: (extent.height + height - 1) / height * height,
+                image_layers: image::SubresourceLayers {
+                    aspects,
+                    level: 0,
+                    layers: 0..1,
+                },
+                image_offset: image::Offset::ZERO,
+                image_extent: extent,
+            }),
+        );
+    }
+
+    let mut release = BarrierBatch::new();
+    if let Some(barrier) = sync.release.images.get(&id.0) {
+        let Range { ref start, ref end } = barrier.states;
+        release.image(&(state(start)..state(end)), resource.image.borrow(), aspects);
+    }
+    if let Some(staging) = staging {
+        release.buffer(&staging_barrier(), staging);
+    }
+    release.record(cbuf);
+}
+
+/// Record copy of the buffer into staging buffer surrounded by graph barriers.
+/// Only barriers are recorded without staging buffer.
+fn record_buffer_copy<B, U>(
+    cbuf: &mut B::CommandBuffer,
+    sync: &SyncData<usize, usize>,
+    id: BufferId,
+    resource: &BufferResource<U>,
+    staging: Option<&B::Buffer>,
+) where
+    B: Backend,
+    U: Borrow<B::Buffer>,
+{
+    let state = |state: &State<Buffer>| (state.access, state.stages);
+
+    let mut acquire = BarrierBatch::new();
+    if let Some(barrier) = sync.acquire.buffers.get(&id.0) {
+        let Range { ref start, ref end } = barrier.states;
+        acquire.buffer(&(state(start)..state(end)), resource.buffer.borrow());
+    }
+    acquire.record(cbuf);
+
+    if let Some(staging) = staging {
+        cbuf.copy_buffer(
+            resource.buffer.borrow(),
+            staging,
+            Some(BufferCopy {
+                src: 0,
+                dst: 0,
+                size: resource.size,
+            }),
+        );
+    }
+
+    let mut release = BarrierBatch::new();
+    if let Some(barrier) = sync.release.buffers.get(&id.0) {
+        let Range { ref start, ref end } = barrier.states;
+        release.buffer(&(state(start)..state(end)), resource.buffer.borrow());
+    }
+    if let Some(staging) = staging {
+        release.buffer(&staging_barrier(), staging);
+    }
+    release.record(cbuf);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pitch_counts_texel_blocks() {
+        let extent = image::Extent {
+            width: 10,
+            height: 6,
+            depth: 1,
+        };
+        assert_eq!(pitch(Format::Rgba8Unorm, extent), (40, 6));
+        assert_eq!(pitch(Format::Bc1RgbUnorm, extent), (24, 2));
+    }
+}
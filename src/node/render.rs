@@ -1,25 +1,28 @@
 use std::{
-    borrow::Borrow, iter::{empty, once, Empty}, ops::{Index, Range},
+    borrow::Borrow, iter::Empty, ops::{Index, Range},
+    sync::{atomic::{AtomicUsize, Ordering}, Arc},
 };
 
 use hal::{
     buffer,
     command::{
-        ClearColor, ClearValue, CommandBuffer, CommandBufferFlags, MultiShot, OneShot, Primary,
-        RawCommandBuffer, RawLevel, RenderPassInlineEncoder, Submit, Submittable,
+        BufferCopy, BufferImageCopy, ClearColor, ClearColorRaw, ClearDepthStencilRaw, ClearValue,
+        CommandBufferFlags, ImageCopy, MultiShot, OneShot, Primary, RawCommandBuffer, RawLevel,
+        RenderPassInlineEncoder, Submit, Submittable,
     },
-    format::{Format, Swizzle}, image, image::Extent, memory::{Barrier, Dependencies},
+    format::{Aspects, Format, Swizzle}, image, image::Extent, memory::{Barrier, Dependencies},
     pass::{
         Attachment, AttachmentLoadOp, AttachmentOps, AttachmentStoreOp, Subpass, SubpassDependency,
-        SubpassDesc,
+        SubpassDesc, SubpassRef,
     },
     pool::{CommandPool, CommandPoolCreateFlags, RawCommandPool},
     pso::{
         AttributeDesc, BakedStates, BasePipeline, BlendDesc, BlendState, BufferIndex,
-        ColorBlendDesc, ColorMask, Comparison, DepthStencilDesc, DepthTest,
-        DescriptorSetLayoutBinding, ElemStride, Element, GraphicsPipelineDesc, GraphicsShaderSet,
-        InputAssemblerDesc, PipelineCreationFlags, PipelineStage, PrimitiveRestart, Rasterizer,
-        Rect, ShaderStageFlags, StencilTest, VertexBufferDesc, Viewport,
+        ColorBlendDesc, ColorMask, Comparison, DepthStencilDesc, DepthTest, Descriptor,
+        DescriptorSetLayoutBinding, DescriptorSetWrite, ElemStride, Element, GraphicsPipelineDesc,
+        GraphicsShaderSet, InputAssemblerDesc, PipelineCreationFlags, PipelineStage,
+        PrimitiveRestart, Rasterizer, Rect, ShaderStageFlags, StencilTest, VertexBufferDesc,
+        Viewport,
     },
     queue::{CommandQueue, Graphics, RawCommandQueue, RawSubmission}, Backend, Device, Primitive,
 };
@@ -28,7 +31,15 @@ use relevant::Relevant;
 
 use smallvec::SmallVec;
 
-use node::{build::NodeBuilder, BufferInfo, ImageInfo, Node, NodeDesc};
+use descriptors::Descriptors;
+use node::{
+    barriers::{record_barriers, BarrierBatch}, build::NodeBuilder, Barriers, BufferInfo,
+    ImageInfo, Node, NodeDesc,
+};
+use util::{BufferId, FrameInfo, ImageId, StorageAccess};
+
+#[cfg(feature = "validation")]
+use node::validate::{Hazard, Validated, Validator};
 
 /// Set layout
 #[derive(Clone, Debug, Default)]
@@ -43,6 +54,67 @@ pub struct Layout {
     pub push_constants: Vec<(ShaderStageFlags, Range<u32>)>,
 }
 
+/// Descriptor binding of a graph image.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct ImageBinding {
+    /// Index of the pipeline layout in `RenderPassDesc::layouts`.
+    pub layout: usize,
+
+    /// Index of the set in the pipeline layout.
+    pub set: usize,
+
+    /// Binding index in the set.
+    pub binding: u32,
+}
+
+/// Role of the graph buffer in the render pass.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum BufferRole {
+    /// Uniform buffer read by shaders.
+    Uniform,
+
+    /// Storage buffer accessed by shaders.
+    Storage(StorageAccess),
+
+    /// Vertex buffer.
+    Vertex,
+
+    /// Index buffer.
+    Index,
+
+    /// Buffer with indirect draw arguments.
+    Indirect,
+}
+
+impl BufferRole {
+    /// Usage, state and stages of the buffer in this role.
+    pub fn usage(self) -> (buffer::Usage, buffer::State, PipelineStage) {
+        match self {
+            BufferRole::Uniform => (
+                buffer::Usage::UNIFORM,
+                buffer::Access::CONSTANT_BUFFER_READ,
+                all_graphics_shaders_stages(),
+            ),
+            BufferRole::Storage(access) => access.buffer(all_graphics_shaders_stages()),
+            BufferRole::Vertex => (
+                buffer::Usage::VERTEX,
+                buffer::Access::VERTEX_BUFFER_READ,
+                PipelineStage::VERTEX_INPUT,
+            ),
+            BufferRole::Index => (
+                buffer::Usage::INDEX,
+                buffer::Access::INDEX_BUFFER_READ,
+                PipelineStage::VERTEX_INPUT,
+            ),
+            BufferRole::Indirect => (
+                buffer::Usage::INDIRECT,
+                buffer::Access::INDIRECT_COMMAND_READ,
+                PipelineStage::DRAW_INDIRECT,
+            ),
+        }
+    }
+}
+
 /// Pipeline info
 #[derive(Clone, Debug)]
 pub struct Pipeline {
@@ -57,6 +129,11 @@ pub trait RenderPassDesc<B: Backend>: Send + Sync + Sized + 'static {
     /// Name of this pass.
     fn name() -> &'static str;
 
+    /// Roles of graph buffers used by the pass.
+    fn buffers() -> Vec<BufferRole> {
+        Vec::new()
+    }
+
     /// Number of images to sample.
     fn sampled() -> usize {
         0
@@ -67,6 +144,28 @@ pub trait RenderPassDesc<B: Backend>: Send + Sync + Sized + 'static {
         0
     }
 
+    /// Access to the storage image from shaders.
+    /// Index of the storage image is in `0 .. storage()`.
+    fn storage_access(_index: usize) -> StorageAccess {
+        StorageAccess::Read
+    }
+
+    /// Descriptor bindings for sampled images along with info for samplers to create.
+    /// Either empty or one for each sampled image.
+    /// If not empty node writes combined image sampler descriptors into sets
+    /// available through `Descriptors::bound`.
+    fn sampled_bindings() -> Vec<(ImageBinding, image::SamplerInfo)> {
+        Vec::new()
+    }
+
+    /// Descriptor bindings for storage images.
+    /// Either empty or one for each storage image.
+    /// If not empty node writes storage image descriptors into sets
+    /// available through `Descriptors::bound`.
+    fn storage_bindings() -> Vec<ImageBinding> {
+        Vec::new()
+    }
+
     /// Number of color output images.
     fn colors() -> usize;
 
@@ -75,6 +174,24 @@ pub trait RenderPassDesc<B: Backend>: Send + Sync + Sized + 'static {
         false
     }
 
+    /// Record graph barriers at the start and the end of the main command buffer
+    /// instead of separate static command buffers.
+    /// Saves two command buffers per submission at the cost of recording barriers every frame.
+    /// Attachments are transitioned by the render pass through its layouts and dependencies.
+    fn inline_barriers() -> bool {
+        false
+    }
+
+    /// Commands recorded by `draw` are the same every frame.
+    /// Render pass is recorded once per framebuffer and resubmitted
+    /// until `RenderPass::dirty` reports changes.
+    /// `prepare` is called only when the render pass is recorded again
+    /// and its commands are submitted once.
+    /// Can't be combined with `inline_barriers`.
+    fn static_commands() -> bool {
+        false
+    }
+
     /// Pipeline layouts
     fn layouts() -> Vec<Layout> {
         vec![Layout {
@@ -142,20 +259,36 @@ where
         I::Item: Borrow<B::ImageView>;
 
     /// Prepare to record drawing commands.
-    fn prepare<A, S>(
+    ///
+    /// # Parameters
+    ///
+    /// `sets`      - descriptor set allocator for set layouts declared by `RenderPassDesc::layouts`.
+    ///               Sets freed here are reused once frames in flight can't use them anymore.
+    ///
+    /// `buffers`   - graph buffers. One for each role returned by `RenderPassDesc::buffers`.
+    ///
+    /// `cbuf`      - command buffer to record commands that must be executed before render pass.
+    ///               Commands touching graph resources are validated with `validation` feature.
+    ///
+    /// `device`    - `Device<B>` implementation. `B::Device` or wrapper.
+    ///
+    /// `aux`       - auxiliary data container. May be anything the implementation desires.
+    ///
+    fn prepare(
         &mut self,
-        sets: &A,
-        cbuf: &mut CommandBuffer<B, Graphics>,
+        sets: &mut Descriptors<B>,
+        buffers: &[&B::Buffer],
+        cbuf: &mut PrepareBuffer<B>,
         device: &mut D,
         aux: &T,
-    ) where
-        A: Index<usize>,
-        A::Output: Index<usize, Output = S>,
-        S: Borrow<B::DescriptorSetLayout>;
+    );
 
     /// Record drawing commands to the command buffer provided.
+    /// `buffers` are graph buffers. One for each role returned by `RenderPassDesc::buffers`.
     fn draw<L, P>(
         &mut self,
+        sets: &Descriptors<B>,
+        buffers: &[&B::Buffer],
         layouts: &L,
         pipelines: &P,
         encoder: RenderPassInlineEncoder<B, Primary>,
@@ -166,10 +299,199 @@ where
         P: Index<usize>,
         P::Output: Borrow<B::GraphicsPipeline>;
 
+    /// Check if commands must be recorded again.
+    /// Called every frame for passes with `RenderPassDesc::static_commands`.
+    fn dirty(&self, _aux: &T) -> bool {
+        false
+    }
+
     /// Dispose of the pass.
     fn dispose(self, device: &mut D, aux: &mut T);
 }
 
+/// Command buffer `RenderPass::prepare` records into.
+/// With `validation` feature commands are checked against declarations of the pass.
+/// See `validate::Validated`.
+pub struct PrepareBuffer<'a, B: Backend + 'a> {
+    #[cfg(feature = "validation")]
+    cbuf: Validated<'a, B, B::CommandBuffer>,
+
+    #[cfg(not(feature = "validation"))]
+    cbuf: &'a mut B::CommandBuffer,
+}
+
+impl<'a, B> PrepareBuffer<'a, B>
+where
+    B: Backend,
+{
+    /// Get wrapped command buffer to record commands that are not validated.
+    #[cfg(feature = "validation")]
+    pub fn raw(&mut self) -> &mut B::CommandBuffer {
+        self.cbuf.raw()
+    }
+
+    /// Get wrapped command buffer to record commands that are not validated.
+    #[cfg(not(feature = "validation"))]
+    pub fn raw(&mut self) -> &mut B::CommandBuffer {
+        self.cbuf
+    }
+
+    /// Register buffer owned by the pass.
+    /// Commands touching buffers neither declared nor owned are reported with `validation` feature.
+    pub fn own_buffer(&mut self, _buffer: &B::Buffer) {
+        #[cfg(feature = "validation")]
+        self.cbuf.own_buffer(_buffer);
+    }
+
+    /// Register image owned by the pass.
+    /// Commands touching images neither declared nor owned are reported with `validation` feature.
+    pub fn own_image(&mut self, _image: &B::Image) {
+        #[cfg(feature = "validation")]
+        self.cbuf.own_image(_image);
+    }
+
+    /// Record pipeline barrier.
+    pub fn pipeline_barrier<'b, T>(
+        &mut self,
+        stages: Range<PipelineStage>,
+        dependencies: Dependencies,
+        barriers: T,
+    ) where
+        T: IntoIterator,
+        T::Item: Borrow<Barrier<'b, B>>,
+    {
+        self.cbuf.pipeline_barrier(stages, dependencies, barriers);
+    }
+
+    /// Record buffer to buffer copy.
+    pub fn copy_buffer<T>(&mut self, src: &B::Buffer, dst: &B::Buffer, regions: T)
+    where
+        T: IntoIterator,
+        T::Item: Borrow<BufferCopy>,
+    {
+        self.cbuf.copy_buffer(src, dst, regions);
+    }
+
+    /// Record image to image copy.
+    pub fn copy_image<T>(
+        &mut self,
+        src: &B::Image,
+        src_layout: image::Layout,
+        dst: &B::Image,
+        dst_layout: image::Layout,
+        regions: T,
+    ) where
+        T: IntoIterator,
+        T::Item: Borrow<ImageCopy>,
+    {
+        self.cbuf
+            .copy_image(src, src_layout, dst, dst_layout, regions);
+    }
+
+    /// Record buffer to image copy.
+    pub fn copy_buffer_to_image<T>(
+        &mut self,
+        src: &B::Buffer,
+        dst: &B::Image,
+        dst_layout: image::Layout,
+        regions: T,
+    ) where
+        T: IntoIterator,
+        T::Item: Borrow<BufferImageCopy>,
+    {
+        self.cbuf.copy_buffer_to_image(src, dst, dst_layout, regions);
+    }
+
+    /// Record image to buffer copy.
+    pub fn copy_image_to_buffer<T>(
+        &mut self,
+        src: &B::Image,
+        src_layout: image::Layout,
+        dst: &B::Buffer,
+        regions: T,
+    ) where
+        T: IntoIterator,
+        T::Item: Borrow<BufferImageCopy>,
+    {
+        self.cbuf.copy_image_to_buffer(src, src_layout, dst, regions);
+    }
+
+    /// Record image clear.
+    pub fn clear_image<T>(
+        &mut self,
+        image: &B::Image,
+        layout: image::Layout,
+        color: ClearColorRaw,
+        depth_stencil: ClearDepthStencilRaw,
+        subresource_ranges: T,
+    ) where
+        T: IntoIterator,
+        T::Item: Borrow<image::SubresourceRange>,
+    {
+        self.cbuf
+            .clear_image(image, layout, color, depth_stencil, subresource_ranges);
+    }
+
+    /// Record buffer update.
+    pub fn update_buffer(&mut self, buffer: &B::Buffer, offset: buffer::Offset, data: &[u8]) {
+        self.cbuf.update_buffer(buffer, offset, data);
+    }
+
+    /// Check that graph resources are returned to declared states.
+    /// Doesn't end recording.
+    fn end(self) {
+        #[cfg(feature = "validation")]
+        self.cbuf.finish();
+    }
+}
+
+/// Graph barriers recorded into the main command buffer.
+/// Attachments are excluded as the render pass transitions them.
+/// See `RenderPassDesc::inline_barriers`.
+struct InlineBarriers {
+    buffers: Vec<(BufferId, Barriers<buffer::State>)>,
+    images: Vec<(ImageId, Barriers<image::State>, Aspects)>,
+}
+
+impl InlineBarriers {
+    fn new<U, I>(buffers: &[BufferInfo<U>], images: &[ImageInfo<I>]) -> Self {
+        InlineBarriers {
+            buffers: buffers
+                .iter()
+                .map(|info| (info.id, info.barriers.clone()))
+                .collect(),
+            images: images
+                .iter()
+                .map(|info| {
+                    let aspects = info.format.surface_desc().aspects;
+                    (info.id, info.barriers.clone(), aspects)
+                })
+                .collect(),
+        }
+    }
+
+    /// Either acquire or release barriers for the frame.
+    fn batch<'a, B>(&self, frame: &FrameInfo<'a, B>, release: bool) -> BarrierBatch<'a, B>
+    where
+        B: Backend,
+    {
+        let mut batch = BarrierBatch::new();
+        for &(id, ref barriers) in &self.buffers {
+            let barrier = if release { &barriers.release } else { &barriers.acquire };
+            if let Some(ref barrier) = *barrier {
+                batch.buffer(barrier, frame.buffer(id));
+            }
+        }
+        for &(id, ref barriers, aspects) in &self.images {
+            let barrier = if release { &barriers.release } else { &barriers.acquire };
+            if let Some(ref barrier) = *barrier {
+                batch.image(barrier, frame.image(id), aspects);
+            }
+        }
+        batch
+    }
+}
+
 /// Render pass node.
 pub struct RenderPassNode<B: Backend, R> {
     relevant: Relevant,
@@ -178,19 +500,31 @@ pub struct RenderPassNode<B: Backend, R> {
 
     render_pass: B::RenderPass,
     pipeline_layouts: Vec<B::PipelineLayout>,
-    set_layouts: Vec<Vec<B::DescriptorSetLayout>>,
+    descriptors: Descriptors<B>,
     graphics_pipelines: Vec<B::GraphicsPipeline>,
 
-    views: Vec<B::ImageView>,
-    framebuffer: B::Framebuffer,
+    buffers: Vec<BufferId>,
+    /// Views of every image the graph image changes between. See `ImageInfo::frames`.
+    views: Vec<Vec<B::ImageView>>,
+    samplers: Vec<B::Sampler>,
+    framebuffers: Vec<B::Framebuffer>,
+    frame: Option<Arc<AtomicUsize>>,
     clears: Vec<ClearValue>,
 
     pool: CommandPool<B, Graphics>,
+    prepare_pool: B::CommandPool,
+    prepare: B::CommandBuffer,
     static_pool: B::CommandPool,
-    acquire: B::CommandBuffer,
-    release: Option<B::CommandBuffer>,
+    acquire: Vec<B::CommandBuffer>,
+    release: Vec<B::CommandBuffer>,
+    inline: Option<InlineBarriers>,
+    static_pools: Vec<CommandPool<B, Graphics>>,
+    recorded: Vec<Option<Submit<B, Graphics, MultiShot>>>,
 
     pass: R,
+
+    #[cfg(feature = "validation")]
+    validator: Validator<B>,
 }
 
 /// Overall description for node.
@@ -199,7 +533,7 @@ where
     B: Backend,
     R: RenderPassDesc<B>,
 {
-    type Buffers = Empty<(buffer::Usage, buffer::State, PipelineStage)>;
+    type Buffers = SmallVec<[(buffer::Usage, buffer::State, PipelineStage); 16]>;
 
     type Images = SmallVec<[(image::Usage, image::State, PipelineStage); 16]>;
 
@@ -210,7 +544,7 @@ where
     }
 
     fn buffers() -> Self::Buffers {
-        empty()
+        R::buffers().into_iter().map(BufferRole::usage).collect()
     }
 
     fn images() -> Self::Images {
@@ -224,16 +558,8 @@ where
                 all_graphics_shaders_stages(),
             )
         });
-        let storage = (0..R::storage()).map(|_| {
-            (
-                image::Usage::STORAGE,
-                (
-                    image::Access::SHADER_READ,
-                    image::Layout::ShaderReadOnlyOptimal,
-                ),
-                all_graphics_shaders_stages(),
-            )
-        });
+        let storage = (0..R::storage())
+            .map(|index| R::storage_access(index).image(all_graphics_shaders_stages()));
         let colors = (0..R::colors()).map(|_| {
             (
                 image::Usage::COLOR_ATTACHMENT,
@@ -282,6 +608,7 @@ where
     {
         trace!("Creating RenderPass instance for '{}'", R::name());
 
+        assert_eq!(R::buffers().len(), buffers.len());
         assert_eq!(
             R::sampled() + R::storage() + R::colors() + R::depth() as usize,
             images.len()
@@ -290,6 +617,29 @@ where
         let color_info = |index| &images[R::sampled() + R::storage() + index];
         let depth_info = || &images[R::sampled() + R::storage() + R::colors()];
 
+        // With inline barriers the render pass transitions attachments itself.
+        let attachment_layouts = |info: &ImageInfo<I>| {
+            let from = if info.clear.is_some() {
+                image::Layout::Undefined
+            } else if R::inline_barriers() {
+                info.barriers
+                    .acquire
+                    .as_ref()
+                    .map_or(info.layout, |barrier| (barrier.start.0).1)
+            } else {
+                info.layout
+            };
+            let to = if R::inline_barriers() {
+                info.barriers
+                    .release
+                    .as_ref()
+                    .map_or(info.layout, |barrier| (barrier.end.0).1)
+            } else {
+                info.layout
+            };
+            from..to
+        };
+
         let render_pass: B::RenderPass = {
             let attachments = (0..R::colors())
                 .map(|index| Attachment {
@@ -303,15 +653,7 @@ where
                         store: AttachmentStoreOp::Store,
                     },
                     stencil_ops: AttachmentOps::DONT_CARE,
-                    layouts: {
-                        let layout = color_info(index).layout;
-                        let from = if color_info(index).clear.is_some() {
-                            image::Layout::Undefined
-                        } else {
-                            layout
-                        };
-                        from..layout
-                    },
+                    layouts: attachment_layouts(color_info(index)),
                     samples: 1,
                 })
                 .chain(if R::depth() {
@@ -326,15 +668,7 @@ where
                             store: AttachmentStoreOp::Store,
                         },
                         stencil_ops: AttachmentOps::DONT_CARE,
-                        layouts: {
-                            let layout = depth_info().layout;
-                            let from = if depth_info().clear.is_some() {
-                                image::Layout::Undefined
-                            } else {
-                                layout
-                            };
-                            from..layout
-                        },
+                        layouts: attachment_layouts(depth_info()),
                         samples: 1,
                     })
                 } else {
@@ -358,8 +692,32 @@ where
                 preserves: &[],
             };
 
-            let result =
-                device.create_render_pass(attachments, Some(subpass), empty::<SubpassDependency>());
+            let dependencies = if R::inline_barriers() {
+                images[R::sampled() + R::storage()..]
+                    .iter()
+                    .flat_map(|info| {
+                        let acquire = info.barriers.acquire.as_ref().map(|barrier| {
+                            SubpassDependency {
+                                passes: SubpassRef::External..SubpassRef::Pass(0),
+                                stages: barrier.start.1..barrier.end.1,
+                                accesses: (barrier.start.0).0..(barrier.end.0).0,
+                            }
+                        });
+                        let release = info.barriers.release.as_ref().map(|barrier| {
+                            SubpassDependency {
+                                passes: SubpassRef::Pass(0)..SubpassRef::External,
+                                stages: barrier.start.1..barrier.end.1,
+                                accesses: (barrier.start.0).0..(barrier.end.0).0,
+                            }
+                        });
+                        acquire.into_iter().chain(release)
+                    })
+                    .collect()
+            } else {
+                Vec::new()
+            };
+
+            let result = device.create_render_pass(attachments, Some(subpass), dependencies);
 
             trace!("RenderPass instance created for '{}'", R::name());
             result
@@ -393,26 +751,35 @@ where
                     extent = Some(info.kind.extent());
                 }
 
-                device
-                    .create_image_view(
-                        info.image.borrow(),
-                        match info.kind {
-                            image::Kind::D1(_, _) => image::ViewKind::D1,
-                            image::Kind::D2(_, _, _, _) => image::ViewKind::D2,
-                            image::Kind::D3(_, _, _) => image::ViewKind::D3,
-                        },
-                        info.format,
-                        Swizzle::NO,
-                        image::SubresourceRange {
-                            aspects: info.format.surface_desc().aspects,
-                            levels: 0..1,
-                            layers: 0..1,
-                        },
-                    )
-                    .unwrap()
+                // Views for each swapchain image or both history images.
+                (0..info.frames())
+                    .map(|frame| {
+                        create_view::<B, _, _>(device, info, info.frame_image(frame).borrow())
+                    })
+                    .collect::<Vec<_>>()
             })
             .collect::<Vec<_>>();
 
+        // Framebuffers and descriptor sets are created for each frame.
+        let frames = images.iter().map(ImageInfo::frames).max().unwrap_or(1);
+        {
+            let mut indices = images
+                .iter()
+                .filter_map(|info| info.frames.as_ref())
+                .map(|frames| &frames.index);
+            if let Some(first) = indices.next() {
+                assert!(
+                    indices.all(|index| Arc::ptr_eq(first, index)),
+                    "Node {} can't use history images along with directly presented image",
+                    R::name()
+                );
+            }
+        }
+        let frame_view = |index: usize, frame: usize| {
+            let ref views = views[index];
+            views.get(frame).unwrap_or(&views[0])
+        };
+
         let extent = extent.unwrap_or(Extent {
             width: 0,
             height: 0,
@@ -428,21 +795,18 @@ where
 
         trace!("Creating layouts for '{}'", R::name());
 
-        let (pipeline_layouts, set_layouts): (Vec<_>, Vec<_>) = R::layouts()
+        let layouts = R::layouts();
+        let mut descriptors = Descriptors::new(&layouts, device);
+        let pipeline_layouts = layouts
             .into_iter()
-            .map(|layout| {
-                let set_layouts = layout
-                    .sets
-                    .into_iter()
-                    .map(|set| {
-                        device.create_descriptor_set_layout(set.bindings, empty::<B::Sampler>())
-                    })
-                    .collect::<Vec<_>>();
-                let pipeline_layout =
-                    device.create_pipeline_layout(&set_layouts, layout.push_constants);
-                (pipeline_layout, set_layouts)
+            .enumerate()
+            .map(|(index, layout)| {
+                device.create_pipeline_layout(
+                    descriptors.set_layouts(index),
+                    layout.push_constants,
+                )
             })
-            .unzip();
+            .collect::<Vec<_>>();
 
         trace!("Creating graphics pipelines for '{}'", R::name());
 
@@ -517,115 +881,154 @@ where
             pipelines
         };
 
-        let mut static_pool = pools(device, CommandPoolCreateFlags::empty()).into_raw();
+        let mut prepare_pool = pools(device, CommandPoolCreateFlags::empty()).into_raw();
+        let prepare = prepare_pool.allocate(1, RawLevel::Primary).pop().unwrap();
 
-        let with_release = buffers.iter().any(|info| info.barriers.release.is_some())
-            || images.iter().any(|info| info.barriers.release.is_some());
+        let mut static_pool = pools(device, CommandPoolCreateFlags::empty()).into_raw();
 
-        let mut static_cbufs = static_pool.allocate(1 + with_release as usize, RawLevel::Primary);
+        let with_acquire = !R::inline_barriers();
+        let with_release = !R::inline_barriers()
+            && (buffers.iter().any(|info| info.barriers.release.is_some())
+                || images.iter().any(|info| info.barriers.release.is_some()));
 
-        let mut acquire = static_cbufs.pop().unwrap();
-        acquire.begin(CommandBufferFlags::EMPTY, Default::default());
+        let mut static_cbufs = static_pool.allocate(
+            frames * (with_acquire as usize + with_release as usize),
+            RawLevel::Primary,
+        );
 
-        for (barrier, buffer) in buffers.iter().filter_map(|info| {
-            info.barriers
-                .acquire
-                .as_ref()
-                .map(|barrier| (barrier, info.buffer.borrow()))
-        }) {
-            acquire.pipeline_barrier(
-                barrier.start.1..barrier.end.1,
-                Dependencies::empty(),
-                Some(Barrier::Buffer {
-                    states: barrier.start.0..barrier.end.0,
-                    target: buffer,
-                }),
-            );
-        }
-        for (barrier, image, aspects) in images.iter().filter_map(|info| {
-            info.barriers.acquire.as_ref().map(|barrier| {
-                (
-                    barrier,
-                    info.image.borrow(),
-                    info.format.surface_desc().aspects,
-                )
+        let acquire = (0..frames * with_acquire as usize)
+            .map(|frame| {
+                let mut acquire = static_cbufs.pop().unwrap();
+                acquire.begin(CommandBufferFlags::EMPTY, Default::default());
+                record_barriers::<B, _, _, _>(
+                    &mut acquire,
+                    &buffers,
+                    &images,
+                    frame,
+                    false,
+                );
+                acquire.finish();
+                acquire
             })
-        }) {
-            acquire.pipeline_barrier(
-                barrier.start.1..barrier.end.1,
-                Dependencies::empty(),
-                Some(Barrier::Image {
-                    states: barrier.start.0..barrier.end.0,
-                    target: image,
-                    range: image::SubresourceRange {
-                        aspects,
-                        levels: 0..1,
-                        layers: 0..1,
-                    },
-                }),
-            );
-        }
-        acquire.finish();
+            .collect::<Vec<_>>();
 
         let release = if with_release {
-            let mut release = static_cbufs.pop().unwrap();
-            release.begin(CommandBufferFlags::EMPTY, Default::default());
-            for (barrier, buffer) in buffers.iter().filter_map(|info| {
-                info.barriers
-                    .release
-                    .as_ref()
-                    .map(|barrier| (barrier, info.buffer.borrow()))
-            }) {
-                release.pipeline_barrier(
-                    barrier.start.1..barrier.end.1,
-                    Dependencies::empty(),
-                    Some(Barrier::Buffer {
-                        states: barrier.start.0..barrier.end.0,
-                        target: buffer,
-                    }),
-                );
-            }
-
-            for (barrier, image, aspects) in images.iter().filter_map(|info| {
-                info.barriers.release.as_ref().map(|barrier| {
-                    (
-                        barrier,
-                        info.image.borrow(),
-                        info.format.surface_desc().aspects,
-                    )
+            (0..frames)
+                .map(|frame| {
+                    let mut release = static_cbufs.pop().unwrap();
+                    release.begin(CommandBufferFlags::EMPTY, Default::default());
+                    record_barriers::<B, _, _, _>(
+                        &mut release,
+                        &buffers,
+                        &images,
+                        frame,
+                        true,
+                    );
+                    release.finish();
+                    release
                 })
-            }) {
-                release.pipeline_barrier(
-                    barrier.start.1..barrier.end.1,
-                    Dependencies::empty(),
-                    Some(Barrier::Image {
-                        states: barrier.start.0..barrier.end.0,
-                        target: image,
-                        range: image::SubresourceRange {
-                            aspects,
-                            levels: 0..1,
-                            layers: 0..1,
-                        },
-                    }),
-                );
-            }
-            release.finish();
-            Some(release)
+                .collect()
+        } else {
+            Vec::new()
+        };
+
+        assert!(
+            !(R::inline_barriers() && R::static_commands()),
+            "Static commands can't be combined with inline barriers"
+        );
+        let static_pools = if R::static_commands() {
+            (0..frames)
+                .map(|_| pools(device, CommandPoolCreateFlags::empty()))
+                .collect()
+        } else {
+            Vec::new()
+        };
+
+        // Attachment barriers are folded into the render pass.
+        let inline = if R::inline_barriers() {
+            Some(InlineBarriers::new(&buffers, &images[..R::sampled() + R::storage()]))
         } else {
             None
         };
 
-        let framebuffer = device
-            .create_framebuffer(
-                &render_pass,
-                views.iter().skip(R::sampled() + R::storage()),
-                extent,
-            )
-            .unwrap();
+        let framebuffers = (0..frames)
+            .map(|frame| {
+                let attachment_views = (R::sampled() + R::storage()..images.len())
+                    .map(|index| frame_view(index, frame));
+                device
+                    .create_framebuffer(&render_pass, attachment_views, extent)
+                    .unwrap()
+            })
+            .collect::<Vec<_>>();
+
+        trace!("Bind graph images for '{}'", R::name());
+
+        let sampled_bindings = R::sampled_bindings();
+        let storage_bindings = R::storage_bindings();
+        assert!(sampled_bindings.is_empty() || sampled_bindings.len() == R::sampled());
+        assert!(storage_bindings.is_empty() || storage_bindings.len() == R::storage());
+
+        let samplers = sampled_bindings
+            .iter()
+            .map(|&(_, ref info)| device.create_sampler(info.clone()))
+            .collect::<Vec<_>>();
+
+        for binding in sampled_bindings
+            .iter()
+            .map(|&(binding, _)| binding)
+            .chain(storage_bindings.iter().cloned())
+        {
+            descriptors
+                .bind(device, binding.layout, binding.set, frames)
+                .unwrap();
+        }
 
+        for frame in 0..frames {
+            descriptors.select_frame(frame);
+            let sampled_writes = sampled_bindings.iter().zip(&samplers).enumerate().map(
+                |(index, (&(binding, _), sampler))| DescriptorSetWrite {
+                    set: descriptors.bound(binding.layout, binding.set).unwrap(),
+                    binding: binding.binding,
+                    array_offset: 0,
+                    descriptors: Some(Descriptor::CombinedImageSampler(
+                        frame_view(index, frame),
+                        images[index].layout,
+                        sampler,
+                    )),
+                },
+            );
+            let storage_writes = storage_bindings
+                .iter()
+                .enumerate()
+                .map(|(index, &binding)| {
+                    let index = R::sampled() + index;
+                    DescriptorSetWrite {
+                        set: descriptors.bound(binding.layout, binding.set).unwrap(),
+                        binding: binding.binding,
+                        array_offset: 0,
+                        descriptors: Some(Descriptor::Image(
+                            frame_view(index, frame),
+                            images[index].layout,
+                        )),
+                    }
+                });
+            device.write_descriptor_sets(sampled_writes.chain(storage_writes));
+        }
+
+        #[cfg(feature = "validation")]
+        let validator =
+            validate::<B, R, _, _>(&buffers, &images, &sampled_bindings, &storage_bindings);
+
+        // Passes get views of the first frame. Graph descriptor sets are switched each frame.
         let pass = R::build(
-            &views[..R::sampled()],
-            &views[R::sampled()..R::sampled() + R::storage()],
+            views[..R::sampled()]
+                .iter()
+                .map(|views| &views[0])
+                .collect::<Vec<_>>(),
+            views[R::sampled()..R::sampled() + R::storage()]
+                .iter()
+                .map(|views| &views[0])
+                .collect::<Vec<_>>(),
             device,
             aux,
         );
@@ -634,23 +1037,38 @@ where
             relevant: Relevant,
             extent,
             render_pass,
-            set_layouts,
+            descriptors,
             pipeline_layouts,
             graphics_pipelines,
             pool: pools(device, CommandPoolCreateFlags::empty()),
+            prepare_pool,
+            prepare,
             static_pool,
             acquire,
             release,
+            inline,
+            static_pools,
+            recorded: (0..frames).map(|_| None).collect(),
+            buffers: buffers.iter().map(|info| info.id).collect(),
             views,
-            framebuffer,
+            samplers,
+            framebuffers,
+            frame: images
+                .iter()
+                .filter_map(|info| info.frames.as_ref())
+                .map(|frames| frames.index.clone())
+                .next(),
             clears,
             pass,
+            #[cfg(feature = "validation")]
+            validator,
         }
     }
 
     #[inline]
     fn run<'a, W, S>(
         &'a mut self,
+        frame: &FrameInfo<B>,
         wait: W,
         queue: &mut CommandQueue<B, Graphics>,
         signal: S,
@@ -670,44 +1088,144 @@ where
             h: self.extent.height as u16,
         };
 
+        let buffers = self
+            .buffers
+            .iter()
+            .map(|&id| frame.buffer(id))
+            .collect::<SmallVec<[_; 16]>>();
+
+        let index = self
+            .frame
+            .as_ref()
+            .map_or(0, |index| index.load(Ordering::Acquire));
+
+        self.descriptors.next_frame(frame.slot());
+        self.descriptors.select_frame(index);
+
+        if R::static_commands() && self.pass.dirty(aux) {
+            // Recordings for other framebuffers are stale too.
+            for recorded in &mut self.recorded {
+                *recorded = None;
+            }
+        }
+        let record = !R::static_commands() || self.recorded[index].is_none();
+
         self.pool.reset();
-        let mut cbuf = self.pool.acquire_command_buffer::<OneShot>(false);
-        {
-            profile!("Render pass prepare");
-            self.pass.prepare(&self.set_layouts, &mut cbuf, device, aux);
+
+        // `prepare` commands are recorded after inline acquire barriers.
+        // Static passes skip them once recorded as inline barriers are not allowed for them.
+        if record {
+            self.prepare_pool.reset();
+            let ref mut cbuf = self.prepare;
+            cbuf.begin(CommandBufferFlags::ONE_TIME_SUBMIT, Default::default());
+            if let Some(ref inline) = self.inline {
+                for (stages, barriers) in inline.batch(frame, false) {
+                    cbuf.pipeline_barrier(stages, Dependencies::empty(), barriers);
+                }
+            }
+            {
+                profile!("Render pass prepare");
+                #[cfg(feature = "validation")]
+                let mut prepare = {
+                    self.validator.select_frame(index);
+                    PrepareBuffer {
+                        cbuf: self.validator.record(cbuf),
+                    }
+                };
+                #[cfg(not(feature = "validation"))]
+                let mut prepare = PrepareBuffer { cbuf: &mut *cbuf };
+                self.pass
+                    .prepare(&mut self.descriptors, &buffers, &mut prepare, device, aux);
+                prepare.end();
+            }
+            cbuf.finish();
         }
-        {
-            let encoder = {
-                profile!("begin render pass");
-                cbuf.begin_render_pass_inline(
-                    &self.render_pass,
-                    &self.framebuffer,
-                    area,
-                    &self.clears,
-                )
-            };
+
+        // One-shot buffer holds non-static drawing and inline release barriers.
+        let begin = if !R::static_commands() {
+            let mut cbuf = self.pool.acquire_command_buffer::<OneShot>(false);
             {
+                let encoder = {
+                    profile!("begin render pass");
+                    cbuf.begin_render_pass_inline(
+                        &self.render_pass,
+                        &self.framebuffers[index],
+                        area,
+                        &self.clears,
+                    )
+                };
                 profile!("Render pass draw");
                 self.pass.draw(
+                    &self.descriptors,
+                    &buffers,
                     &self.pipeline_layouts,
                     &self.graphics_pipelines,
                     encoder,
                     aux,
                 );
             }
+            if let Some(ref inline) = self.inline {
+                for (stages, barriers) in inline.batch(frame, true) {
+                    cbuf.pipeline_barrier(stages, Dependencies::empty(), barriers);
+                }
+            }
+            Some(cbuf.finish())
+        } else {
+            None
+        };
+
+        if R::static_commands() {
+            if self.recorded[index].is_none() {
+                let ref mut pool = self.static_pools[index];
+                pool.reset();
+                let mut static_cbuf = pool.acquire_command_buffer::<MultiShot>(true);
+                {
+                    let encoder = {
+                        profile!("begin render pass");
+                        static_cbuf.begin_render_pass_inline(
+                            &self.render_pass,
+                            &self.framebuffers[index],
+                            area,
+                            &self.clears,
+                        )
+                    };
+                    profile!("Render pass draw");
+                    self.pass.draw(
+                        &self.descriptors,
+                        &buffers,
+                        &self.pipeline_layouts,
+                        &self.graphics_pipelines,
+                        encoder,
+                        aux,
+                    );
+                }
+                self.recorded[index] = Some(static_cbuf.finish());
+            }
         }
 
         unsafe {
             profile!("Submission");
+            let begin = begin.map(|begin| begin.into_buffer());
+            // `Submittable` is implemented for `&Submit<B, C, MultiShot, L>`.
+            // `into_buffer` returns `Cow::Borrowed` leaving recorded buffer for the next frames.
+            let recorded = self.recorded[index]
+                .as_ref()
+                .map(|recorded| recorded.into_buffer());
             queue.as_raw_mut().submit_raw(
                 RawSubmission {
                     wait_semaphores: &wait
                         .into_iter()
                         .map(|(semaphore, stage)| (semaphore.borrow(), stage))
                         .collect::<SmallVec<[_; 16]>>(),
-                    cmd_buffers: once(&self.acquire)
-                        .chain(once(cbuf.finish().into_buffer().as_ref()))
-                        .chain(self.release.as_ref()),
+                    cmd_buffers: frame.command_buffers(
+                        self.acquire
+                            .get(index)
+                            .into_iter()
+                            .chain(if record { Some(&self.prepare) } else { None })
+                            .chain(begin.as_ref().map(|begin| begin.as_ref()))
+                            .chain(recorded.as_ref().map(|recorded| recorded.as_ref()))
+                            .chain(self.release.get(index)),
+                    ),
                     signal_semaphores: &signal
                         .into_iter()
                         .map(Borrow::borrow)
@@ -718,18 +1236,178 @@ where
         }
     }
 
+    #[cfg(feature = "validation")]
+    fn hazards(&self) -> &[Hazard] {
+        self.validator.hazards()
+    }
+
     fn dispose(mut self, device: &mut D, aux: &mut T) {
         self.pass.dispose(device, aux);
         drop(self.acquire);
         drop(self.release);
+        drop(self.recorded);
+        for mut pool in self.static_pools {
+            pool.reset();
+            device.destroy_command_pool(pool.into_raw());
+        }
         self.static_pool.reset();
+        self.prepare_pool.free(vec![self.prepare]);
         self.pool.reset();
         device.destroy_command_pool(self.static_pool);
+        device.destroy_command_pool(self.prepare_pool);
         device.destroy_command_pool(self.pool.into_raw());
+        for pipeline in self.graphics_pipelines {
+            device.destroy_graphics_pipeline(pipeline);
+        }
+        for layout in self.pipeline_layouts {
+            device.destroy_pipeline_layout(layout);
+        }
+        self.descriptors.dispose(device);
+        for sampler in self.samplers {
+            device.destroy_sampler(sampler);
+        }
+        for framebuffer in self.framebuffers {
+            device.destroy_framebuffer(framebuffer);
+        }
+        for view in self.views.into_iter().flat_map(|views| views) {
+            device.destroy_image_view(view);
+        }
+        device.destroy_render_pass(self.render_pass);
         self.relevant.dispose();
     }
 }
 
+fn create_view<B, D, I>(device: &mut D, info: &ImageInfo<I>, image: &B::Image) -> B::ImageView
+where
+    B: Backend,
+    D: Device<B>,
+{
+    device
+        .create_image_view(
+            image,
+            match info.kind {
+                image::Kind::D1(_, _) => image::ViewKind::D1,
+                image::Kind::D2(_, _, _, _) => image::ViewKind::D2,
+                image::Kind::D3(_, _, _) => image::ViewKind::D3,
+            },
+            info.format,
+            Swizzle::NO,
+            image::SubresourceRange {
+                aspects: info.format.surface_desc().aspects,
+                levels: 0..1,
+                layers: 0..1,
+            },
+        )
+        .unwrap()
+}
+
+/// Check attachments and images written into descriptor sets against declarations of the pass.
+/// Images are bound with descriptor types and shader stages of the `RenderPassDesc::layouts`.
+#[cfg(feature = "validation")]
+fn validate<B, R, U, I>(
+    buffers: &[BufferInfo<U>],
+    images: &[ImageInfo<I>],
+    sampled: &[(ImageBinding, image::SamplerInfo)],
+    storage: &[ImageBinding],
+) -> Validator<B>
+where
+    B: Backend,
+    R: RenderPassDesc<B>,
+    U: Borrow<B::Buffer>,
+    I: Borrow<B::Image>,
+{
+    let mut validator = Validator::for_node::<RenderPassNode<B, R>, _, _>(buffers, images);
+
+    let layouts = R::layouts();
+    let bindings = sampled
+        .iter()
+        .enumerate()
+        .map(|(index, &(binding, _))| (index, binding))
+        .chain(
+            storage
+                .iter()
+                .enumerate()
+                .map(|(index, &binding)| (R::sampled() + index, binding)),
+        );
+    for (index, binding) in bindings {
+        let desc = layouts[binding.layout].sets[binding.set]
+            .bindings
+            .iter()
+            .find(|desc| desc.binding == binding.binding);
+        let desc = match desc {
+            Some(desc) => desc,
+            None => continue,
+        };
+        let ref info = images[index];
+        let state = match desc.ty {
+            ::hal::pso::DescriptorType::StorageImage => {
+                let access = if index < R::sampled() {
+                    image::Access::SHADER_READ
+                } else {
+                    R::storage_access(index - R::sampled()).image_access()
+                };
+                (access, image::Layout::General)
+            }
+            _ => (image::Access::SHADER_READ, info.layout),
+        };
+        for frame in 0..info.frames() {
+            validator.select_frame(frame);
+            validator.access_image(
+                "write_descriptor_sets",
+                info.frame_image(frame).borrow(),
+                state,
+                shader_stages(desc.stage_flags),
+            );
+        }
+    }
+
+    for (index, info) in images.iter().enumerate().skip(R::sampled() + R::storage()) {
+        let (access, stages) = if index < R::sampled() + R::storage() + R::colors() {
+            (
+                image::Access::COLOR_ATTACHMENT_READ | image::Access::COLOR_ATTACHMENT_WRITE,
+                PipelineStage::COLOR_ATTACHMENT_OUTPUT,
+            )
+        } else {
+            (
+                image::Access::DEPTH_STENCIL_ATTACHMENT_READ
+                    | image::Access::DEPTH_STENCIL_ATTACHMENT_WRITE,
+                PipelineStage::EARLY_FRAGMENT_TESTS | PipelineStage::LATE_FRAGMENT_TESTS,
+            )
+        };
+        for frame in 0..info.frames() {
+            validator.select_frame(frame);
+            validator.access_image(
+                "begin_render_pass",
+                info.frame_image(frame).borrow(),
+                (access, info.layout),
+                stages,
+            );
+        }
+    }
+    validator.select_frame(0);
+
+    validator
+}
+
+/// Pipeline stages of the shader stages.
+#[cfg(feature = "validation")]
+fn shader_stages(flags: ShaderStageFlags) -> PipelineStage {
+    let mut stages = PipelineStage::empty();
+    for &(flag, stage) in &[
+        (ShaderStageFlags::VERTEX, PipelineStage::VERTEX_SHADER),
+        (ShaderStageFlags::HULL, PipelineStage::HULL_SHADER),
+        (ShaderStageFlags::DOMAIN, PipelineStage::DOMAIN_SHADER),
+        (ShaderStageFlags::GEOMETRY, PipelineStage::GEOMETRY_SHADER),
+        (ShaderStageFlags::FRAGMENT, PipelineStage::FRAGMENT_SHADER),
+        (ShaderStageFlags::COMPUTE, PipelineStage::COMPUTE_SHADER),
+    ] {
+        if flags.contains(flag) {
+            stages |= stage;
+        }
+    }
+    stages
+}
+
 fn all_graphics_shaders_stages() -> PipelineStage {
     PipelineStage::VERTEX_SHADER
         // | PipelineStage::DOMAIN_SHADER
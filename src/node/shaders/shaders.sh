@@ -0,0 +1,3 @@
+#!/bin/sh
+glslangValidator -V present.vert -o present.vert.spv
+glslangValidator -V present.frag -o present.frag.spv
@@ -0,0 +1,727 @@
+//! Validation of commands recorded by nodes against their declarations.
+//!
+//! Create `Validator` from the infos passed to `Node::build`
+//! and record commands through `Validator::record`.
+//! Every graph resource touched must be declared by `NodeDesc::buffers` or `NodeDesc::images`
+//! with compatible access, layout and stages.
+//! Resources owned by the node must be registered with `own_buffer` and `own_image`.
+//! Found hazards are logged along with the node name and kept until `clear` is called.
+//! The same hazard found again is not reported twice.
+//!
+//! `RenderPassNode` and `PresentNode` validate their attachments and images bound to descriptor
+//! sets. Commands recorded by `RenderPass::prepare` are validated every frame.
+//! Hazards found by nodes are collected with `Graph::hazards`.
+//!
+//! Intended for debug builds. Enabled with `validation` feature.
+
+use std::{
+    borrow::Borrow, collections::HashMap, fmt, iter::once, marker::PhantomData, ops::Range,
+};
+
+use hal::{
+    buffer,
+    command::{
+        BufferCopy, BufferImageCopy, ClearColorRaw, ClearDepthStencilRaw, ImageCopy,
+        RawCommandBuffer,
+    },
+    image, memory::{Barrier, Dependencies}, pso::PipelineStage, Backend,
+};
+
+use smallvec::SmallVec;
+
+use node::{BufferInfo, ImageInfo, NodeDesc};
+use util::*;
+
+/// Graph resource touched by the node.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Resource {
+    /// Buffer declared by the node.
+    Buffer(BufferId),
+
+    /// Image declared by the node.
+    Image(ImageId),
+
+    /// Buffer neither declared by the node nor owned by it.
+    UnknownBuffer,
+
+    /// Image neither declared by the node nor owned by it.
+    UnknownImage,
+}
+
+/// Kind of the hazard.
+#[derive(Clone, Debug, PartialEq)]
+pub enum HazardKind {
+    /// Resource is not declared by the node.
+    Undeclared,
+
+    /// Buffer is accessed in a way its current state doesn't allow.
+    BufferAccess {
+        /// Access required by the command.
+        required: buffer::Access,
+
+        /// Access allowed by the current state.
+        allowed: buffer::Access,
+    },
+
+    /// Image is accessed in a way its current state doesn't allow.
+    ImageAccess {
+        /// Access required by the command.
+        required: image::Access,
+
+        /// Access allowed by the current state.
+        allowed: image::Access,
+    },
+
+    /// Image is used in layout different from the current one.
+    Layout {
+        /// Layout specified in the command.
+        required: image::Layout,
+
+        /// Current layout of the image.
+        current: image::Layout,
+    },
+
+    /// Resource is accessed at stages not covered by the current state.
+    Stage {
+        /// Stages required by the command.
+        required: PipelineStage,
+
+        /// Stages of the current state.
+        allowed: PipelineStage,
+    },
+
+    /// Barrier doesn't start from the current state of the resource,
+    /// or barrier provided by the graph doesn't match the declaration.
+    Barrier,
+
+    /// Resource is not returned to the declared state by the end of recording.
+    Unrestored,
+}
+
+/// Access made by the node that doesn't respect its declarations.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Hazard {
+    /// Name of the node.
+    pub node: String,
+
+    /// Name of the command.
+    pub command: &'static str,
+
+    /// Resource accessed.
+    pub resource: Resource,
+
+    /// Name given to the resource at creation.
+    /// Filled by `Graph::hazards`. See `Graph::image_name` and `Graph::buffer_name`.
+    pub resource_name: Option<String>,
+
+    /// What is wrong with the access.
+    pub kind: HazardKind,
+}
+
+impl fmt::Display for Hazard {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "Node '{}' made {:?} access to {:?}",
+            self.node, self.kind, self.resource
+        )?;
+        if let Some(ref name) = self.resource_name {
+            write!(f, " '{}'", name)?;
+        }
+        write!(f, " in '{}'", self.command)
+    }
+}
+
+/// Declared and current state of the resource.
+#[derive(Clone, Copy, Debug)]
+struct Tracked<S> {
+    declared: (S, PipelineStage),
+    current: (S, PipelineStage),
+}
+
+impl<S: Copy> Tracked<S> {
+    fn new(state: S, stages: PipelineStage) -> Self {
+        Tracked {
+            declared: (state, stages),
+            current: (state, stages),
+        }
+    }
+}
+
+/// Validator of the commands recorded by single node.
+pub struct Validator<B: Backend> {
+    node: String,
+    buffers: HashMap<usize, (BufferId, Tracked<buffer::State>)>,
+    images: HashMap<usize, (ImageId, Tracked<image::State>)>,
+    /// Images changing between frames by frame. See `ImageInfo::frames`.
+    frame_images: Vec<HashMap<usize, (ImageId, Tracked<image::State>)>>,
+    frame: usize,
+    owned: Vec<usize>,
+    hazards: Vec<Hazard>,
+    pd: PhantomData<fn() -> B>,
+}
+
+impl<B> Validator<B>
+where
+    B: Backend,
+{
+    /// Create validator without declarations.
+    pub fn new(node: &str) -> Self {
+        Validator {
+            node: node.to_string(),
+            buffers: HashMap::new(),
+            images: HashMap::new(),
+            frame_images: Vec::new(),
+            frame: 0,
+            owned: Vec::new(),
+            hazards: Vec::new(),
+            pd: PhantomData,
+        }
+    }
+
+    /// Create validator for the node from infos passed to `Node::build`.
+    /// Barriers of the infos are checked against `NodeDesc` declarations.
+    pub fn for_node<N, U, I>(buffers: &[BufferInfo<U>], images: &[ImageInfo<I>]) -> Self
+    where
+        N: NodeDesc,
+        U: Borrow<B::Buffer>,
+        I: Borrow<B::Image>,
+    {
+        let mut validator = Validator::new(N::name());
+        for (info, (_, access, stages)) in buffers.iter().zip(N::buffers()) {
+            validator.declare_buffer(info, access, stages);
+        }
+        for (info, (_, state, stages)) in images.iter().zip(N::images()) {
+            validator.declare_image(info, state, stages);
+        }
+        validator
+    }
+
+    /// Declare graph buffer used by the node.
+    pub fn declare_buffer<U>(
+        &mut self,
+        info: &BufferInfo<U>,
+        access: buffer::State,
+        stages: PipelineStage,
+    ) where
+        U: Borrow<B::Buffer>,
+    {
+        if !barriers_match(&info.barriers, &(access, stages), |a, b| a.contains(*b)) {
+            self.report("build", Resource::Buffer(info.id), HazardKind::Barrier);
+        }
+        let buffer: &B::Buffer = info.buffer.borrow();
+        self.buffers
+            .insert(address(buffer), (info.id, Tracked::new(access, stages)));
+    }
+
+    /// Declare graph image used by the node.
+    /// Every swapchain image is declared if the image is presented directly
+    /// and both images of the pair if it is a history image.
+    /// Those are tracked for the frame they are used in. See `Validator::select_frame`.
+    pub fn declare_image<I>(
+        &mut self,
+        info: &ImageInfo<I>,
+        state: image::State,
+        stages: PipelineStage,
+    ) where
+        I: Borrow<B::Image>,
+    {
+        // Layout is picked by the chain. Access comes from the declaration.
+        let state = (state.0, info.layout);
+        let covers = |a: &image::State, b: &image::State| a.0.contains(b.0) && a.1 == b.1;
+        if !barriers_match(&info.barriers, &(state, stages), covers) {
+            self.report("build", Resource::Image(info.id), HazardKind::Barrier);
+        }
+        if info.frames.is_none() {
+            let image: &B::Image = info.image.borrow();
+            self.images
+                .insert(address(image), (info.id, Tracked::new(state, stages)));
+            return;
+        }
+        for frame in 0..info.frames() {
+            if self.frame_images.len() <= frame {
+                self.frame_images.push(HashMap::new());
+            }
+            let image: &B::Image = info.frame_image(frame).borrow();
+            self.frame_images[frame]
+                .insert(address(image), (info.id, Tracked::new(state, stages)));
+        }
+    }
+
+    /// Validate following accesses against images of the frame.
+    /// History images swap their roles between frames, so the same image
+    /// is expected in different states depending on the frame.
+    /// See `ImageInfo::frames`.
+    pub fn select_frame(&mut self, frame: usize) {
+        self.frame = frame;
+    }
+
+    /// Register buffer owned by the node. Accesses to it are not validated.
+    pub fn own_buffer(&mut self, buffer: &B::Buffer) {
+        self.own(address(buffer));
+    }
+
+    /// Register image owned by the node. Accesses to it are not validated.
+    pub fn own_image(&mut self, image: &B::Image) {
+        self.own(address(image));
+    }
+
+    fn own(&mut self, address: usize) {
+        if !self.owned.contains(&address) {
+            self.owned.push(address);
+        }
+    }
+
+    /// Check access to the image made outside of validated recording.
+    /// Such as use as render pass attachment or by shaders through descriptor set.
+    pub fn access_image(
+        &mut self,
+        command: &'static str,
+        image: &B::Image,
+        state: image::State,
+        stages: PipelineStage,
+    ) {
+        self.check_image(command, image, state, stages);
+    }
+
+    /// Start validated recording into the command buffer.
+    /// Resources are expected to be in declared states.
+    pub fn record<'a, C>(&'a mut self, cbuf: &'a mut C) -> Validated<'a, B, C>
+    where
+        C: RawCommandBuffer<B>,
+    {
+        for &mut (_, ref mut tracked) in self.buffers.values_mut() {
+            tracked.current = tracked.declared;
+        }
+        for images in once(&mut self.images).chain(&mut self.frame_images) {
+            for &mut (_, ref mut tracked) in images.values_mut() {
+                tracked.current = tracked.declared;
+            }
+        }
+        Validated {
+            validator: self,
+            cbuf,
+        }
+    }
+
+    /// Hazards found so far.
+    pub fn hazards(&self) -> &[Hazard] {
+        &self.hazards
+    }
+
+    /// Forget found hazards.
+    pub fn clear(&mut self) {
+        self.hazards.clear();
+    }
+
+    fn report(&mut self, command: &'static str, resource: Resource, kind: HazardKind) {
+        let hazard = Hazard {
+            node: self.node.clone(),
+            command,
+            resource,
+            resource_name: None,
+            kind,
+        };
+        if !self.hazards.contains(&hazard) {
+            error!("{}", hazard);
+            self.hazards.push(hazard);
+        }
+    }
+
+    /// Tracked image of the selected frame or image not changing between frames.
+    fn image(&mut self, address: usize) -> Option<&mut (ImageId, Tracked<image::State>)> {
+        let frame = self.frame;
+        match self
+            .frame_images
+            .get_mut(frame)
+            .and_then(|images| images.get_mut(&address))
+        {
+            Some(image) => Some(image),
+            None => self.images.get_mut(&address),
+        }
+    }
+
+    fn check_buffer(
+        &mut self,
+        command: &'static str,
+        buffer: &B::Buffer,
+        required: buffer::Access,
+        stages: PipelineStage,
+    ) {
+        let address = address(buffer);
+        let (id, tracked) = match self.buffers.get(&address) {
+            Some(&(id, tracked)) => (id, tracked),
+            None => {
+                if !self.owned.contains(&address) {
+                    self.report(command, Resource::UnknownBuffer, HazardKind::Undeclared);
+                }
+                return;
+            }
+        };
+        let (allowed, allowed_stages) = tracked.current;
+        if !allowed.contains(required) {
+            let kind = HazardKind::BufferAccess { required, allowed };
+            self.report(command, Resource::Buffer(id), kind);
+        }
+        if !allowed_stages.contains(stages) {
+            let kind = HazardKind::Stage {
+                required: stages,
+                allowed: allowed_stages,
+            };
+            self.report(command, Resource::Buffer(id), kind);
+        }
+    }
+
+    fn check_image(
+        &mut self,
+        command: &'static str,
+        image: &B::Image,
+        required: image::State,
+        stages: PipelineStage,
+    ) {
+        let address = address(image);
+        let (id, tracked) = match self.image(address) {
+            Some(&mut (id, tracked)) => (id, tracked),
+            None => {
+                if !self.owned.contains(&address) {
+                    self.report(command, Resource::UnknownImage, HazardKind::Undeclared);
+                }
+                return;
+            }
+        };
+        let ((allowed, current), allowed_stages) = tracked.current;
+        if !allowed.contains(required.0) {
+            let kind = HazardKind::ImageAccess {
+                required: required.0,
+                allowed,
+            };
+            self.report(command, Resource::Image(id), kind);
+        }
+        if current != required.1 {
+            let kind = HazardKind::Layout {
+                required: required.1,
+                current,
+            };
+            self.report(command, Resource::Image(id), kind);
+        }
+        if !allowed_stages.contains(stages) {
+            let kind = HazardKind::Stage {
+                required: stages,
+                allowed: allowed_stages,
+            };
+            self.report(command, Resource::Image(id), kind);
+        }
+    }
+}
+
+impl<B> fmt::Debug for Validator<B>
+where
+    B: Backend,
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Validator")
+            .field("node", &self.node)
+            .field("hazards", &self.hazards)
+            .finish()
+    }
+}
+
+/// Command buffer wrapper that validates commands touching graph resources.
+/// Commands without validated counterpart can be recorded with `raw`.
+pub struct Validated<'a, B: Backend + 'a, C: 'a> {
+    validator: &'a mut Validator<B>,
+    cbuf: &'a mut C,
+}
+
+impl<'a, B, C> Validated<'a, B, C>
+where
+    B: Backend,
+    C: RawCommandBuffer<B>,
+{
+    /// Get wrapped command buffer to record commands that are not validated.
+    pub fn raw(&mut self) -> &mut C {
+        self.cbuf
+    }
+
+    /// Register buffer owned by the node. See `Validator::own_buffer`.
+    pub fn own_buffer(&mut self, buffer: &B::Buffer) {
+        self.validator.own_buffer(buffer);
+    }
+
+    /// Register image owned by the node. See `Validator::own_image`.
+    pub fn own_image(&mut self, image: &B::Image) {
+        self.validator.own_image(image);
+    }
+
+    /// Record pipeline barrier.
+    /// Barriers of graph resources must start from their current state.
+    pub fn pipeline_barrier<'b, T>(
+        &mut self,
+        stages: Range<PipelineStage>,
+        dependencies: Dependencies,
+        barriers: T,
+    ) where
+        T: IntoIterator,
+        T::Item: Borrow<Barrier<'b, B>>,
+    {
+        let barriers = barriers.into_iter().collect::<SmallVec<[_; 16]>>();
+        for barrier in &barriers {
+            match *barrier.borrow() {
+                Barrier::Buffer {
+                    ref states,
+                    target,
+                } => self.buffer_barrier(states, target, &stages),
+                Barrier::Image {
+                    ref states,
+                    target,
+                    ..
+                } => self.image_barrier(states, target, &stages),
+                _ => {}
+            }
+        }
+        self.cbuf.pipeline_barrier(stages, dependencies, barriers);
+    }
+
+    fn buffer_barrier(
+        &mut self,
+        states: &Range<buffer::State>,
+        target: &B::Buffer,
+        stages: &Range<PipelineStage>,
+    ) {
+        let validator = &mut *self.validator;
+        let address = address(target);
+        let update = match validator.buffers.get_mut(&address) {
+            Some(&mut (id, ref mut tracked)) => {
+                let matches = states.start.contains(tracked.current.0)
+                    && stages.start.contains(tracked.current.1);
+                tracked.current = (states.end, stages.end);
+                Some((id, matches))
+            }
+            None => None,
+        };
+        match update {
+            Some((_, true)) => {}
+            Some((id, false)) => {
+                validator.report("pipeline_barrier", Resource::Buffer(id), HazardKind::Barrier)
+            }
+            None if !validator.owned.contains(&address) => validator.report(
+                "pipeline_barrier",
+                Resource::UnknownBuffer,
+                HazardKind::Undeclared,
+            ),
+            None => {}
+        }
+    }
+
+    fn image_barrier(
+        &mut self,
+        states: &Range<image::State>,
+        target: &B::Image,
+        stages: &Range<PipelineStage>,
+    ) {
+        let validator = &mut *self.validator;
+        let address = address(target);
+        let update = match validator.image(address) {
+            Some(&mut (id, ref mut tracked)) => {
+                // Transition from `Undefined` discards content and is allowed from any state.
+                let ((access, layout), current_stages) = tracked.current;
+                let matches = states.start.1 == image::Layout::Undefined
+                    || (states.start.1 == layout
+                        && states.start.0.contains(access)
+                        && stages.start.contains(current_stages));
+                tracked.current = (states.end, stages.end);
+                Some((id, matches))
+            }
+            None => None,
+        };
+        match update {
+            Some((_, true)) => {}
+            Some((id, false)) => {
+                validator.report("pipeline_barrier", Resource::Image(id), HazardKind::Barrier)
+            }
+            None if !validator.owned.contains(&address) => validator.report(
+                "pipeline_barrier",
+                Resource::UnknownImage,
+                HazardKind::Undeclared,
+            ),
+            None => {}
+        }
+    }
+
+    /// Record buffer to buffer copy.
+    pub fn copy_buffer<T>(&mut self, src: &B::Buffer, dst: &B::Buffer, regions: T)
+    where
+        T: IntoIterator,
+        T::Item: Borrow<BufferCopy>,
+    {
+        let (read, write) = (buffer::Access::TRANSFER_READ, buffer::Access::TRANSFER_WRITE);
+        self.validator
+            .check_buffer("copy_buffer", src, read, PipelineStage::TRANSFER);
+        self.validator
+            .check_buffer("copy_buffer", dst, write, PipelineStage::TRANSFER);
+        self.cbuf.copy_buffer(src, dst, regions);
+    }
+
+    /// Record image to image copy.
+    pub fn copy_image<T>(
+        &mut self,
+        src: &B::Image,
+        src_layout: image::Layout,
+        dst: &B::Image,
+        dst_layout: image::Layout,
+        regions: T,
+    ) where
+        T: IntoIterator,
+        T::Item: Borrow<ImageCopy>,
+    {
+        self.validator.check_image(
+            "copy_image",
+            src,
+            (image::Access::TRANSFER_READ, src_layout),
+            PipelineStage::TRANSFER,
+        );
+        self.validator.check_image(
+            "copy_image",
+            dst,
+            (image::Access::TRANSFER_WRITE, dst_layout),
+            PipelineStage::TRANSFER,
+        );
+        self.cbuf
+            .copy_image(src, src_layout, dst, dst_layout, regions);
+    }
+
+    /// Record buffer to image copy.
+    pub fn copy_buffer_to_image<T>(
+        &mut self,
+        src: &B::Buffer,
+        dst: &B::Image,
+        dst_layout: image::Layout,
+        regions: T,
+    ) where
+        T: IntoIterator,
+        T::Item: Borrow<BufferImageCopy>,
+    {
+        self.validator.check_buffer(
+            "copy_buffer_to_image",
+            src,
+            buffer::Access::TRANSFER_READ,
+            PipelineStage::TRANSFER,
+        );
+        self.validator.check_image(
+            "copy_buffer_to_image",
+            dst,
+            (image::Access::TRANSFER_WRITE, dst_layout),
+            PipelineStage::TRANSFER,
+        );
+        self.cbuf.copy_buffer_to_image(src, dst, dst_layout, regions);
+    }
+
+    /// Record image to buffer copy.
+    pub fn copy_image_to_buffer<T>(
+        &mut self,
+        src: &B::Image,
+        src_layout: image::Layout,
+        dst: &B::Buffer,
+        regions: T,
+    ) where
+        T: IntoIterator,
+        T::Item: Borrow<BufferImageCopy>,
+    {
+        self.validator.check_image(
+            "copy_image_to_buffer",
+            src,
+            (image::Access::TRANSFER_READ, src_layout),
+            PipelineStage::TRANSFER,
+        );
+        self.validator.check_buffer(
+            "copy_image_to_buffer",
+            dst,
+            buffer::Access::TRANSFER_WRITE,
+            PipelineStage::TRANSFER,
+        );
+        self.cbuf.copy_image_to_buffer(src, src_layout, dst, regions);
+    }
+
+    /// Record image clear.
+    pub fn clear_image<T>(
+        &mut self,
+        image: &B::Image,
+        layout: image::Layout,
+        color: ClearColorRaw,
+        depth_stencil: ClearDepthStencilRaw,
+        subresource_ranges: T,
+    ) where
+        T: IntoIterator,
+        T::Item: Borrow<image::SubresourceRange>,
+    {
+        self.validator.check_image(
+            "clear_image",
+            image,
+            (image::Access::TRANSFER_WRITE, layout),
+            PipelineStage::TRANSFER,
+        );
+        self.cbuf
+            .clear_image(image, layout, color, depth_stencil, subresource_ranges);
+    }
+
+    /// Record buffer update.
+    pub fn update_buffer(&mut self, buffer: &B::Buffer, offset: buffer::Offset, data: &[u8]) {
+        self.validator.check_buffer(
+            "update_buffer",
+            buffer,
+            buffer::Access::TRANSFER_WRITE,
+            PipelineStage::TRANSFER,
+        );
+        self.cbuf.update_buffer(buffer, offset, data);
+    }
+
+    /// End validated recording.
+    /// Checks that every graph resource is returned to the declared state
+    /// as graph barriers expect.
+    pub fn finish(self) {
+        let validator = self.validator;
+        let unrestored = validator
+            .buffers
+            .values()
+            .filter(|&&(_, ref tracked)| tracked.current != tracked.declared)
+            .map(|&(id, _)| Resource::Buffer(id))
+            .chain(
+                once(&validator.images)
+                    .chain(validator.frame_images.get(validator.frame))
+                    .flat_map(|images| images.values())
+                    .filter(|&&(_, ref tracked)| tracked.current != tracked.declared)
+                    .map(|&(id, _)| Resource::Image(id)),
+            )
+            .collect::<SmallVec<[_; 16]>>();
+        for resource in unrestored {
+            validator.report("finish", resource, HazardKind::Unrestored);
+        }
+    }
+}
+
+/// Check that graph barriers lead to and from state covering the declared one.
+/// State of the chain link may cover accesses of several nodes.
+fn barriers_match<S, F>(
+    barriers: &Barriers<S>,
+    declared: &(S, PipelineStage),
+    covers: F,
+) -> bool
+where
+    F: Fn(&S, &S) -> bool,
+{
+    let matches = |state: &(S, PipelineStage)| {
+        covers(&state.0, &declared.0) && state.1.contains(declared.1)
+    };
+    barriers
+        .acquire
+        .as_ref()
+        .map_or(true, |barrier| matches(&barrier.end))
+        && barriers
+            .release
+            .as_ref()
+            .map_or(true, |barrier| matches(&barrier.start))
+}
+
+fn address<T>(resource: &T) -> usize {
+    resource as *const T as usize
+}
@@ -0,0 +1,448 @@
+//! Comparison of graph outputs against reference images.
+//!
+//! Images are read back with readback nodes. See `GraphBuilder::add_readback`.
+//! Reference images are stored as PNG files or binary PPM (`P6`) files with 8 bits per channel.
+//! Format is picked by the file extension. PNG references may be RGB or RGBA.
+//! Missing reference is written from actual image
+//! if `XFG_UPDATE_GOLDEN` environment variable is set.
+
+use std::{
+    borrow::Borrow, collections::HashMap, env, error::Error, fmt, fs::File, io::{self, Read, Write},
+    path::{Path, PathBuf},
+};
+
+use hal::{format::Format, queue::QueueFamilyId, Backend, Device};
+use png::{self, HasParameters};
+
+use graph::Graph;
+use node::readback::Readout;
+use util::NodeId;
+
+/// Environment variable that allows writing missing reference images.
+pub const UPDATE_VAR: &str = "XFG_UPDATE_GOLDEN";
+
+/// RGB image with 8 bits per channel.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Picture {
+    /// Width of the image.
+    pub width: u32,
+
+    /// Height of the image.
+    pub height: u32,
+
+    /// Tightly packed rows of RGB texels.
+    pub data: Vec<u8>,
+}
+
+impl Picture {
+    /// Convert data read back from image.
+    /// Supports 8 bit RGBA and BGRA formats. Alpha is dropped.
+    /// Returns `None` for buffers and other formats.
+    pub fn from_readout(readout: &Readout) -> Option<Self> {
+        let bgra = match readout.format? {
+            Format::Rgba8Unorm | Format::Rgba8Srgb => false,
+            Format::Bgra8Unorm | Format::Bgra8Srgb => true,
+            _ => return None,
+        };
+        let extent = readout.extent?;
+
+        let mut data = Vec::with_capacity((extent.width * extent.height * 3) as usize);
+        for row in readout
+            .data
+            .chunks(readout.row_pitch)
+            .take(extent.height as usize)
+        {
+            for texel in row[..extent.width as usize * 4].chunks(4) {
+                if bgra {
+                    data.extend_from_slice(&[texel[2], texel[1], texel[0]]);
+                } else {
+                    data.extend_from_slice(&texel[..3]);
+                }
+            }
+        }
+
+        Some(Picture {
+            width: extent.width,
+            height: extent.height,
+            data,
+        })
+    }
+
+    /// Read binary PPM file.
+    pub fn read_ppm<P: AsRef<Path>>(path: P) -> Result<Self, GoldenError> {
+        let mut bytes = Vec::new();
+        File::open(path)?.read_to_end(&mut bytes)?;
+
+        if !bytes.starts_with(b"P6") {
+            return Err(GoldenError::Format("Not a binary PPM".into()));
+        }
+        let mut pos = 2;
+        let mut header = [0u32; 3];
+        for value in &mut header {
+            // Skip whitespace and comments.
+            loop {
+                match bytes.get(pos) {
+                    Some(&b'#') => {
+                        while bytes.get(pos).map_or(false, |&b| b != b'\n') {
+                            pos += 1;
+                        }
+                    }
+                    Some(b) if b.is_ascii_whitespace() => pos += 1,
+                    _ => break,
+                }
+            }
+            let start = pos;
+            while bytes.get(pos).map_or(false, u8::is_ascii_digit) {
+                pos += 1;
+            }
+            *value = ::std::str::from_utf8(&bytes[start..pos])
+                .unwrap()
+                .parse()
+                .map_err(|_| GoldenError::Format("Malformed PPM header".into()))?;
+        }
+        let (width, height, max) = (header[0], header[1], header[2]);
+        if max != 255 {
+            return Err(GoldenError::Format("Only 8 bit PPM is supported".into()));
+        }
+        let size = data_size(width, height)?;
+
+        // Single whitespace separates header from data. Trailing bytes are ignored.
+        let data = bytes.get(pos + 1..).unwrap_or(&[]);
+        if data.len() < size {
+            return Err(GoldenError::Format("PPM data is truncated".into()));
+        }
+
+        Ok(Picture {
+            width,
+            height,
+            data: data[..size].to_vec(),
+        })
+    }
+
+    /// Write binary PPM file.
+    pub fn write_ppm<P: AsRef<Path>>(&self, path: P) -> Result<(), GoldenError> {
+        let mut file = File::create(path)?;
+        write!(file, "P6\n{} {}\n255\n", self.width, self.height)?;
+        file.write_all(&self.data)?;
+        Ok(())
+    }
+
+    /// Read PNG file with 8 bit RGB or RGBA texels. Alpha is dropped.
+    pub fn read_png<P: AsRef<Path>>(path: P) -> Result<Self, GoldenError> {
+        let decoder = png::Decoder::new(File::open(path)?);
+        let (info, mut reader) = decoder.read_info()?;
+        if info.bit_depth != png::BitDepth::Eight {
+            return Err(GoldenError::Format("Only 8 bit PNG is supported".into()));
+        }
+        let channels = match info.color_type {
+            png::ColorType::RGB => 3,
+            png::ColorType::RGBA => 4,
+            _ => return Err(GoldenError::Format("Only RGB and RGBA PNG is supported".into())),
+        };
+        let size = data_size(info.width, info.height)?;
+
+        let mut texels = vec![0; info.buffer_size()];
+        reader.next_frame(&mut texels)?;
+        let data = if channels == 3 {
+            texels.truncate(size);
+            texels
+        } else {
+            texels
+                .chunks(4)
+                .flat_map(|texel| texel[..3].iter().cloned())
+                .collect()
+        };
+
+        Ok(Picture {
+            width: info.width,
+            height: info.height,
+            data,
+        })
+    }
+
+    /// Write 8 bit RGB PNG file.
+    pub fn write_png<P: AsRef<Path>>(&self, path: P) -> Result<(), GoldenError> {
+        let mut encoder = png::Encoder::new(File::create(path)?, self.width, self.height);
+        encoder.set(png::ColorType::RGB).set(png::BitDepth::Eight);
+        let mut writer = encoder.write_header()?;
+        writer.write_image_data(&self.data)?;
+        Ok(())
+    }
+
+    /// Read PNG or PPM file depending on the extension.
+    /// Files without `png` extension are read as PPM.
+    pub fn read<P: AsRef<Path>>(path: P) -> Result<Self, GoldenError> {
+        if is_png(path.as_ref()) {
+            Picture::read_png(path)
+        } else {
+            Picture::read_ppm(path)
+        }
+    }
+
+    /// Write PNG or PPM file depending on the extension.
+    /// Files without `png` extension are written as PPM.
+    pub fn write<P: AsRef<Path>>(&self, path: P) -> Result<(), GoldenError> {
+        if is_png(path.as_ref()) {
+            self.write_png(path)
+        } else {
+            self.write_ppm(path)
+        }
+    }
+}
+
+/// Size of RGB data of the picture.
+/// Fails for extents which size doesn't fit into `usize`.
+fn data_size(width: u32, height: u32) -> Result<usize, GoldenError> {
+    (width as usize)
+        .checked_mul(height as usize)
+        .and_then(|texels| texels.checked_mul(3))
+        .ok_or_else(|| GoldenError::Format("Image is too large".into()))
+}
+
+fn is_png(path: &Path) -> bool {
+    path.extension()
+        .and_then(|ext| ext.to_str())
+        .map_or(false, |ext| ext.eq_ignore_ascii_case("png"))
+}
+
+/// Allowed difference between actual and reference images.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Tolerance {
+    /// Maximum difference of any channel.
+    pub max: u8,
+
+    /// Maximum root mean square error over all channels.
+    pub rmse: f64,
+}
+
+impl Tolerance {
+    /// Require images to be equal.
+    pub fn exact() -> Self {
+        Tolerance { max: 0, rmse: 0.0 }
+    }
+}
+
+/// Measured difference between actual and reference images.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Difference {
+    /// Maximum difference of any channel.
+    pub max: u8,
+
+    /// Root mean square error over all channels.
+    pub rmse: f64,
+}
+
+impl Difference {
+    /// Check if difference is within tolerance.
+    pub fn within(&self, tolerance: &Tolerance) -> bool {
+        self.max <= tolerance.max && self.rmse <= tolerance.rmse
+    }
+}
+
+/// Error that may occur during golden image comparison.
+#[derive(Debug)]
+pub enum GoldenError {
+    /// Failed to read or write image file.
+    Io(io::Error),
+
+    /// Reference image is malformed or format of the read back image is not supported.
+    Format(String),
+
+    /// Readback node didn't deliver any data.
+    NoData(NodeId),
+
+    /// Reference image is missing. Set `XFG_UPDATE_GOLDEN` to write it.
+    Missing(PathBuf),
+
+    /// Extents of actual and reference images differ.
+    Extent {
+        /// Extent of the actual image.
+        actual: (u32, u32),
+
+        /// Extent of the reference image.
+        expected: (u32, u32),
+    },
+
+    /// Difference exceeds tolerance.
+    Mismatch {
+        /// Measured difference.
+        difference: Difference,
+
+        /// Path where the diff image was written.
+        diff: PathBuf,
+    },
+}
+
+impl From<io::Error> for GoldenError {
+    fn from(error: io::Error) -> Self {
+        GoldenError::Io(error)
+    }
+}
+
+impl From<png::DecodingError> for GoldenError {
+    fn from(error: png::DecodingError) -> Self {
+        match error {
+            png::DecodingError::IoError(error) => GoldenError::Io(error),
+            error => GoldenError::Format(error.to_string()),
+        }
+    }
+}
+
+impl From<png::EncodingError> for GoldenError {
+    fn from(error: png::EncodingError) -> Self {
+        match error {
+            png::EncodingError::IoError(error) => GoldenError::Io(error),
+            error => GoldenError::Format(error.to_string()),
+        }
+    }
+}
+
+impl fmt::Display for GoldenError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            GoldenError::Io(ref error) => write!(f, "I/O error: {}", error),
+            GoldenError::Format(ref msg) => write!(f, "Format error: {}", msg),
+            GoldenError::NoData(node) => write!(f, "No data read back by {:?}", node),
+            GoldenError::Missing(ref path) => write!(
+                f,
+                "Reference image {} is missing. Set {} to write it",
+                path.display(),
+                UPDATE_VAR
+            ),
+            GoldenError::Extent { actual, expected } => write!(
+                f,
+                "Image extent {:?} differs from reference {:?}",
+                actual, expected
+            ),
+            GoldenError::Mismatch {
+                ref difference,
+                ref diff,
+            } => write!(
+                f,
+                "Image differs from reference by {:?}. See {}",
+                difference,
+                diff.display()
+            ),
+        }
+    }
+}
+
+impl Error for GoldenError {
+    fn description(&self) -> &str {
+        "Golden image comparison failed"
+    }
+}
+
+/// Measure difference between images of the same extent.
+pub fn difference(actual: &Picture, expected: &Picture) -> Difference {
+    assert_eq!((actual.width, actual.height), (expected.width, expected.height));
+    let mut max = 0;
+    let mut sum = 0.0;
+    for (&a, &e) in actual.data.iter().zip(&expected.data) {
+        let d = (a as i16 - e as i16).abs() as u8;
+        max = max.max(d);
+        sum += (d as f64) * (d as f64);
+    }
+    Difference {
+        max,
+        rmse: (sum / actual.data.len().max(1) as f64).sqrt(),
+    }
+}
+
+/// Create image of per-channel absolute differences.
+pub fn diff_picture(actual: &Picture, expected: &Picture) -> Picture {
+    assert_eq!((actual.width, actual.height), (expected.width, expected.height));
+    Picture {
+        width: actual.width,
+        height: actual.height,
+        data: actual
+            .data
+            .iter()
+            .zip(&expected.data)
+            .map(|(&a, &e)| (a as i16 - e as i16).abs() as u8)
+            .collect(),
+    }
+}
+
+/// Compare image against PNG or binary PPM reference stored at `path`.
+/// On mismatch diff image is written next to the reference with `.diff` added
+/// before the extension along with actual image with `.actual` added.
+/// E.g. `reference.diff.png` and `reference.actual.png`.
+pub fn check<P: AsRef<Path>>(
+    actual: &Picture,
+    path: P,
+    tolerance: &Tolerance,
+) -> Result<Difference, GoldenError> {
+    let path = path.as_ref();
+    if !path.exists() {
+        if env::var_os(UPDATE_VAR).is_some() {
+            actual.write(path)?;
+            return Ok(Difference { max: 0, rmse: 0.0 });
+        }
+        return Err(GoldenError::Missing(path.to_owned()));
+    }
+
+    let expected = Picture::read(path)?;
+    if (actual.width, actual.height) != (expected.width, expected.height) {
+        return Err(GoldenError::Extent {
+            actual: (actual.width, actual.height),
+            expected: (expected.width, expected.height),
+        });
+    }
+
+    let difference = difference(actual, &expected);
+    if difference.within(tolerance) {
+        return Ok(difference);
+    }
+
+    let extension = if is_png(path) { "png" } else { "ppm" };
+    let diff = path.with_extension(format!("diff.{}", extension));
+    diff_picture(actual, &expected).write(&diff)?;
+    actual.write(path.with_extension(format!("actual.{}", extension)))?;
+    Err(GoldenError::Mismatch { difference, diff })
+}
+
+/// Run the graph for number of frames and read back images from the readback nodes.
+/// Waits for all fences after each frame.
+/// Returns latest data of each readback node in the same order.
+///
+/// # Parameters
+///
+/// `frames`    - number of frames to run. Must be greater than `0`.
+///
+/// `readbacks` - ids returned by `GraphBuilder::add_readback`.
+pub fn run<B, D, T, U, I>(
+    graph: &mut Graph<B, D, T, U, I>,
+    frames: usize,
+    readbacks: &[NodeId],
+    command_queues: &mut HashMap<QueueFamilyId, Vec<B::CommandQueue>>,
+    device: &mut D,
+    aux: &mut T,
+    fences: &mut Vec<B::Fence>,
+) -> Result<Vec<Readout>, GoldenError>
+where
+    B: Backend,
+    D: Device<B>,
+    U: Borrow<B::Buffer>,
+    I: Borrow<B::Image>,
+{
+    assert!(frames > 0);
+    let mut readouts: Vec<Option<Readout>> = readbacks.iter().map(|_| None).collect();
+    for _ in 0..frames {
+        let status = graph.run(command_queues, device, aux, fences);
+        for fence in &fences[..status.fences] {
+            device.wait_for_fence(fence, !0);
+        }
+        for (readout, &node) in readouts.iter_mut().zip(readbacks) {
+            if let Some(latest) = graph.readback(node, device) {
+                *readout = Some(latest);
+            }
+        }
+    }
+
+    readouts
+        .into_iter()
+        .zip(readbacks)
+        .map(|(readout, &node)| readout.ok_or(GoldenError::NoData(node)))
+        .collect()
+}
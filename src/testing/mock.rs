@@ -0,0 +1,2054 @@
+//! Backend that records calls instead of sending them to a GPU.
+//!
+//! Every call made to the `Device`, command buffers and `CommandQueue`s
+//! of the backend is appended to the shared `Log`.
+//! Command buffers are logged along with the submission that executes them,
+//! so the log shows barriers as they were recorded and the order of submissions.
+//!
+//! Resources live in host memory. Clears, buffer updates and copies between buffers and images
+//! are executed when submitted so that readbacks see written data. Nothing is drawn.
+//! Fences are signaled at submission. Semaphores are only logged.
+//!
+//! `gfx-backend-empty` can't be used instead as its device is not implemented.
+
+use std::{
+    borrow::{Borrow, BorrowMut}, collections::HashMap, fmt, ops::Range,
+    sync::{atomic::{AtomicBool, AtomicUsize, Ordering}, Arc, Mutex},
+};
+
+use hal::{
+    self, adapter::{MemoryProperties, MemoryType}, buffer,
+    command::{
+        AttachmentClear, BufferCopy, BufferImageCopy, ClearColorRaw, ClearDepthStencilRaw,
+        ClearValueRaw, CommandBufferFlags, CommandBufferInheritanceInfo, DescriptorSetOffset,
+        ImageBlit, ImageCopy, ImageResolve, RawCommandBuffer, RawLevel, SubpassContents,
+    },
+    device::{BindError, FramebufferError, OutOfMemory, ShaderError, WaitFor},
+    error::{DeviceCreationError, HostExecutionError}, format, image, mapping,
+    memory::{Barrier, Dependencies, Properties, Requirements},
+    pass::{Attachment, SubpassDependency, SubpassDesc, SubpassRef},
+    pool::{CommandPoolCreateFlags, RawCommandPool},
+    pso::{
+        self, AllocationError, ClearRect, ColorValue, DepthBias, Descriptor, DescriptorRangeDesc,
+        DescriptorSetLayoutBinding, DescriptorSetWrite, Face, PipelineStage, Rect,
+        ShaderStageFlags, StencilValue, Viewport,
+    },
+    query::{Query, QueryControl, QueryId, QueryType, ResultFlags},
+    queue::{
+        QueueFamily as RawQueueFamily, QueueFamilyId, QueuePriority, QueueType, RawCommandQueue,
+        RawSubmission,
+    },
+    range::RangeArg,
+    window::{Backbuffer, Extent2D, FrameSync, PresentMode, SurfaceCapabilities, SwapchainConfig},
+    DrawCount, Features, Gpu, IndexCount, InstanceCount, Limits, MemoryTypeId, VertexCount,
+    VertexOffset, WorkGroupCount,
+};
+
+/// Backend that records calls instead of sending them to a GPU.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Backend {}
+
+impl hal::Backend for Backend {
+    type PhysicalDevice = PhysicalDevice;
+    type Device = Device;
+
+    type Surface = Surface;
+    type Swapchain = Swapchain;
+
+    type QueueFamily = QueueFamily;
+    type CommandQueue = CommandQueue;
+    type CommandBuffer = CommandBuffer;
+
+    type ShaderModule = Handle;
+    type RenderPass = Handle;
+    type Framebuffer = Framebuffer;
+
+    type Memory = Memory;
+    type CommandPool = CommandPool;
+
+    type UnboundBuffer = UnboundBuffer;
+    type Buffer = Buffer;
+    type BufferView = Handle;
+    type UnboundImage = UnboundImage;
+    type Image = Image;
+    type ImageView = ImageView;
+    type Sampler = Handle;
+
+    type ComputePipeline = Handle;
+    type GraphicsPipeline = Handle;
+    type PipelineLayout = Handle;
+    type DescriptorPool = DescriptorPool;
+    type DescriptorSet = Handle;
+    type DescriptorSetLayout = Handle;
+
+    type Fence = Fence;
+    type Semaphore = Handle;
+    type Event = Handle;
+    type QueryPool = Handle;
+}
+
+/// Dependency of the render pass subpasses.
+/// `None` stands for commands outside of the render pass.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Dependency {
+    /// Subpass the dependency is from.
+    pub from: Option<usize>,
+
+    /// Subpass the dependency is to.
+    pub to: Option<usize>,
+
+    /// Source and destination stages.
+    pub stages: Range<PipelineStage>,
+
+    /// Source and destination accesses.
+    pub accesses: Range<image::Access>,
+}
+
+/// Pipeline barrier recorded into the command buffer.
+#[derive(Clone, Debug, PartialEq)]
+pub struct PipelineBarrier {
+    /// Source and destination stages.
+    pub stages: Range<PipelineStage>,
+
+    /// Barriers of buffers by buffer id.
+    pub buffers: Vec<(usize, Range<buffer::State>)>,
+
+    /// Barriers of images by image id.
+    pub images: Vec<(usize, Range<image::State>)>,
+}
+
+impl PipelineBarrier {
+    fn new<'a, T>(stages: Range<PipelineStage>, barriers: T) -> Self
+    where
+        T: IntoIterator,
+        T::Item: Borrow<Barrier<'a, Backend>>,
+    {
+        let mut buffers = Vec::new();
+        let mut images = Vec::new();
+        for barrier in barriers {
+            match *barrier.borrow() {
+                Barrier::Buffer {
+                    ref states,
+                    target,
+                } => buffers.push((target.id, states.clone())),
+                Barrier::Image {
+                    ref states,
+                    target,
+                    ..
+                } => images.push((target.id, states.clone())),
+                _ => {}
+            }
+        }
+        PipelineBarrier {
+            stages,
+            buffers,
+            images,
+        }
+    }
+}
+
+/// Command recorded into the command buffer.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Command {
+    /// Pipeline barrier.
+    Barrier(PipelineBarrier),
+
+    /// Event set after stages.
+    SetEvent(usize, PipelineStage),
+
+    /// Event reset after stages.
+    ResetEvent(usize, PipelineStage),
+
+    /// Wait for events followed by the barrier.
+    WaitEvents(Vec<usize>, PipelineBarrier),
+
+    /// Image cleared in the layout.
+    ClearImage(usize, image::Layout),
+
+    /// Buffer updated with data.
+    UpdateBuffer(usize),
+
+    /// Buffer copied into buffer.
+    CopyBuffer(usize, usize),
+
+    /// Image in the layout copied into image in the layout.
+    CopyImage((usize, image::Layout), (usize, image::Layout)),
+
+    /// Image in the layout blitted into image in the layout.
+    BlitImage((usize, image::Layout), (usize, image::Layout)),
+
+    /// Buffer copied into image in the layout.
+    CopyBufferToImage(usize, (usize, image::Layout)),
+
+    /// Image in the layout copied into buffer.
+    CopyImageToBuffer((usize, image::Layout), usize),
+
+    /// Render pass begun with the framebuffer which attachments are image views listed.
+    BeginRenderPass {
+        /// Id of the render pass.
+        render_pass: usize,
+
+        /// Id of the framebuffer.
+        framebuffer: usize,
+
+        /// Ids of the attachment views.
+        attachments: Vec<usize>,
+    },
+
+    /// Render pass ended.
+    EndRenderPass,
+
+    /// Graphics descriptor sets bound.
+    BindDescriptorSets(Vec<usize>),
+
+    /// Timestamp written after stages.
+    WriteTimestamp(PipelineStage),
+
+    /// Secondary command buffers executed.
+    ExecuteCommands(Vec<usize>),
+
+    /// Any other command.
+    Other(&'static str),
+}
+
+/// Submission of command buffers to the queue.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Submission {
+    /// Family of the queue.
+    pub family: QueueFamilyId,
+
+    /// Index of the queue in the family.
+    pub queue: usize,
+
+    /// Semaphores waited along with stages.
+    pub waits: Vec<(usize, PipelineStage)>,
+
+    /// Semaphores signaled.
+    pub signals: Vec<usize>,
+
+    /// Ids of the command buffers submitted.
+    pub cbufs: Vec<usize>,
+
+    /// Commands of all command buffers in submission order.
+    pub commands: Vec<Command>,
+
+    /// Fence signaled.
+    pub fence: Option<usize>,
+}
+
+/// Present of swapchain images.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Present {
+    /// Family of the queue.
+    pub family: QueueFamilyId,
+
+    /// Index of the queue in the family.
+    pub queue: usize,
+
+    /// Swapchains along with indices of the images presented.
+    pub swapchains: Vec<(usize, u32)>,
+
+    /// Semaphores waited.
+    pub waits: Vec<usize>,
+}
+
+/// Call made to the backend.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Call {
+    /// Memory allocated.
+    AllocateMemory {
+        /// Id of the memory.
+        memory: usize,
+
+        /// Size of the memory.
+        size: u64,
+    },
+
+    /// Memory freed.
+    FreeMemory(usize),
+
+    /// Buffer created.
+    CreateBuffer {
+        /// Id of the buffer.
+        buffer: usize,
+
+        /// Size of the buffer.
+        size: u64,
+
+        /// Usage of the buffer.
+        usage: buffer::Usage,
+    },
+
+    /// Buffer destroyed.
+    DestroyBuffer(usize),
+
+    /// Image created.
+    CreateImage {
+        /// Id of the image.
+        image: usize,
+
+        /// Kind of the image.
+        kind: image::Kind,
+
+        /// Format of the image.
+        format: format::Format,
+
+        /// Usage of the image.
+        usage: image::Usage,
+    },
+
+    /// Image destroyed.
+    DestroyImage(usize),
+
+    /// Image view created.
+    CreateImageView {
+        /// Id of the view.
+        view: usize,
+
+        /// Id of the image.
+        image: usize,
+    },
+
+    /// Image view destroyed.
+    DestroyImageView(usize),
+
+    /// Render pass created.
+    CreateRenderPass {
+        /// Id of the render pass.
+        render_pass: usize,
+
+        /// Initial and final layouts of the attachments.
+        layouts: Vec<Range<image::Layout>>,
+
+        /// Dependencies of the subpasses.
+        dependencies: Vec<Dependency>,
+    },
+
+    /// Framebuffer created.
+    CreateFramebuffer {
+        /// Id of the framebuffer.
+        framebuffer: usize,
+
+        /// Ids of the attachment views.
+        attachments: Vec<usize>,
+    },
+
+    /// Framebuffer destroyed.
+    DestroyFramebuffer(usize),
+
+    /// Descriptors written into the set.
+    WriteDescriptorSet {
+        /// Id of the set.
+        set: usize,
+
+        /// Binding written.
+        binding: u32,
+
+        /// Ids of the image views written.
+        views: Vec<usize>,
+
+        /// Ids of the buffers written.
+        buffers: Vec<usize>,
+    },
+
+    /// Semaphore created.
+    CreateSemaphore(usize),
+
+    /// Semaphore destroyed.
+    DestroySemaphore(usize),
+
+    /// Fence created.
+    CreateFence(usize),
+
+    /// Fence destroyed.
+    DestroyFence(usize),
+
+    /// Swapchain created.
+    CreateSwapchain {
+        /// Id of the swapchain.
+        swapchain: usize,
+
+        /// Ids of the swapchain images. Empty if the backbuffer is a framebuffer.
+        images: Vec<usize>,
+
+        /// Extent of the swapchain images.
+        extent: image::Extent,
+    },
+
+    /// Swapchain destroyed.
+    DestroySwapchain(usize),
+
+    /// Swapchain image acquired. Index is `None` if acquisition failed.
+    AcquireImage {
+        /// Id of the swapchain.
+        swapchain: usize,
+
+        /// Index of the image acquired.
+        index: Option<u32>,
+    },
+
+    /// Any other object created.
+    Create(&'static str, usize),
+
+    /// Any other object destroyed.
+    Destroy(&'static str, usize),
+
+    /// Command buffers submitted.
+    Submit(Submission),
+
+    /// Swapchain images presented.
+    Present(Present),
+}
+
+/// Shared log of the calls made to the backend.
+#[derive(Clone, Debug, Default)]
+pub struct Log {
+    calls: Arc<Mutex<Vec<Call>>>,
+}
+
+impl Log {
+    /// Get all logged calls.
+    pub fn calls(&self) -> Vec<Call> {
+        self.calls.lock().unwrap().clone()
+    }
+
+    /// Get logged submissions.
+    pub fn submissions(&self) -> Vec<Submission> {
+        self.calls
+            .lock()
+            .unwrap()
+            .iter()
+            .filter_map(|call| match *call {
+                Call::Submit(ref submission) => Some(submission.clone()),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// Get logged presents.
+    pub fn presents(&self) -> Vec<Present> {
+        self.calls
+            .lock()
+            .unwrap()
+            .iter()
+            .filter_map(|call| match *call {
+                Call::Present(ref present) => Some(present.clone()),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// Get pipeline barriers of submitted command buffers in submission order.
+    /// Barriers recorded with events are included.
+    pub fn barriers(&self) -> Vec<PipelineBarrier> {
+        self.submissions()
+            .into_iter()
+            .flat_map(|submission| submission.commands)
+            .filter_map(|command| match command {
+                Command::Barrier(barrier) | Command::WaitEvents(_, barrier) => Some(barrier),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// Remove all logged calls.
+    pub fn clear(&self) {
+        self.calls.lock().unwrap().clear();
+    }
+
+    fn push(&self, call: Call) {
+        self.calls.lock().unwrap().push(call);
+    }
+}
+
+/// Log and generator of object ids shared by objects of the device.
+#[derive(Debug, Default)]
+struct Shared {
+    log: Log,
+    next: AtomicUsize,
+}
+
+impl Shared {
+    fn next(&self) -> usize {
+        self.next.fetch_add(1, Ordering::Relaxed)
+    }
+}
+
+/// Host memory backing resources.
+type Storage = Arc<Mutex<Vec<u8>>>;
+
+/// Work executed when command buffer is submitted.
+type Op = Arc<Fn() + Send + Sync>;
+
+/// Object identified only by its id.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Handle {
+    /// Id of the object. Unique among all objects of the device.
+    pub id: usize,
+}
+
+/// Memory allocated from the device.
+#[derive(Debug)]
+pub struct Memory {
+    /// Id of the memory.
+    pub id: usize,
+    data: Storage,
+}
+
+/// Buffer not bound to memory.
+#[derive(Debug)]
+pub struct UnboundBuffer {
+    id: usize,
+    size: u64,
+}
+
+/// Buffer bound to memory.
+#[derive(Debug)]
+pub struct Buffer {
+    /// Id of the buffer.
+    pub id: usize,
+    size: u64,
+    memory: Storage,
+    offset: u64,
+}
+
+/// Image not bound to memory.
+#[derive(Debug)]
+pub struct UnboundImage {
+    id: usize,
+    kind: image::Kind,
+    format: format::Format,
+}
+
+/// Image with host storage.
+/// Texels of the first level and layer are tightly packed.
+#[derive(Debug)]
+pub struct Image {
+    /// Id of the image.
+    pub id: usize,
+    kind: image::Kind,
+    format: format::Format,
+    data: Storage,
+}
+
+impl Image {
+    fn new(id: usize, kind: image::Kind, format: format::Format) -> Self {
+        Image {
+            id,
+            kind,
+            format,
+            data: Arc::new(Mutex::new(vec![0; image_size(kind, format) as usize])),
+        }
+    }
+
+    /// Copy texels of the image.
+    pub fn data(&self) -> Vec<u8> {
+        self.data.lock().unwrap().clone()
+    }
+
+    fn texel(&self) -> usize {
+        self.format.surface_desc().bits as usize / 8
+    }
+}
+
+/// View of the image.
+#[derive(Debug)]
+pub struct ImageView {
+    /// Id of the view.
+    pub id: usize,
+
+    /// Id of the image.
+    pub image: usize,
+}
+
+/// Framebuffer with attachments.
+#[derive(Debug)]
+pub struct Framebuffer {
+    /// Id of the framebuffer.
+    pub id: usize,
+    attachments: Vec<usize>,
+}
+
+/// Fence signaled at submission.
+#[derive(Debug)]
+pub struct Fence {
+    /// Id of the fence.
+    pub id: usize,
+    signaled: Arc<AtomicBool>,
+}
+
+/// Family of queues.
+#[derive(Clone, Debug)]
+pub struct QueueFamily {
+    id: QueueFamilyId,
+    queue_type: QueueType,
+    queues: usize,
+}
+
+impl QueueFamily {
+    /// Create family of queues of the type.
+    pub fn new(id: usize, queue_type: QueueType, queues: usize) -> Self {
+        QueueFamily {
+            id: QueueFamilyId(id),
+            queue_type,
+            queues,
+        }
+    }
+}
+
+impl RawQueueFamily for QueueFamily {
+    fn queue_type(&self) -> QueueType {
+        self.queue_type
+    }
+
+    fn max_queues(&self) -> usize {
+        self.queues
+    }
+
+    fn id(&self) -> QueueFamilyId {
+        self.id
+    }
+}
+
+/// Single general queue family.
+pub fn families() -> Vec<QueueFamily> {
+    vec![QueueFamily::new(0, QueueType::General, 1)]
+}
+
+/// Device local and host visible coherent memory types.
+pub fn memory_types() -> Vec<MemoryType> {
+    vec![
+        MemoryType {
+            properties: Properties::DEVICE_LOCAL,
+            heap_index: 0,
+        },
+        MemoryType {
+            properties: Properties::CPU_VISIBLE | Properties::COHERENT | Properties::CPU_CACHED,
+            heap_index: 1,
+        },
+    ]
+}
+
+/// Physical device of the mock backend. Devices are created with `Device::new` instead.
+#[derive(Debug)]
+pub struct PhysicalDevice;
+
+impl hal::PhysicalDevice<Backend> for PhysicalDevice {
+    fn open(
+        &self,
+        _: &[(&QueueFamily, &[QueuePriority])],
+    ) -> Result<Gpu<Backend>, DeviceCreationError> {
+        panic!("Mock devices are created with `Device::new`")
+    }
+
+    fn format_properties(&self, _: Option<format::Format>) -> format::Properties {
+        format::Properties::default()
+    }
+
+    fn image_format_properties(
+        &self,
+        _: format::Format,
+        _: u8,
+        _: image::Tiling,
+        _: image::Usage,
+        _: image::StorageFlags,
+    ) -> Option<image::FormatProperties> {
+        None
+    }
+
+    fn memory_properties(&self) -> MemoryProperties {
+        MemoryProperties {
+            memory_types: memory_types(),
+            memory_heaps: vec![!0, !0],
+        }
+    }
+
+    fn features(&self) -> Features {
+        Features::empty()
+    }
+
+    fn limits(&self) -> Limits {
+        Limits::default()
+    }
+}
+
+/// Surface of the given extent.
+#[derive(Debug)]
+pub struct Surface {
+    extent: Extent2D,
+    framebuffer: bool,
+    invalid: Arc<AtomicBool>,
+}
+
+impl Surface {
+    /// Create surface which swapchains have images.
+    pub fn new(width: u32, height: u32) -> Self {
+        Surface {
+            extent: Extent2D { width, height },
+            framebuffer: false,
+            invalid: Arc::new(AtomicBool::new(false)),
+        }
+    }
+
+    /// Create surface which swapchains have `Backbuffer::Framebuffer`.
+    pub fn with_framebuffer(width: u32, height: u32) -> Self {
+        Surface {
+            framebuffer: true,
+            ..Surface::new(width, height)
+        }
+    }
+
+    /// Change extent of the surface.
+    /// Swapchains created before fail to acquire and present images.
+    pub fn resize(&mut self, width: u32, height: u32) {
+        self.extent = Extent2D { width, height };
+        self.invalid.store(true, Ordering::Release);
+        self.invalid = Arc::new(AtomicBool::new(false));
+    }
+
+    /// Capabilities of the surface.
+    pub fn capabilities(&self) -> SurfaceCapabilities {
+        SurfaceCapabilities {
+            image_count: 2..4,
+            current_extent: Some(self.extent),
+            extents: self.extent..self.extent,
+            max_image_layers: 1,
+        }
+    }
+}
+
+impl hal::Surface<Backend> for Surface {
+    fn kind(&self) -> image::Kind {
+        image::Kind::D2(self.extent.width, self.extent.height, 1, 1)
+    }
+
+    fn compatibility(
+        &self,
+        _: &PhysicalDevice,
+    ) -> (SurfaceCapabilities, Option<Vec<format::Format>>, Vec<PresentMode>) {
+        (self.capabilities(), None, vec![PresentMode::Fifo])
+    }
+
+    fn supports_queue_family(&self, _: &QueueFamily) -> bool {
+        true
+    }
+}
+
+/// Swapchain cycling through its images.
+#[derive(Debug)]
+pub struct Swapchain {
+    /// Id of the swapchain.
+    pub id: usize,
+    count: u32,
+    next: u32,
+    invalid: Arc<AtomicBool>,
+    log: Log,
+}
+
+impl hal::Swapchain<Backend> for Swapchain {
+    fn acquire_image(&mut self, _: FrameSync<Backend>) -> Result<u32, ()> {
+        let index = if self.invalid.load(Ordering::Acquire) {
+            None
+        } else {
+            let index = self.next;
+            self.next = (self.next + 1) % self.count;
+            Some(index)
+        };
+        self.log.push(Call::AcquireImage {
+            swapchain: self.id,
+            index,
+        });
+        index.ok_or(())
+    }
+}
+
+/// Device recording calls into the log.
+#[derive(Debug, Default)]
+pub struct Device {
+    shared: Arc<Shared>,
+}
+
+impl Device {
+    /// Create device with empty log.
+    pub fn new() -> Self {
+        Device::default()
+    }
+
+    /// Log of the device and objects created by it.
+    pub fn log(&self) -> Log {
+        self.shared.log.clone()
+    }
+
+    /// Create queues of the families.
+    pub fn queues(&self, families: &[QueueFamily]) -> HashMap<QueueFamilyId, Vec<CommandQueue>> {
+        families
+            .iter()
+            .map(|family| {
+                let queues = (0..family.queues)
+                    .map(|index| CommandQueue {
+                        family: family.id,
+                        index,
+                        log: self.log(),
+                    })
+                    .collect();
+                (family.id, queues)
+            })
+            .collect()
+    }
+
+    /// Create buffer bound to its own memory.
+    /// Suitable for buffer callback of `GraphBuilder::build`.
+    pub fn buffer(&self, size: u64, usage: buffer::Usage) -> Buffer {
+        let unbound = hal::Device::create_buffer(self, size, usage).unwrap();
+        let memory = Memory {
+            id: self.shared.next(),
+            data: Arc::new(Mutex::new(vec![0; size as usize])),
+        };
+        hal::Device::bind_buffer_memory(self, &memory, 0, unbound).unwrap()
+    }
+
+    /// Create image with its own storage.
+    /// Suitable for image callback of `GraphBuilder::build`.
+    pub fn image(&self, kind: image::Kind, format: format::Format, usage: image::Usage) -> Image {
+        let unbound = hal::Device::create_image(
+            self,
+            kind,
+            1,
+            format,
+            image::Tiling::Optimal,
+            usage,
+            image::StorageFlags::empty(),
+        ).unwrap();
+        let memory = Memory {
+            id: self.shared.next(),
+            data: Arc::new(Mutex::new(Vec::new())),
+        };
+        hal::Device::bind_image_memory(self, &memory, 0, unbound).unwrap()
+    }
+
+    fn create(&self, kind: &'static str) -> Handle {
+        let id = self.shared.next();
+        self.shared.log.push(Call::Create(kind, id));
+        Handle { id }
+    }
+
+    fn destroy(&self, kind: &'static str, handle: Handle) {
+        self.shared.log.push(Call::Destroy(kind, handle.id));
+    }
+}
+
+impl hal::Device<Backend> for Device {
+    fn allocate_memory(&self, _: MemoryTypeId, size: u64) -> Result<Memory, OutOfMemory> {
+        let id = self.shared.next();
+        self.shared.log.push(Call::AllocateMemory { memory: id, size });
+        Ok(Memory {
+            id,
+            data: Arc::new(Mutex::new(vec![0; size as usize])),
+        })
+    }
+
+    fn free_memory(&self, memory: Memory) {
+        self.shared.log.push(Call::FreeMemory(memory.id));
+    }
+
+    fn create_command_pool(&self, family: QueueFamilyId, _: CommandPoolCreateFlags) -> CommandPool {
+        let id = self.shared.next();
+        self.shared.log.push(Call::Create("CommandPool", id));
+        CommandPool {
+            id,
+            family,
+            shared: self.shared.clone(),
+        }
+    }
+
+    fn destroy_command_pool(&self, pool: CommandPool) {
+        self.shared.log.push(Call::Destroy("CommandPool", pool.id));
+    }
+
+    fn create_render_pass<'a, IA, IS, ID>(
+        &self,
+        attachments: IA,
+        _: IS,
+        dependencies: ID,
+    ) -> Handle
+    where
+        IA: IntoIterator,
+        IA::Item: Borrow<Attachment>,
+        IS: IntoIterator,
+        IS::Item: Borrow<SubpassDesc<'a>>,
+        ID: IntoIterator,
+        ID::Item: Borrow<SubpassDependency>,
+    {
+        let subpass = |pass: &SubpassRef| match *pass {
+            SubpassRef::External => None,
+            SubpassRef::Pass(index) => Some(index),
+        };
+        let id = self.shared.next();
+        self.shared.log.push(Call::CreateRenderPass {
+            render_pass: id,
+            layouts: attachments
+                .into_iter()
+                .map(|attachment| attachment.borrow().layouts.clone())
+                .collect(),
+            dependencies: dependencies
+                .into_iter()
+                .map(|dependency| {
+                    let dependency = dependency.borrow();
+                    Dependency {
+                        from: subpass(&dependency.passes.start),
+                        to: subpass(&dependency.passes.end),
+                        stages: dependency.stages.clone(),
+                        accesses: dependency.accesses.clone(),
+                    }
+                })
+                .collect(),
+        });
+        Handle { id }
+    }
+
+    fn destroy_render_pass(&self, render_pass: Handle) {
+        self.destroy("RenderPass", render_pass);
+    }
+
+    fn create_pipeline_layout<IS, IR>(&self, _: IS, _: IR) -> Handle
+    where
+        IS: IntoIterator,
+        IS::Item: Borrow<Handle>,
+        IR: IntoIterator,
+        IR::Item: Borrow<(ShaderStageFlags, Range<u32>)>,
+    {
+        self.create("PipelineLayout")
+    }
+
+    fn destroy_pipeline_layout(&self, layout: Handle) {
+        self.destroy("PipelineLayout", layout);
+    }
+
+    fn create_graphics_pipeline<'a>(
+        &self,
+        _: &pso::GraphicsPipelineDesc<'a, Backend>,
+    ) -> Result<Handle, pso::CreationError> {
+        Ok(self.create("GraphicsPipeline"))
+    }
+
+    fn create_graphics_pipelines<'a, T>(
+        &self,
+        descs: T,
+    ) -> Vec<Result<Handle, pso::CreationError>>
+    where
+        T: IntoIterator,
+        T::Item: Borrow<pso::GraphicsPipelineDesc<'a, Backend>>,
+    {
+        descs
+            .into_iter()
+            .map(|desc| self.create_graphics_pipeline(desc.borrow()))
+            .collect()
+    }
+
+    fn destroy_graphics_pipeline(&self, pipeline: Handle) {
+        self.destroy("GraphicsPipeline", pipeline);
+    }
+
+    fn create_compute_pipeline<'a>(
+        &self,
+        _: &pso::ComputePipelineDesc<'a, Backend>,
+    ) -> Result<Handle, pso::CreationError> {
+        Ok(self.create("ComputePipeline"))
+    }
+
+    fn create_compute_pipelines<'a, T>(
+        &self,
+        descs: T,
+    ) -> Vec<Result<Handle, pso::CreationError>>
+    where
+        T: IntoIterator,
+        T::Item: Borrow<pso::ComputePipelineDesc<'a, Backend>>,
+    {
+        descs
+            .into_iter()
+            .map(|desc| self.create_compute_pipeline(desc.borrow()))
+            .collect()
+    }
+
+    fn destroy_compute_pipeline(&self, pipeline: Handle) {
+        self.destroy("ComputePipeline", pipeline);
+    }
+
+    fn create_framebuffer<I>(
+        &self,
+        _: &Handle,
+        attachments: I,
+        _: image::Extent,
+    ) -> Result<Framebuffer, FramebufferError>
+    where
+        I: IntoIterator,
+        I::Item: Borrow<ImageView>,
+    {
+        let id = self.shared.next();
+        let attachments = attachments
+            .into_iter()
+            .map(|view| view.borrow().id)
+            .collect::<Vec<_>>();
+        self.shared.log.push(Call::CreateFramebuffer {
+            framebuffer: id,
+            attachments: attachments.clone(),
+        });
+        Ok(Framebuffer { id, attachments })
+    }
+
+    fn destroy_framebuffer(&self, framebuffer: Framebuffer) {
+        self.shared
+            .log
+            .push(Call::DestroyFramebuffer(framebuffer.id));
+    }
+
+    fn create_shader_module(&self, _: &[u8]) -> Result<Handle, ShaderError> {
+        Ok(self.create("ShaderModule"))
+    }
+
+    fn destroy_shader_module(&self, module: Handle) {
+        self.destroy("ShaderModule", module);
+    }
+
+    fn create_buffer(
+        &self,
+        size: u64,
+        usage: buffer::Usage,
+    ) -> Result<UnboundBuffer, buffer::CreationError> {
+        let id = self.shared.next();
+        self.shared.log.push(Call::CreateBuffer {
+            buffer: id,
+            size,
+            usage,
+        });
+        Ok(UnboundBuffer { id, size })
+    }
+
+    fn get_buffer_requirements(&self, buffer: &UnboundBuffer) -> Requirements {
+        Requirements {
+            size: buffer.size,
+            alignment: 1,
+            type_mask: 0b11,
+        }
+    }
+
+    fn bind_buffer_memory(
+        &self,
+        memory: &Memory,
+        offset: u64,
+        buffer: UnboundBuffer,
+    ) -> Result<Buffer, BindError> {
+        Ok(Buffer {
+            id: buffer.id,
+            size: buffer.size,
+            memory: memory.data.clone(),
+            offset,
+        })
+    }
+
+    fn destroy_buffer(&self, buffer: Buffer) {
+        self.shared.log.push(Call::DestroyBuffer(buffer.id));
+    }
+
+    fn create_buffer_view<R>(
+        &self,
+        _: &Buffer,
+        _: Option<format::Format>,
+        _: R,
+    ) -> Result<Handle, buffer::ViewError>
+    where
+        R: RangeArg<u64>,
+    {
+        Ok(self.create("BufferView"))
+    }
+
+    fn destroy_buffer_view(&self, view: Handle) {
+        self.destroy("BufferView", view);
+    }
+
+    fn create_image(
+        &self,
+        kind: image::Kind,
+        _: image::Level,
+        format: format::Format,
+        _: image::Tiling,
+        usage: image::Usage,
+        _: image::StorageFlags,
+    ) -> Result<UnboundImage, image::CreationError> {
+        let id = self.shared.next();
+        self.shared.log.push(Call::CreateImage {
+            image: id,
+            kind,
+            format,
+            usage,
+        });
+        Ok(UnboundImage { id, kind, format })
+    }
+
+    fn get_image_requirements(&self, image: &UnboundImage) -> Requirements {
+        Requirements {
+            size: image_size(image.kind, image.format),
+            alignment: 1,
+            type_mask: 0b11,
+        }
+    }
+
+    fn get_image_subresource_footprint(
+        &self,
+        image: &Image,
+        _: image::Subresource,
+    ) -> image::SubresourceFootprint {
+        let extent = image.kind.extent();
+        let row_pitch = (extent.width as usize * image.texel()) as u64;
+        let depth_pitch = row_pitch * extent.height as u64;
+        image::SubresourceFootprint {
+            slice: 0..depth_pitch * extent.depth as u64,
+            row_pitch,
+            array_pitch: depth_pitch * extent.depth as u64,
+            depth_pitch,
+        }
+    }
+
+    fn bind_image_memory(
+        &self,
+        _: &Memory,
+        _: u64,
+        image: UnboundImage,
+    ) -> Result<Image, BindError> {
+        Ok(Image::new(image.id, image.kind, image.format))
+    }
+
+    fn destroy_image(&self, image: Image) {
+        self.shared.log.push(Call::DestroyImage(image.id));
+    }
+
+    fn create_image_view(
+        &self,
+        image: &Image,
+        _: image::ViewKind,
+        _: format::Format,
+        _: format::Swizzle,
+        _: image::SubresourceRange,
+    ) -> Result<ImageView, image::ViewError> {
+        let id = self.shared.next();
+        self.shared.log.push(Call::CreateImageView {
+            view: id,
+            image: image.id,
+        });
+        Ok(ImageView {
+            id,
+            image: image.id,
+        })
+    }
+
+    fn destroy_image_view(&self, view: ImageView) {
+        self.shared.log.push(Call::DestroyImageView(view.id));
+    }
+
+    fn create_sampler(&self, _: image::SamplerInfo) -> Handle {
+        self.create("Sampler")
+    }
+
+    fn destroy_sampler(&self, sampler: Handle) {
+        self.destroy("Sampler", sampler);
+    }
+
+    fn create_descriptor_pool<I>(&self, _: usize, _: I) -> DescriptorPool
+    where
+        I: IntoIterator,
+        I::Item: Borrow<DescriptorRangeDesc>,
+    {
+        let id = self.shared.next();
+        self.shared.log.push(Call::Create("DescriptorPool", id));
+        DescriptorPool {
+            id,
+            shared: self.shared.clone(),
+        }
+    }
+
+    fn destroy_descriptor_pool(&self, pool: DescriptorPool) {
+        self.shared
+            .log
+            .push(Call::Destroy("DescriptorPool", pool.id));
+    }
+
+    fn create_descriptor_set_layout<I, J>(&self, _: I, _: J) -> Handle
+    where
+        I: IntoIterator,
+        I::Item: Borrow<DescriptorSetLayoutBinding>,
+        J: IntoIterator,
+        J::Item: Borrow<Handle>,
+    {
+        self.create("DescriptorSetLayout")
+    }
+
+    fn destroy_descriptor_set_layout(&self, layout: Handle) {
+        self.destroy("DescriptorSetLayout", layout);
+    }
+
+    fn write_descriptor_sets<'a, I, J>(&self, writes: I)
+    where
+        I: IntoIterator<Item = DescriptorSetWrite<'a, Backend, J>>,
+        J: IntoIterator,
+        J::Item: Borrow<Descriptor<'a, Backend>>,
+    {
+        for write in writes {
+            let mut views = Vec::new();
+            let mut buffers = Vec::new();
+            for descriptor in write.descriptors {
+                match *descriptor.borrow() {
+                    Descriptor::Image(view, _) | Descriptor::CombinedImageSampler(view, _, _) => {
+                        views.push(view.id)
+                    }
+                    Descriptor::Buffer(buffer, _) => buffers.push(buffer.id),
+                    _ => {}
+                }
+            }
+            self.shared.log.push(Call::WriteDescriptorSet {
+                set: write.set.id,
+                binding: write.binding as u32,
+                views,
+                buffers,
+            });
+        }
+    }
+
+    fn copy_descriptor_sets<'a, I>(&self, _: I)
+    where
+        I: IntoIterator,
+        I::Item: Borrow<pso::DescriptorSetCopy<'a, Backend>>,
+    {
+    }
+
+    fn map_memory<R>(&self, memory: &Memory, range: R) -> Result<*mut u8, mapping::Error>
+    where
+        R: RangeArg<u64>,
+    {
+        // Storage is never reallocated so the pointer stays valid until the memory is freed.
+        let offset = range.start().cloned().unwrap_or(0) as usize;
+        Ok(unsafe { memory.data.lock().unwrap().as_mut_ptr().offset(offset as isize) })
+    }
+
+    fn flush_mapped_memory_ranges<'a, I, R>(&self, _: I)
+    where
+        I: IntoIterator,
+        I::Item: Borrow<(&'a Memory, R)>,
+        R: RangeArg<u64>,
+    {
+    }
+
+    fn invalidate_mapped_memory_ranges<'a, I, R>(&self, _: I)
+    where
+        I: IntoIterator,
+        I::Item: Borrow<(&'a Memory, R)>,
+        R: RangeArg<u64>,
+    {
+    }
+
+    fn unmap_memory(&self, _: &Memory) {}
+
+    fn create_semaphore(&self) -> Handle {
+        let id = self.shared.next();
+        self.shared.log.push(Call::CreateSemaphore(id));
+        Handle { id }
+    }
+
+    fn destroy_semaphore(&self, semaphore: Handle) {
+        self.shared.log.push(Call::DestroySemaphore(semaphore.id));
+    }
+
+    fn create_fence(&self, signaled: bool) -> Fence {
+        let id = self.shared.next();
+        self.shared.log.push(Call::CreateFence(id));
+        Fence {
+            id,
+            signaled: Arc::new(AtomicBool::new(signaled)),
+        }
+    }
+
+    fn reset_fences<I>(&self, fences: I)
+    where
+        I: IntoIterator,
+        I::Item: Borrow<Fence>,
+    {
+        for fence in fences {
+            fence.borrow().signaled.store(false, Ordering::Release);
+        }
+    }
+
+    fn wait_for_fences<I>(&self, fences: I, wait: WaitFor, _: u32) -> bool
+    where
+        I: IntoIterator,
+        I::Item: Borrow<Fence>,
+    {
+        // Submitted work is complete. Fences not submitted would never be signaled.
+        let mut statuses = fences
+            .into_iter()
+            .map(|fence| fence.borrow().signaled.load(Ordering::Acquire));
+        match wait {
+            WaitFor::All => statuses.all(|signaled| signaled),
+            WaitFor::Any => statuses.any(|signaled| signaled),
+        }
+    }
+
+    fn get_fence_status(&self, fence: &Fence) -> bool {
+        fence.signaled.load(Ordering::Acquire)
+    }
+
+    fn destroy_fence(&self, fence: Fence) {
+        self.shared.log.push(Call::DestroyFence(fence.id));
+    }
+
+    fn create_event(&self) -> Handle {
+        self.create("Event")
+    }
+
+    fn destroy_event(&self, event: Handle) {
+        self.destroy("Event", event);
+    }
+
+    fn create_query_pool(&self, _: QueryType, _: u32) -> Handle {
+        self.create("QueryPool")
+    }
+
+    fn destroy_query_pool(&self, pool: Handle) {
+        self.destroy("QueryPool", pool);
+    }
+
+    fn get_query_pool_results(
+        &self,
+        _: &Handle,
+        _: Range<QueryId>,
+        data: &mut [u8],
+        _: buffer::Offset,
+        _: ResultFlags,
+    ) -> Result<bool, HostExecutionError> {
+        // Every query reports zero ticks.
+        for byte in data {
+            *byte = 0;
+        }
+        Ok(true)
+    }
+
+    fn create_swapchain(
+        &self,
+        surface: &mut Surface,
+        config: SwapchainConfig,
+        old: Option<Swapchain>,
+        extent: &Extent2D,
+    ) -> (Swapchain, Backbuffer<Backend>) {
+        if let Some(old) = old {
+            self.destroy_swapchain(old);
+        }
+        let id = self.shared.next();
+        let kind = image::Kind::D2(extent.width, extent.height, 1, 1);
+        let backbuffer = if surface.framebuffer {
+            Backbuffer::Framebuffer(Framebuffer {
+                id: self.shared.next(),
+                attachments: Vec::new(),
+            })
+        } else {
+            Backbuffer::Images(
+                (0..config.image_count)
+                    .map(|_| Image::new(self.shared.next(), kind, config.color_format))
+                    .collect(),
+            )
+        };
+        self.shared.log.push(Call::CreateSwapchain {
+            swapchain: id,
+            images: match backbuffer {
+                Backbuffer::Images(ref images) => images.iter().map(|image| image.id).collect(),
+                Backbuffer::Framebuffer(_) => Vec::new(),
+            },
+            extent: kind.extent(),
+        });
+        let swapchain = Swapchain {
+            id,
+            count: config.image_count,
+            next: 0,
+            invalid: surface.invalid.clone(),
+            log: self.log(),
+        };
+        (swapchain, backbuffer)
+    }
+
+    fn destroy_swapchain(&self, swapchain: Swapchain) {
+        self.shared.log.push(Call::DestroySwapchain(swapchain.id));
+    }
+
+    fn wait_idle(&self) -> Result<(), HostExecutionError> {
+        Ok(())
+    }
+}
+
+/// Queue logging submissions and presents.
+#[derive(Debug)]
+pub struct CommandQueue {
+    family: QueueFamilyId,
+    index: usize,
+    log: Log,
+}
+
+impl RawCommandQueue<Backend> for CommandQueue {
+    unsafe fn submit_raw<IC>(
+        &mut self,
+        submission: RawSubmission<Backend, IC>,
+        fence: Option<&Fence>,
+    ) where
+        IC: IntoIterator,
+        IC::Item: Borrow<CommandBuffer>,
+    {
+        let mut cbufs = Vec::new();
+        let mut commands = Vec::new();
+        for cbuf in submission.cmd_buffers {
+            let cbuf = cbuf.borrow();
+            for op in &cbuf.ops {
+                op();
+            }
+            cbufs.push(cbuf.id);
+            commands.extend(cbuf.commands.iter().cloned());
+        }
+        if let Some(fence) = fence {
+            fence.signaled.store(true, Ordering::Release);
+        }
+        self.log.push(Call::Submit(Submission {
+            family: self.family,
+            queue: self.index,
+            waits: submission
+                .wait_semaphores
+                .iter()
+                .map(|&(semaphore, stage)| (semaphore.id, stage))
+                .collect(),
+            signals: submission
+                .signal_semaphores
+                .iter()
+                .map(|semaphore| semaphore.id)
+                .collect(),
+            cbufs,
+            commands,
+            fence: fence.map(|fence| fence.id),
+        }));
+    }
+
+    fn present<IS, S, IW>(&mut self, swapchains: IS, wait_semaphores: IW) -> Result<(), ()>
+    where
+        IS: IntoIterator<Item = (S, u32)>,
+        S: BorrowMut<Swapchain>,
+        IW: IntoIterator,
+        IW::Item: Borrow<Handle>,
+    {
+        let mut invalid = false;
+        let swapchains = swapchains
+            .into_iter()
+            .map(|(swapchain, index)| {
+                let swapchain = swapchain.borrow();
+                invalid |= swapchain.invalid.load(Ordering::Acquire);
+                (swapchain.id, index)
+            })
+            .collect();
+        self.log.push(Call::Present(Present {
+            family: self.family,
+            queue: self.index,
+            swapchains,
+            waits: wait_semaphores
+                .into_iter()
+                .map(|semaphore| semaphore.borrow().id)
+                .collect(),
+        }));
+        if invalid {
+            Err(())
+        } else {
+            Ok(())
+        }
+    }
+
+    fn wait_idle(&self) -> Result<(), HostExecutionError> {
+        Ok(())
+    }
+}
+
+/// Pool allocating command buffers.
+#[derive(Debug)]
+pub struct CommandPool {
+    id: usize,
+    family: QueueFamilyId,
+    shared: Arc<Shared>,
+}
+
+impl RawCommandPool<Backend> for CommandPool {
+    fn reset(&mut self) {}
+
+    fn allocate(&mut self, num: usize, _: RawLevel) -> Vec<CommandBuffer> {
+        (0..num)
+            .map(|_| CommandBuffer {
+                id: self.shared.next(),
+                commands: Vec::new(),
+                ops: Vec::new(),
+            })
+            .collect()
+    }
+
+    unsafe fn free(&mut self, _: Vec<CommandBuffer>) {}
+}
+
+/// Pool allocating descriptor sets.
+#[derive(Debug)]
+pub struct DescriptorPool {
+    id: usize,
+    shared: Arc<Shared>,
+}
+
+impl pso::DescriptorPool<Backend> for DescriptorPool {
+    fn allocate_set(&mut self, _: &Handle) -> Result<Handle, AllocationError> {
+        Ok(Handle {
+            id: self.shared.next(),
+        })
+    }
+
+    fn free_sets<I>(&mut self, _: I)
+    where
+        I: IntoIterator<Item = Handle>,
+    {
+    }
+
+    fn reset(&mut self) {}
+}
+
+/// Command buffer keeping recorded commands.
+#[derive(Clone)]
+pub struct CommandBuffer {
+    /// Id of the command buffer.
+    pub id: usize,
+    commands: Vec<Command>,
+    ops: Vec<Op>,
+}
+
+impl CommandBuffer {
+    /// Commands recorded since the last `begin`.
+    pub fn commands(&self) -> &[Command] {
+        &self.commands
+    }
+
+    fn push(&mut self, command: Command) {
+        self.commands.push(command);
+    }
+}
+
+impl fmt::Debug for CommandBuffer {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("CommandBuffer")
+            .field("id", &self.id)
+            .field("commands", &self.commands)
+            .finish()
+    }
+}
+
+impl RawCommandBuffer<Backend> for CommandBuffer {
+    fn begin(&mut self, _: CommandBufferFlags, _: CommandBufferInheritanceInfo<Backend>) {
+        self.commands.clear();
+        self.ops.clear();
+    }
+
+    fn finish(&mut self) {}
+
+    fn reset(&mut self, _: bool) {
+        self.commands.clear();
+        self.ops.clear();
+    }
+
+    fn pipeline_barrier<'a, T>(
+        &mut self,
+        stages: Range<PipelineStage>,
+        _: Dependencies,
+        barriers: T,
+    ) where
+        T: IntoIterator,
+        T::Item: Borrow<Barrier<'a, Backend>>,
+    {
+        self.push(Command::Barrier(PipelineBarrier::new(stages, barriers)));
+    }
+
+    fn fill_buffer<R>(&mut self, _: &Buffer, _: R, _: u32)
+    where
+        R: RangeArg<buffer::Offset>,
+    {
+        self.push(Command::Other("fill_buffer"));
+    }
+
+    fn update_buffer(&mut self, buffer: &Buffer, offset: buffer::Offset, data: &[u8]) {
+        self.push(Command::UpdateBuffer(buffer.id));
+        let memory = buffer.memory.clone();
+        let offset = (buffer.offset + offset) as usize;
+        let data = data.to_vec();
+        self.ops.push(Arc::new(move || {
+            memory.lock().unwrap()[offset..offset + data.len()].copy_from_slice(&data);
+        }));
+    }
+
+    fn clear_image<T>(
+        &mut self,
+        image: &Image,
+        layout: image::Layout,
+        color: ClearColorRaw,
+        _: ClearDepthStencilRaw,
+        _: T,
+    ) where
+        T: IntoIterator,
+        T::Item: Borrow<image::SubresourceRange>,
+    {
+        self.push(Command::ClearImage(image.id, layout));
+        // Only 8-bit RGBA texels are cleared to the color. Others are zeroed.
+        let texel = if image.texel() == 4 {
+            let float32 = unsafe { color.float32 };
+            let mut texel = [0u8; 4];
+            for (byte, value) in texel.iter_mut().zip(&float32) {
+                *byte = (value.max(0.0).min(1.0) * 255.0).round() as u8;
+            }
+            texel.to_vec()
+        } else {
+            vec![0; image.texel()]
+        };
+        let data = image.data.clone();
+        self.ops.push(Arc::new(move || {
+            for chunk in data.lock().unwrap().chunks_mut(texel.len()) {
+                chunk.copy_from_slice(&texel);
+            }
+        }));
+    }
+
+    fn clear_attachments<T, U>(&mut self, _: T, _: U)
+    where
+        T: IntoIterator,
+        T::Item: Borrow<AttachmentClear>,
+        U: IntoIterator,
+        U::Item: Borrow<ClearRect>,
+    {
+        self.push(Command::Other("clear_attachments"));
+    }
+
+    fn resolve_image<T>(&mut self, _: &Image, _: image::Layout, _: &Image, _: image::Layout, _: T)
+    where
+        T: IntoIterator,
+        T::Item: Borrow<ImageResolve>,
+    {
+        self.push(Command::Other("resolve_image"));
+    }
+
+    fn blit_image<T>(
+        &mut self,
+        src: &Image,
+        src_layout: image::Layout,
+        dst: &Image,
+        dst_layout: image::Layout,
+        _: image::Filter,
+        _: T,
+    ) where
+        T: IntoIterator,
+        T::Item: Borrow<ImageBlit>,
+    {
+        self.push(Command::BlitImage(
+            (src.id, src_layout),
+            (dst.id, dst_layout),
+        ));
+    }
+
+    fn bind_index_buffer(&mut self, _: buffer::IndexBufferView<Backend>) {
+        self.push(Command::Other("bind_index_buffer"));
+    }
+
+    fn bind_vertex_buffers(&mut self, _: u32, _: pso::VertexBufferSet<Backend>) {
+        self.push(Command::Other("bind_vertex_buffers"));
+    }
+
+    fn set_viewports<T>(&mut self, _: u32, _: T)
+    where
+        T: IntoIterator,
+        T::Item: Borrow<Viewport>,
+    {
+        self.push(Command::Other("set_viewports"));
+    }
+
+    fn set_scissors<T>(&mut self, _: u32, _: T)
+    where
+        T: IntoIterator,
+        T::Item: Borrow<Rect>,
+    {
+        self.push(Command::Other("set_scissors"));
+    }
+
+    fn set_stencil_reference(&mut self, _: Face, _: StencilValue) {
+        self.push(Command::Other("set_stencil_reference"));
+    }
+
+    fn set_stencil_read_mask(&mut self, _: Face, _: StencilValue) {
+        self.push(Command::Other("set_stencil_read_mask"));
+    }
+
+    fn set_stencil_write_mask(&mut self, _: Face, _: StencilValue) {
+        self.push(Command::Other("set_stencil_write_mask"));
+    }
+
+    fn set_blend_constants(&mut self, _: ColorValue) {
+        self.push(Command::Other("set_blend_constants"));
+    }
+
+    fn set_depth_bounds(&mut self, _: Range<f32>) {
+        self.push(Command::Other("set_depth_bounds"));
+    }
+
+    fn set_line_width(&mut self, _: f32) {
+        self.push(Command::Other("set_line_width"));
+    }
+
+    fn set_depth_bias(&mut self, _: DepthBias) {
+        self.push(Command::Other("set_depth_bias"));
+    }
+
+    fn begin_render_pass<T>(
+        &mut self,
+        render_pass: &Handle,
+        framebuffer: &Framebuffer,
+        _: Rect,
+        _: T,
+        _: SubpassContents,
+    ) where
+        T: IntoIterator,
+        T::Item: Borrow<ClearValueRaw>,
+    {
+        self.push(Command::BeginRenderPass {
+            render_pass: render_pass.id,
+            framebuffer: framebuffer.id,
+            attachments: framebuffer.attachments.clone(),
+        });
+    }
+
+    fn next_subpass(&mut self, _: SubpassContents) {
+        self.push(Command::Other("next_subpass"));
+    }
+
+    fn end_render_pass(&mut self) {
+        self.push(Command::EndRenderPass);
+    }
+
+    fn bind_graphics_pipeline(&mut self, _: &Handle) {
+        self.push(Command::Other("bind_graphics_pipeline"));
+    }
+
+    fn bind_graphics_descriptor_sets<I, J>(&mut self, _: &Handle, _: usize, sets: I, _: J)
+    where
+        I: IntoIterator,
+        I::Item: Borrow<Handle>,
+        J: IntoIterator,
+        J::Item: Borrow<DescriptorSetOffset>,
+    {
+        let sets = sets.into_iter().map(|set| set.borrow().id).collect();
+        self.push(Command::BindDescriptorSets(sets));
+    }
+
+    fn bind_compute_pipeline(&mut self, _: &Handle) {
+        self.push(Command::Other("bind_compute_pipeline"));
+    }
+
+    fn bind_compute_descriptor_sets<I, J>(&mut self, _: &Handle, _: usize, _: I, _: J)
+    where
+        I: IntoIterator,
+        I::Item: Borrow<Handle>,
+        J: IntoIterator,
+        J::Item: Borrow<DescriptorSetOffset>,
+    {
+        self.push(Command::Other("bind_compute_descriptor_sets"));
+    }
+
+    fn dispatch(&mut self, _: WorkGroupCount) {
+        self.push(Command::Other("dispatch"));
+    }
+
+    fn dispatch_indirect(&mut self, _: &Buffer, _: buffer::Offset) {
+        self.push(Command::Other("dispatch_indirect"));
+    }
+
+    fn copy_buffer<T>(&mut self, src: &Buffer, dst: &Buffer, regions: T)
+    where
+        T: IntoIterator,
+        T::Item: Borrow<BufferCopy>,
+    {
+        self.push(Command::CopyBuffer(src.id, dst.id));
+        let regions = regions
+            .into_iter()
+            .map(|region| region.borrow().clone())
+            .collect::<Vec<_>>();
+        let (src_memory, src_offset) = (src.memory.clone(), src.offset);
+        let (dst_memory, dst_offset) = (dst.memory.clone(), dst.offset);
+        self.ops.push(Arc::new(move || {
+            for region in &regions {
+                let src = (src_offset + region.src) as usize;
+                let dst = (dst_offset + region.dst) as usize;
+                let size = region.size as usize;
+                let data = src_memory.lock().unwrap()[src..src + size].to_vec();
+                dst_memory.lock().unwrap()[dst..dst + size].copy_from_slice(&data);
+            }
+        }));
+    }
+
+    fn copy_image<T>(
+        &mut self,
+        src: &Image,
+        src_layout: image::Layout,
+        dst: &Image,
+        dst_layout: image::Layout,
+        regions: T,
+    ) where
+        T: IntoIterator,
+        T::Item: Borrow<ImageCopy>,
+    {
+        self.push(Command::CopyImage(
+            (src.id, src_layout),
+            (dst.id, dst_layout),
+        ));
+        let regions = regions
+            .into_iter()
+            .map(|region| region.borrow().clone())
+            .collect::<Vec<_>>();
+        let texel = src.texel();
+        let (src_data, src_extent) = (src.data.clone(), src.kind.extent());
+        let (dst_data, dst_extent) = (dst.data.clone(), dst.kind.extent());
+        self.ops.push(Arc::new(move || {
+            for region in &regions {
+                let data = read_texels(
+                    &src_data.lock().unwrap(),
+                    src_extent,
+                    texel,
+                    region.src_offset,
+                    region.extent,
+                );
+                write_texels(
+                    &mut dst_data.lock().unwrap(),
+                    dst_extent,
+                    texel,
+                    region.dst_offset,
+                    region.extent,
+                    &data,
+                );
+            }
+        }));
+    }
+
+    fn copy_buffer_to_image<T>(
+        &mut self,
+        src: &Buffer,
+        dst: &Image,
+        dst_layout: image::Layout,
+        regions: T,
+    ) where
+        T: IntoIterator,
+        T::Item: Borrow<BufferImageCopy>,
+    {
+        self.push(Command::CopyBufferToImage(src.id, (dst.id, dst_layout)));
+        let regions = regions
+            .into_iter()
+            .map(|region| region.borrow().clone())
+            .collect::<Vec<_>>();
+        let texel = dst.texel();
+        let (memory, offset) = (src.memory.clone(), src.offset);
+        let (data, extent) = (dst.data.clone(), dst.kind.extent());
+        self.ops.push(Arc::new(move || {
+            for region in &regions {
+                let texels = read_texels(
+                    &memory.lock().unwrap()[(offset + region.buffer_offset) as usize..],
+                    buffer_extent(region),
+                    texel,
+                    image::Offset::ZERO,
+                    region.image_extent,
+                );
+                write_texels(
+                    &mut data.lock().unwrap(),
+                    extent,
+                    texel,
+                    region.image_offset,
+                    region.image_extent,
+                    &texels,
+                );
+            }
+        }));
+    }
+
+    fn copy_image_to_buffer<T>(
+        &mut self,
+        src: &Image,
+        src_layout: image::Layout,
+        dst: &Buffer,
+        regions: T,
+    ) where
+        T: IntoIterator,
+        T::Item: Borrow<BufferImageCopy>,
+    {
+        self.push(Command::CopyImageToBuffer((src.id, src_layout), dst.id));
+        let regions = regions
+            .into_iter()
+            .map(|region| region.borrow().clone())
+            .collect::<Vec<_>>();
+        let texel = src.texel();
+        let (data, extent) = (src.data.clone(), src.kind.extent());
+        let (memory, offset) = (dst.memory.clone(), dst.offset);
+        self.ops.push(Arc::new(move || {
+            for region in &regions {
+                let texels = read_texels(
+                    &data.lock().unwrap(),
+                    extent,
+                    texel,
+                    region.image_offset,
+                    region.image_extent,
+                );
+                write_texels(
+                    &mut memory.lock().unwrap()[(offset + region.buffer_offset) as usize..],
+                    buffer_extent(region),
+                    texel,
+                    image::Offset::ZERO,
+                    region.image_extent,
+                    &texels,
+                );
+            }
+        }));
+    }
+
+    fn draw(&mut self, _: Range<VertexCount>, _: Range<InstanceCount>) {
+        self.push(Command::Other("draw"));
+    }
+
+    fn draw_indexed(&mut self, _: Range<IndexCount>, _: VertexOffset, _: Range<InstanceCount>) {
+        self.push(Command::Other("draw_indexed"));
+    }
+
+    fn draw_indirect(&mut self, _: &Buffer, _: buffer::Offset, _: DrawCount, _: u32) {
+        self.push(Command::Other("draw_indirect"));
+    }
+
+    fn draw_indexed_indirect(&mut self, _: &Buffer, _: buffer::Offset, _: DrawCount, _: u32) {
+        self.push(Command::Other("draw_indexed_indirect"));
+    }
+
+    fn set_event(&mut self, event: &Handle, stages: PipelineStage) {
+        self.push(Command::SetEvent(event.id, stages));
+    }
+
+    fn reset_event(&mut self, event: &Handle, stages: PipelineStage) {
+        self.push(Command::ResetEvent(event.id, stages));
+    }
+
+    fn wait_events<'a, I, J>(&mut self, events: I, stages: Range<PipelineStage>, barriers: J)
+    where
+        I: IntoIterator,
+        I::Item: Borrow<Handle>,
+        J: IntoIterator,
+        J::Item: Borrow<Barrier<'a, Backend>>,
+    {
+        let events = events.into_iter().map(|event| event.borrow().id).collect();
+        self.push(Command::WaitEvents(
+            events,
+            PipelineBarrier::new(stages, barriers),
+        ));
+    }
+
+    fn begin_query(&mut self, _: Query<Backend>, _: QueryControl) {
+        self.push(Command::Other("begin_query"));
+    }
+
+    fn end_query(&mut self, _: Query<Backend>) {
+        self.push(Command::Other("end_query"));
+    }
+
+    fn reset_query_pool(&mut self, _: &Handle, _: Range<QueryId>) {
+        self.push(Command::Other("reset_query_pool"));
+    }
+
+    fn write_timestamp(&mut self, stage: PipelineStage, _: Query<Backend>) {
+        self.push(Command::WriteTimestamp(stage));
+    }
+
+    fn push_graphics_constants(&mut self, _: &Handle, _: ShaderStageFlags, _: u32, _: &[u32]) {
+        self.push(Command::Other("push_graphics_constants"));
+    }
+
+    fn push_compute_constants(&mut self, _: &Handle, _: u32, _: &[u32]) {
+        self.push(Command::Other("push_compute_constants"));
+    }
+
+    fn execute_commands<I>(&mut self, buffers: I)
+    where
+        I: IntoIterator,
+        I::Item: Borrow<CommandBuffer>,
+    {
+        let mut ids = Vec::new();
+        for buffer in buffers {
+            let buffer = buffer.borrow();
+            ids.push(buffer.id);
+            self.ops.extend(buffer.ops.iter().cloned());
+        }
+        self.push(Command::ExecuteCommands(ids));
+    }
+}
+
+/// Size of the first level and layer of the image in bytes.
+fn image_size(kind: image::Kind, format: format::Format) -> u64 {
+    let extent = kind.extent();
+    let texel = format.surface_desc().bits as u64 / 8;
+    extent.width as u64 * extent.height as u64 * extent.depth as u64 * texel
+}
+
+/// Extent of the image data in the buffer.
+fn buffer_extent(region: &BufferImageCopy) -> image::Extent {
+    image::Extent {
+        width: if region.buffer_width == 0 {
+            region.image_extent.width
+        } else {
+            region.buffer_width
+        },
+        height: if region.buffer_height == 0 {
+            region.image_extent.height
+        } else {
+            region.buffer_height
+        },
+        depth: region.image_extent.depth,
+    }
+}
+
+/// Read box of texels from tightly packed data of the extent.
+fn read_texels(
+    data: &[u8],
+    extent: image::Extent,
+    texel: usize,
+    offset: image::Offset,
+    size: image::Extent,
+) -> Vec<u8> {
+    let row = size.width as usize * texel;
+    let mut texels = Vec::with_capacity(row * (size.height * size.depth) as usize);
+    for z in 0..size.depth as usize {
+        for y in 0..size.height as usize {
+            let start = texel_index(extent, offset, y, z) * texel;
+            texels.extend_from_slice(&data[start..start + row]);
+        }
+    }
+    texels
+}
+
+/// Write box of texels into tightly packed data of the extent.
+fn write_texels(
+    data: &mut [u8],
+    extent: image::Extent,
+    texel: usize,
+    offset: image::Offset,
+    size: image::Extent,
+    texels: &[u8],
+) {
+    let row = size.width as usize * texel;
+    for (index, chunk) in texels.chunks(row).enumerate() {
+        let (y, z) = (index % size.height as usize, index / size.height as usize);
+        let start = texel_index(extent, offset, y, z) * texel;
+        data[start..start + row].copy_from_slice(chunk);
+    }
+}
+
+fn texel_index(extent: image::Extent, offset: image::Offset, y: usize, z: usize) -> usize {
+    let (width, height) = (extent.width as usize, extent.height as usize);
+    ((offset.z as usize + z) * height + offset.y as usize + y) * width + offset.x as usize
+}
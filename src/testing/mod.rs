@@ -0,0 +1,10 @@
+//! Utilities for testing graphs.
+//!
+//! See `mock` module for backend that logs every call including resources, recorded barriers,
+//! submissions with semaphores and presents. Build the graph with the mock `Device`
+//! and inspect what `Graph::run` submitted.
+//!
+//! See `golden` module for comparing graph outputs against reference images.
+
+pub mod golden;
+pub mod mock;
@@ -23,7 +23,74 @@ pub struct BufferId(pub(crate) Id<Buffer>);
 #[derive(Clone, Copy, Debug, PartialOrd, PartialEq, Ord, Eq, Hash)]
 pub struct NodeId(pub(crate) PassId);
 
+/// Kind of access to storage image or buffer from shaders.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum StorageAccess {
+    /// Shaders only read the resource.
+    Read,
+
+    /// Shaders only write the resource.
+    Write,
+
+    /// Shaders both read and write the resource.
+    ReadWrite,
+}
+
+impl StorageAccess {
+    /// Check if shaders read the resource.
+    pub fn is_read(self) -> bool {
+        self != StorageAccess::Write
+    }
+
+    /// Check if shaders write the resource.
+    pub fn is_write(self) -> bool {
+        self != StorageAccess::Read
+    }
+
+    /// Image access flags.
+    pub fn image_access(self) -> image::Access {
+        let mut access = image::Access::empty();
+        if self.is_read() {
+            access |= image::Access::SHADER_READ;
+        }
+        if self.is_write() {
+            access |= image::Access::SHADER_WRITE;
+        }
+        access
+    }
+
+    /// Buffer access flags.
+    pub fn buffer_access(self) -> buffer::Access {
+        let mut access = buffer::Access::empty();
+        if self.is_read() {
+            access |= buffer::Access::SHADER_READ;
+        }
+        if self.is_write() {
+            access |= buffer::Access::SHADER_WRITE;
+        }
+        access
+    }
+
+    /// Usage, state and stages of the storage buffer accessed by shaders at specified stages.
+    /// Suitable as an item of `NodeDesc::Buffers`.
+    pub fn buffer(self, stages: PipelineStage) -> (buffer::Usage, buffer::State, PipelineStage) {
+        (buffer::Usage::STORAGE, self.buffer_access(), stages)
+    }
+
+    /// Usage, state and stages of the storage image accessed by shaders at specified stages.
+    /// Storage images are always in `General` layout.
+    /// Suitable as an item of `NodeDesc::Images`.
+    pub fn image(self, stages: PipelineStage) -> (image::Usage, image::State, PipelineStage) {
+        (
+            image::Usage::STORAGE,
+            (self.image_access(), image::Layout::General),
+            stages,
+        )
+    }
+}
+
 /// Set of barriers for the node to execute.
+#[derive(Clone, Debug, PartialEq)]
 pub struct Barriers<S> {
     /// Buffer barriers.
     pub acquire: Option<Range<(S, PipelineStage)>>,
@@ -76,7 +143,68 @@ pub struct ImageInfo<'a, I: 'a> {
     pub clear: Option<ClearValue>,
 
     /// The image.
+    /// First swapchain image if the image is presented directly.
     pub image: &'a I,
+
+    /// Swapchain images if the image is presented directly
+    /// or both images of the pair if it is a history image.
+    /// See `PresentBuilder::with_direct` and `GraphBuilder::create_history_image`.
+    pub frames: Option<Frames<'a, I>>,
+}
+
+impl<'a, I> ImageInfo<'a, I> {
+    /// Number of frames the image changes between.
+    /// `1` unless the image is presented directly or is a history image.
+    pub fn frames(&self) -> usize {
+        self.frames.as_ref().map_or(1, |frames| frames.images.len())
+    }
+
+    /// The image used for the frame.
+    pub fn frame_image(&self, frame: usize) -> &'a I {
+        self.frames
+            .as_ref()
+            .map_or(self.image, |frames| &frames.images[frame])
+    }
+}
+
+/// Swapchain images of the directly presented image
+/// or images of the history pair in the order they are used in.
+pub struct Frames<'a, I: 'a> {
+    /// Swapchain images or history images.
+    pub images: &'a [I],
+
+    /// Index of the image used for current frame.
+    /// Updated before nodes run.
+    pub index: Arc<AtomicUsize>,
+}
+
+/// Pairs of history images swapped after each `Graph::run`.
+/// See `GraphBuilder::create_history_image`.
+pub struct HistoryPairs {
+    /// Ids of the image for the current frame and the image for the previous frame.
+    pub pairs: Vec<(Id<Image>, Id<Image>)>,
+
+    /// `1` if images of every pair are swapped for current frame.
+    /// Updated before nodes run.
+    pub parity: Arc<AtomicUsize>,
+}
+
+impl HistoryPairs {
+    /// Other image of the pair if the image is a history image.
+    pub fn partner(&self, id: Id<Image>) -> Option<Id<Image>> {
+        self.pairs
+            .iter()
+            .filter_map(|&(current, previous)| {
+                if current == id {
+                    Some(previous)
+                } else if previous == id {
+                    Some(current)
+                } else {
+                    None
+                }
+            })
+            .next()
+    }
 }
 
 pub struct ImageResource<I> {
@@ -92,3 +220,90 @@ pub struct ImageResource<I> {
     /// The image.
     pub image: I,
 }
+
+/// Swapchain images the graph image is rendered to directly.
+/// See `PresentBuilder::with_direct`.
+pub struct BackbufferResource<B: Backend> {
+    /// Kind of the images.
+    pub kind: image::Kind,
+
+    /// Format of the images.
+    pub format: Format,
+
+    /// Clear value if node is expected to clear the image before use.
+    pub clear: Option<ClearValue>,
+
+    /// Swapchain images. Owned by the swapchain.
+    pub images: Vec<B::Image>,
+
+    /// Index of the swapchain image acquired for current frame.
+    pub index: Arc<AtomicUsize>,
+}
+
+/// Image owned by graph or swapchain images of the direct present.
+pub type GraphImage<B, I> = Either<ImageResource<I>, BackbufferResource<B>>;
+
+/// Graph resources as seen by nodes during `Graph::run`.
+/// Swapchain images of direct presents are resolved to the image acquired for the frame.
+pub struct FrameInfo<'a, B: Backend> {
+    slot: usize,
+    buffers: &'a [&'a B::Buffer],
+    images: &'a [&'a B::Image],
+    before: &'a [&'a B::CommandBuffer],
+    after: &'a [&'a B::CommandBuffer],
+}
+
+impl<'a, B> FrameInfo<'a, B>
+where
+    B: Backend,
+{
+    pub(crate) fn new(
+        slot: usize,
+        buffers: &'a [&'a B::Buffer],
+        images: &'a [&'a B::Image],
+        before: &'a [&'a B::CommandBuffer],
+        after: &'a [&'a B::CommandBuffer],
+    ) -> Self {
+        FrameInfo {
+            slot,
+            buffers,
+            images,
+            before,
+            after,
+        }
+    }
+
+    /// Command buffers of the node surrounded by ones the graph records for it,
+    /// e.g. waits and sets of events for split barriers.
+    /// Nodes must submit all of them in this order within their submission.
+    pub fn command_buffers<'b, I>(&self, cbufs: I) -> impl Iterator<Item = &'b B::CommandBuffer>
+    where
+        'a: 'b,
+        I: IntoIterator<Item = &'b B::CommandBuffer>,
+    {
+        let before: &'b [&'b B::CommandBuffer] = self.before;
+        let after: &'b [&'b B::CommandBuffer] = self.after;
+        before
+            .iter()
+            .cloned()
+            .chain(cbufs)
+            .chain(after.iter().cloned())
+    }
+
+    /// Index of the frame in flight in `0 .. frames`.
+    /// Resources used with the same slot last time are no longer used by the device.
+    /// See `GraphBuilder::with_frames_in_flight`.
+    pub fn slot(&self) -> usize {
+        self.slot
+    }
+
+    /// Graph buffer with the id.
+    pub fn buffer(&self, id: BufferId) -> &'a B::Buffer {
+        self.buffers[id.0.index() as usize]
+    }
+
+    /// Graph image with the id.
+    pub fn image(&self, id: ImageId) -> &'a B::Image {
+        self.images[id.0.index() as usize]
+    }
+}
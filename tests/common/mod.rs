@@ -0,0 +1,397 @@
+//! Nodes shared by integration tests.
+
+#![allow(dead_code)]
+
+use std::{borrow::Borrow, iter::{empty, once, Empty}, ops::Index};
+
+use hal::{
+    buffer,
+    command::{
+        ClearColorRaw, ClearDepthStencilRaw, CommandBufferFlags,
+        DescriptorSetOffset, Primary, RawCommandBuffer, RawLevel, RenderPassInlineEncoder,
+    },
+    format::Format, image, pool::{CommandPool, CommandPoolCreateFlags, RawCommandPool},
+    pso::{
+        DescriptorSetLayoutBinding, DescriptorType, EntryPoint, GraphicsShaderSet, PipelineStage,
+        ShaderStageFlags,
+    },
+    queue::{CommandQueue, Graphics, RawCommandQueue, RawSubmission}, Backend, Device,
+};
+
+use smallvec::SmallVec;
+
+use xfg::{
+    barriers,
+    render::{ImageBinding, Layout, PrepareBuffer, RenderPass, RenderPassDesc, SetLayout},
+    testing::mock, BufferInfo, Descriptors, FrameInfo, ImageInfo, Node, NodeDesc,
+};
+
+#[cfg(feature = "validation")]
+use xfg::validate::{Hazard, Validator};
+
+/// Buffer callback for `GraphBuilder::build` with the mock backend.
+pub fn create_buffer(
+    size: u64,
+    usage: buffer::Usage,
+    device: &mut mock::Device,
+    _: &mut (),
+) -> mock::Buffer {
+    device.buffer(size, usage)
+}
+
+/// Image callback for `GraphBuilder::build` with the mock backend.
+pub fn create_image(
+    kind: image::Kind,
+    format: Format,
+    usage: image::Usage,
+    device: &mut mock::Device,
+    _: &mut (),
+) -> mock::Image {
+    device.image(kind, format, usage)
+}
+
+/// Color `ClearNode` clears the image with.
+pub const CLEAR_COLOR: [f32; 4] = [0.2, 0.4, 0.8, 1.0];
+
+/// Clears the image with `CLEAR_COLOR`.
+/// The clear is recorded through `Validator` with `validation` feature.
+pub struct ClearNode<B: Backend> {
+    pool: B::CommandPool,
+    cbuf: B::CommandBuffer,
+
+    #[cfg(feature = "validation")]
+    validator: Validator<B>,
+}
+
+impl<B> NodeDesc for ClearNode<B>
+where
+    B: Backend,
+{
+    type Buffers = Empty<(buffer::Usage, buffer::State, PipelineStage)>;
+    type Images = Option<(image::Usage, image::State, PipelineStage)>;
+    type Capability = Graphics;
+
+    fn name() -> &'static str {
+        "Clear"
+    }
+
+    fn buffers() -> Self::Buffers {
+        Default::default()
+    }
+
+    fn images() -> Self::Images {
+        Some((
+            image::Usage::TRANSFER_DST,
+            (image::Access::TRANSFER_WRITE, image::Layout::TransferDstOptimal),
+            PipelineStage::TRANSFER,
+        ))
+    }
+}
+
+impl<B, D, T> Node<B, D, T> for ClearNode<B>
+where
+    B: Backend,
+    D: Device<B>,
+{
+    fn build<F, U, I>(
+        buffers: Vec<BufferInfo<U>>,
+        images: Vec<ImageInfo<I>>,
+        mut pools: F,
+        device: &mut D,
+        _aux: &mut T,
+    ) -> Self
+    where
+        F: FnMut(&mut D, CommandPoolCreateFlags) -> CommandPool<B, Graphics>,
+        U: Borrow<B::Buffer>,
+        I: Borrow<B::Image>,
+    {
+        let ref info = images[0];
+        let range = image::SubresourceRange {
+            aspects: info.format.surface_desc().aspects,
+            levels: 0..1,
+            layers: 0..1,
+        };
+
+        #[cfg(feature = "validation")]
+        let mut validator = Validator::for_node::<Self, _, _>(&buffers, &images);
+
+        let mut pool = pools(device, CommandPoolCreateFlags::empty()).into_raw();
+        let mut cbuf = pool.allocate(1, RawLevel::Primary).pop().unwrap();
+        cbuf.begin(CommandBufferFlags::EMPTY, Default::default());
+        barriers::record_barriers::<B, _, _, _>(&mut cbuf, &buffers, &images, 0, false);
+        {
+            #[cfg(feature = "validation")]
+            let mut cbuf = validator.record(&mut cbuf);
+            cbuf.clear_image(
+                info.image.borrow(),
+                image::Layout::TransferDstOptimal,
+                ClearColorRaw {
+                    float32: CLEAR_COLOR,
+                },
+                ClearDepthStencilRaw {
+                    depth: 0.0,
+                    stencil: 0,
+                },
+                Some(range),
+            );
+            #[cfg(feature = "validation")]
+            cbuf.finish();
+        }
+        barriers::record_barriers::<B, _, _, _>(&mut cbuf, &buffers, &images, 0, true);
+        cbuf.finish();
+
+        ClearNode {
+            pool,
+            cbuf,
+            #[cfg(feature = "validation")]
+            validator,
+        }
+    }
+
+    fn run<'a, W, S>(
+        &'a mut self,
+        frame: &FrameInfo<B>,
+        wait: W,
+        queue: &mut CommandQueue<B, Graphics>,
+        signal: S,
+        fence: Option<&B::Fence>,
+        _device: &mut D,
+        _aux: &'a T,
+    ) where
+        W: IntoIterator<Item = (&'a B::Semaphore, PipelineStage)>,
+        S: IntoIterator<Item = &'a B::Semaphore>,
+    {
+        unsafe {
+            queue.as_raw_mut().submit_raw(
+                RawSubmission {
+                    wait_semaphores: &wait.into_iter().collect::<SmallVec<[_; 16]>>(),
+                    signal_semaphores: &signal.into_iter().collect::<SmallVec<[_; 16]>>(),
+                    cmd_buffers: frame.command_buffers(once(&self.cbuf)),
+                },
+                fence,
+            );
+        }
+    }
+
+    #[cfg(feature = "validation")]
+    fn hazards(&self) -> &[Hazard] {
+        self.validator.hazards()
+    }
+
+    fn dispose(mut self, device: &mut D, _aux: &mut T) {
+        self.pool.free(vec![self.cbuf]);
+        device.destroy_command_pool(self.pool);
+    }
+}
+
+/// Samples one image in the fragment shader and renders into one color attachment.
+pub struct SamplePass;
+
+impl<B> RenderPassDesc<B> for SamplePass
+where
+    B: Backend,
+{
+    fn name() -> &'static str {
+        "Sample"
+    }
+
+    fn sampled() -> usize {
+        1
+    }
+
+    fn sampled_bindings() -> Vec<(ImageBinding, image::SamplerInfo)> {
+        vec![(
+            ImageBinding {
+                layout: 0,
+                set: 0,
+                binding: 0,
+            },
+            image::SamplerInfo::new(image::Filter::Linear, image::WrapMode::Clamp),
+        )]
+    }
+
+    fn colors() -> usize {
+        1
+    }
+
+    fn layouts() -> Vec<Layout> {
+        vec![Layout {
+            sets: vec![SetLayout {
+                bindings: vec![DescriptorSetLayoutBinding {
+                    binding: 0,
+                    ty: DescriptorType::CombinedImageSampler,
+                    count: 1,
+                    stage_flags: ShaderStageFlags::FRAGMENT,
+                }],
+            }],
+            push_constants: Vec::new(),
+        }]
+    }
+}
+
+impl<B, D, T> RenderPass<B, D, T> for SamplePass
+where
+    B: Backend,
+    D: Device<B>,
+{
+    fn load_shader_sets<'a>(
+        storage: &'a mut Vec<B::ShaderModule>,
+        device: &mut D,
+        _aux: &mut T,
+    ) -> Vec<GraphicsShaderSet<'a, B>> {
+        let offset = storage.len();
+        storage.push(device.create_shader_module(&[]).unwrap());
+        storage.push(device.create_shader_module(&[]).unwrap());
+
+        vec![GraphicsShaderSet {
+            vertex: EntryPoint {
+                entry: "main",
+                module: &storage[offset + 0],
+                specialization: &[],
+            },
+            hull: None,
+            domain: None,
+            geometry: None,
+            fragment: Some(EntryPoint {
+                entry: "main",
+                module: &storage[offset + 1],
+                specialization: &[],
+            }),
+        }]
+    }
+
+    fn build<I>(_sampled: I, _storage: I, _device: &mut D, _aux: &mut T) -> Self
+    where
+        I: IntoIterator,
+        I::Item: Borrow<B::ImageView>,
+    {
+        SamplePass
+    }
+
+    fn prepare(
+        &mut self,
+        _sets: &mut Descriptors<B>,
+        _buffers: &[&B::Buffer],
+        _cbuf: &mut PrepareBuffer<B>,
+        _device: &mut D,
+        _aux: &T,
+    ) {
+    }
+
+    fn draw<L, P>(
+        &mut self,
+        sets: &Descriptors<B>,
+        _buffers: &[&B::Buffer],
+        layouts: &L,
+        pipelines: &P,
+        mut encoder: RenderPassInlineEncoder<B, Primary>,
+        _aux: &T,
+    ) where
+        L: Index<usize>,
+        L::Output: Borrow<B::PipelineLayout>,
+        P: Index<usize>,
+        P::Output: Borrow<B::GraphicsPipeline>,
+    {
+        encoder.bind_graphics_pipeline(pipelines[0].borrow());
+        encoder.bind_graphics_descriptor_sets(
+            layouts[0].borrow(),
+            0,
+            sets.bound(0, 0),
+            empty::<DescriptorSetOffset>(),
+        );
+        encoder.draw(0..3, 0..1);
+    }
+
+    fn dispose(self, _device: &mut D, _aux: &mut T) {}
+}
+
+/// Same as `SamplePass` but with graph barriers recorded inline.
+pub struct InlineSamplePass;
+
+impl<B> RenderPassDesc<B> for InlineSamplePass
+where
+    B: Backend,
+{
+    fn name() -> &'static str {
+        "InlineSample"
+    }
+
+    fn sampled() -> usize {
+        1
+    }
+
+    fn sampled_bindings() -> Vec<(ImageBinding, image::SamplerInfo)> {
+        <SamplePass as RenderPassDesc<B>>::sampled_bindings()
+    }
+
+    fn colors() -> usize {
+        1
+    }
+
+    fn inline_barriers() -> bool {
+        true
+    }
+
+    fn layouts() -> Vec<Layout> {
+        <SamplePass as RenderPassDesc<B>>::layouts()
+    }
+}
+
+impl<B, D, T> RenderPass<B, D, T> for InlineSamplePass
+where
+    B: Backend,
+    D: Device<B>,
+{
+    fn load_shader_sets<'a>(
+        storage: &'a mut Vec<B::ShaderModule>,
+        device: &mut D,
+        aux: &mut T,
+    ) -> Vec<GraphicsShaderSet<'a, B>> {
+        <SamplePass as RenderPass<B, D, T>>::load_shader_sets(storage, device, aux)
+    }
+
+    fn build<I>(_sampled: I, _storage: I, _device: &mut D, _aux: &mut T) -> Self
+    where
+        I: IntoIterator,
+        I::Item: Borrow<B::ImageView>,
+    {
+        InlineSamplePass
+    }
+
+    fn prepare(
+        &mut self,
+        _sets: &mut Descriptors<B>,
+        _buffers: &[&B::Buffer],
+        _cbuf: &mut PrepareBuffer<B>,
+        _device: &mut D,
+        _aux: &T,
+    ) {
+    }
+
+    fn draw<L, P>(
+        &mut self,
+        sets: &Descriptors<B>,
+        buffers: &[&B::Buffer],
+        layouts: &L,
+        pipelines: &P,
+        encoder: RenderPassInlineEncoder<B, Primary>,
+        aux: &T,
+    ) where
+        L: Index<usize>,
+        L::Output: Borrow<B::PipelineLayout>,
+        P: Index<usize>,
+        P::Output: Borrow<B::GraphicsPipeline>,
+    {
+        <SamplePass as RenderPass<B, D, T>>::draw(
+            &mut SamplePass,
+            sets,
+            buffers,
+            layouts,
+            pipelines,
+            encoder,
+            aux,
+        );
+    }
+
+    fn dispose(self, _device: &mut D, _aux: &mut T) {}
+}
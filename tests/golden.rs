@@ -0,0 +1,174 @@
+#![cfg(feature = "testing")]
+
+extern crate xfg;
+
+use std::{env, fs::{self, File}, io::Write, path::PathBuf};
+
+use xfg::testing::golden::{check, difference, GoldenError, Picture, Tolerance, UPDATE_VAR};
+
+/// Directory for files written by the test. Removed beforehand if exists.
+fn scratch(name: &str) -> PathBuf {
+    let dir = env::temp_dir().join(format!("xfg-golden-{}-{}", name, ::std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+fn picture(width: u32, height: u32, value: u8) -> Picture {
+    Picture {
+        width,
+        height,
+        data: vec![value; (width * height * 3) as usize],
+    }
+}
+
+#[test]
+fn ppm_header_with_comments_is_parsed() {
+    let dir = scratch("header");
+    let path = dir.join("comments.ppm");
+    let mut file = File::create(&path).unwrap();
+    file.write_all(b"P6\n# comment\n2 # width\n1\n255\n").unwrap();
+    file.write_all(&[1, 2, 3, 4, 5, 6]).unwrap();
+    drop(file);
+
+    let picture = Picture::read_ppm(&path).unwrap();
+    assert_eq!((picture.width, picture.height), (2, 1));
+    assert_eq!(picture.data, vec![1, 2, 3, 4, 5, 6]);
+
+    // Written picture is read back unchanged.
+    let path = dir.join("roundtrip.ppm");
+    picture.write_ppm(&path).unwrap();
+    assert_eq!(Picture::read_ppm(&path).unwrap(), picture);
+
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn trailing_ppm_bytes_are_ignored() {
+    let dir = scratch("trailing");
+    let path = dir.join("trailing.ppm");
+    let mut file = File::create(&path).unwrap();
+    file.write_all(b"P6\n1 1\n255\n").unwrap();
+    file.write_all(&[1, 2, 3, 4, 5]).unwrap();
+    drop(file);
+
+    let picture = Picture::read_ppm(&path).unwrap();
+    assert_eq!(picture.data, vec![1, 2, 3]);
+    assert_eq!((picture.width, picture.height), (1, 1));
+
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn png_is_written_and_read_back() {
+    let dir = scratch("png");
+    let path = dir.join("roundtrip.png");
+    let mut picture = picture(3, 2, 20);
+    picture.data[7] = 200;
+    picture.write_png(&path).unwrap();
+    assert_eq!(Picture::read_png(&path).unwrap(), picture);
+
+    // Format is picked by the extension.
+    assert_eq!(Picture::read(&path).unwrap(), picture);
+    assert!(Picture::read_ppm(&path).is_err());
+
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn malformed_ppm_is_rejected() {
+    let dir = scratch("malformed");
+    let cases: &[(&str, &[u8])] = &[
+        ("ascii", b"P3\n1 1\n255\n0 0 0\n"),
+        ("header", b"P6\n1 x\n255\n\0\0\0"),
+        ("depth", b"P6\n1 1\n65535\n\0\0\0\0\0\0"),
+        ("truncated", b"P6\n2 2\n255\n\0\0\0"),
+        ("overflow", b"P6\n4294967295 4294967295\n255\n\0\0\0"),
+    ];
+    for &(name, bytes) in cases {
+        let path = dir.join(name).with_extension("ppm");
+        File::create(&path).unwrap().write_all(bytes).unwrap();
+        match Picture::read_ppm(&path) {
+            Err(GoldenError::Format(_)) => {}
+            other => panic!("{}: {:?}", name, other),
+        }
+    }
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn difference_is_compared_with_tolerance() {
+    let expected = picture(4, 3, 100);
+    let mut actual = expected.clone();
+    assert_eq!(difference(&actual, &expected).max, 0);
+    assert!(difference(&actual, &expected).within(&Tolerance::exact()));
+
+    // One channel out of 36 is off by 18.
+    actual.data[0] = 118;
+    let diff = difference(&actual, &expected);
+    assert_eq!(diff.max, 18);
+    assert!((diff.rmse - 3.0).abs() < 1e-9);
+
+    assert!(!diff.within(&Tolerance::exact()));
+    assert!(diff.within(&Tolerance { max: 18, rmse: 3.0 }));
+    assert!(!diff.within(&Tolerance { max: 17, rmse: 3.0 }));
+    assert!(!diff.within(&Tolerance { max: 18, rmse: 2.9 }));
+
+    // Difference is symmetric.
+    assert_eq!(difference(&expected, &actual), diff);
+}
+
+#[test]
+fn check_writes_diff_on_mismatch() {
+    let dir = scratch("check");
+    let path = dir.join("reference.ppm");
+    let expected = picture(2, 1, 10);
+    expected.write_ppm(&path).unwrap();
+
+    // Matching image leaves no files behind.
+    assert!(check(&expected, &path, &Tolerance::exact()).is_ok());
+    assert!(!dir.join("reference.diff.ppm").exists());
+
+    let mut actual = expected.clone();
+    actual.data[4] = 50;
+    match check(&actual, &path, &Tolerance::exact()) {
+        Err(GoldenError::Mismatch { difference, diff }) => {
+            assert_eq!(difference.max, 40);
+            assert_eq!(diff, dir.join("reference.diff.ppm"));
+            let diff = Picture::read_ppm(&diff).unwrap();
+            assert_eq!(diff.data, vec![0, 0, 0, 0, 40, 0]);
+        }
+        other => panic!("{:?}", other),
+    }
+    assert_eq!(
+        Picture::read_ppm(dir.join("reference.actual.ppm")).unwrap(),
+        actual
+    );
+
+    match check(&picture(1, 1, 10), &path, &Tolerance::exact()) {
+        Err(GoldenError::Extent { actual, expected }) => {
+            assert_eq!((actual, expected), ((1, 1), (2, 1)));
+        }
+        other => panic!("{:?}", other),
+    }
+
+    // PNG reference gets PNG diff.
+    let png = dir.join("reference.png");
+    expected.write_png(&png).unwrap();
+    match check(&actual, &png, &Tolerance::exact()) {
+        Err(GoldenError::Mismatch { diff, .. }) => {
+            assert_eq!(diff, dir.join("reference.diff.png"));
+            assert_eq!(Picture::read_png(&diff).unwrap().data, vec![0, 0, 0, 0, 40, 0]);
+        }
+        other => panic!("{:?}", other),
+    }
+
+    if env::var_os(UPDATE_VAR).is_none() {
+        match check(&actual, dir.join("missing.ppm"), &Tolerance::exact()) {
+            Err(GoldenError::Missing(missing)) => assert_eq!(missing, dir.join("missing.ppm")),
+            other => panic!("{:?}", other),
+        }
+    }
+
+    fs::remove_dir_all(dir).unwrap();
+}
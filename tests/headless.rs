@@ -0,0 +1,39 @@
+//! Headless graphs on `gfx-backend-empty`.
+//! Its device is unimplemented so only a graph without nodes can run here.
+//! Nodes with readback are run end to end on the mock backend in `tests/readback.rs`.
+
+extern crate gfx_backend_empty as backend;
+extern crate gfx_hal as hal;
+extern crate xfg;
+
+use std::{collections::HashMap, iter::empty};
+
+use hal::Backend;
+
+use xfg::GraphBuilder;
+
+type Back = backend::Backend;
+type Buffer = <Back as Backend>::Buffer;
+type Image = <Back as Backend>::Image;
+
+#[test]
+fn headless_graph_runs_without_presents() {
+    let mut device = backend::Device;
+    let builder = GraphBuilder::<Back, backend::Device, (), Buffer, Image>::new();
+
+    let mut graph = builder.build_headless(
+        empty::<backend::QueueFamily>(),
+        |_, _, _, _| unreachable!("No buffers were created"),
+        |_, _, _, _, _| unreachable!("No images were created"),
+        &mut device,
+        &mut (),
+    );
+
+    let mut fences = Vec::new();
+    let status = graph.run(&mut HashMap::new(), &mut device, &mut (), &mut fences);
+    assert!(status.is_ok());
+    assert_eq!(status.fences, 0);
+    assert!(fences.is_empty());
+
+    graph.dispose(&mut device, &mut ());
+}
@@ -0,0 +1,64 @@
+#![cfg(feature = "testing")]
+
+//! Headless graphs with nodes run end to end on the mock backend.
+//! `gfx-backend-empty` can't run nodes. See `tests/headless.rs`.
+
+extern crate gfx_hal as hal;
+extern crate smallvec;
+extern crate xfg;
+
+mod common;
+
+use hal::{format::Format, image, Device};
+
+use xfg::{readback::ReadbackBuilder, testing::mock, GraphBuilder};
+
+use common::{create_buffer, create_image, ClearNode, CLEAR_COLOR};
+
+type Back = mock::Backend;
+type Builder = GraphBuilder<Back, mock::Device, (), mock::Buffer, mock::Image>;
+
+#[test]
+fn headless_graph_reads_back_cleared_image() {
+    const FRAMES: u64 = 3;
+
+    let families = mock::families();
+    let mut device = mock::Device::new();
+    let mut queues = device.queues(&families);
+
+    let mut builder = Builder::new();
+    let target = builder.create_image(image::Kind::D2(8, 4, 1, 1), Format::Rgba8Unorm, None);
+    builder.add_node(ClearNode::<Back>::builder().with_image(target));
+    let readback =
+        builder.add_readback(ReadbackBuilder::image(target, mock::memory_types()).with_frames(2));
+    let mut graph =
+        builder.build_headless(&families, create_buffer, create_image, &mut device, &mut ());
+
+    let expected = CLEAR_COLOR
+        .iter()
+        .map(|value| (value * 255.0).round() as u8)
+        .collect::<Vec<_>>();
+
+    let mut fences = Vec::new();
+    for frame in 0..FRAMES {
+        let status = graph.run(&mut queues, &mut device, &mut (), &mut fences);
+        assert!(status.is_ok());
+        for fence in &fences[..status.fences] {
+            device.wait_for_fence(fence, !0);
+        }
+
+        let readout = graph.readback(readback, &mut device).unwrap();
+        assert_eq!(readout.frame, frame);
+        assert_eq!(readout.format, Some(Format::Rgba8Unorm));
+        assert_eq!(readout.row_pitch, 8 * 4);
+        assert_eq!(readout.data.len(), 8 * 4 * 4);
+        for texel in readout.data.chunks(4) {
+            assert_eq!(texel, &expected[..]);
+        }
+    }
+
+    graph.dispose(&mut device, &mut ());
+    for fence in fences {
+        device.destroy_fence(fence);
+    }
+}
@@ -0,0 +1,1121 @@
+#![cfg(feature = "testing")]
+
+extern crate gfx_hal as hal;
+extern crate smallvec;
+extern crate xfg;
+
+mod common;
+
+use std::{
+    borrow::Borrow, ops::Index, sync::atomic::{AtomicBool, AtomicUsize, Ordering},
+};
+
+use hal::{
+    command::{Primary, RenderPassInlineEncoder}, format::Format, image,
+    pso::{Descriptor, DescriptorSetWrite, GraphicsShaderSet, PipelineStage},
+    queue::{QueueFamilyId, QueueType}, Backend, Device,
+};
+
+use xfg::{
+    present::PresentBuilder, readback::ReadbackBuilder,
+    render::{ImageBinding, Layout, PrepareBuffer, RenderPass, RenderPassDesc},
+    testing::mock::{self, Call, Command, Submission}, Descriptors, GraphBuilder,
+};
+
+use common::{create_buffer, create_image, ClearNode, InlineSamplePass, SamplePass};
+
+type Back = mock::Backend;
+type Builder = GraphBuilder<Back, mock::Device, (), mock::Buffer, mock::Image>;
+type Graph = xfg::Graph<Back, mock::Device, (), mock::Buffer, mock::Image>;
+
+static STATIC_PREPARED: AtomicUsize = AtomicUsize::new(0);
+static STATIC_DRAWN: AtomicUsize = AtomicUsize::new(0);
+static STATIC_DIRTY: AtomicBool = AtomicBool::new(false);
+
+/// Same as `SamplePass` but with static commands.
+/// Counts `prepare` and `draw` calls and reports changes once `STATIC_DIRTY` is set.
+struct StaticPass;
+
+impl<B> RenderPassDesc<B> for StaticPass
+where
+    B: Backend,
+{
+    fn name() -> &'static str {
+        "Static"
+    }
+
+    fn sampled() -> usize {
+        1
+    }
+
+    fn sampled_bindings() -> Vec<(ImageBinding, image::SamplerInfo)> {
+        <SamplePass as RenderPassDesc<B>>::sampled_bindings()
+    }
+
+    fn colors() -> usize {
+        1
+    }
+
+    fn static_commands() -> bool {
+        true
+    }
+
+    fn layouts() -> Vec<Layout> {
+        <SamplePass as RenderPassDesc<B>>::layouts()
+    }
+}
+
+impl<B, D, T> RenderPass<B, D, T> for StaticPass
+where
+    B: Backend,
+    D: Device<B>,
+{
+    fn load_shader_sets<'a>(
+        storage: &'a mut Vec<B::ShaderModule>,
+        device: &mut D,
+        aux: &mut T,
+    ) -> Vec<GraphicsShaderSet<'a, B>> {
+        <SamplePass as RenderPass<B, D, T>>::load_shader_sets(storage, device, aux)
+    }
+
+    fn build<I>(_sampled: I, _storage: I, _device: &mut D, _aux: &mut T) -> Self
+    where
+        I: IntoIterator,
+        I::Item: Borrow<B::ImageView>,
+    {
+        StaticPass
+    }
+
+    fn prepare(
+        &mut self,
+        _sets: &mut Descriptors<B>,
+        _buffers: &[&B::Buffer],
+        _cbuf: &mut PrepareBuffer<B>,
+        _device: &mut D,
+        _aux: &T,
+    ) {
+        STATIC_PREPARED.fetch_add(1, Ordering::SeqCst);
+    }
+
+    fn draw<L, P>(
+        &mut self,
+        sets: &Descriptors<B>,
+        buffers: &[&B::Buffer],
+        layouts: &L,
+        pipelines: &P,
+        encoder: RenderPassInlineEncoder<B, Primary>,
+        aux: &T,
+    ) where
+        L: Index<usize>,
+        L::Output: Borrow<B::PipelineLayout>,
+        P: Index<usize>,
+        P::Output: Borrow<B::GraphicsPipeline>,
+    {
+        STATIC_DRAWN.fetch_add(1, Ordering::SeqCst);
+        <SamplePass as RenderPass<B, D, T>>::draw(
+            &mut SamplePass,
+            sets,
+            buffers,
+            layouts,
+            pipelines,
+            encoder,
+            aux,
+        );
+    }
+
+    fn dirty(&self, _aux: &T) -> bool {
+        STATIC_DIRTY.swap(false, Ordering::SeqCst)
+    }
+
+    fn dispose(self, _device: &mut D, _aux: &mut T) {}
+}
+
+/// Same as `SamplePass` but allocates a new descriptor set in every `prepare`
+/// and frees the one allocated in the previous frame.
+/// Allocated sets are written with binding `7` to find them in the log.
+struct TransientPass<B: Backend> {
+    set: Option<B::DescriptorSet>,
+}
+
+impl<B> RenderPassDesc<B> for TransientPass<B>
+where
+    B: Backend,
+{
+    fn name() -> &'static str {
+        "Transient"
+    }
+
+    fn sampled() -> usize {
+        1
+    }
+
+    fn sampled_bindings() -> Vec<(ImageBinding, image::SamplerInfo)> {
+        <SamplePass as RenderPassDesc<B>>::sampled_bindings()
+    }
+
+    fn colors() -> usize {
+        1
+    }
+
+    fn layouts() -> Vec<Layout> {
+        <SamplePass as RenderPassDesc<B>>::layouts()
+    }
+}
+
+impl<B, D, T> RenderPass<B, D, T> for TransientPass<B>
+where
+    B: Backend,
+    D: Device<B>,
+{
+    fn load_shader_sets<'a>(
+        storage: &'a mut Vec<B::ShaderModule>,
+        device: &mut D,
+        aux: &mut T,
+    ) -> Vec<GraphicsShaderSet<'a, B>> {
+        <SamplePass as RenderPass<B, D, T>>::load_shader_sets(storage, device, aux)
+    }
+
+    fn build<I>(_sampled: I, _storage: I, _device: &mut D, _aux: &mut T) -> Self
+    where
+        I: IntoIterator,
+        I::Item: Borrow<B::ImageView>,
+    {
+        TransientPass { set: None }
+    }
+
+    fn prepare(
+        &mut self,
+        sets: &mut Descriptors<B>,
+        _buffers: &[&B::Buffer],
+        _cbuf: &mut PrepareBuffer<B>,
+        device: &mut D,
+        _aux: &T,
+    ) {
+        if let Some(set) = self.set.take() {
+            sets.free(0, 0, set);
+        }
+        let set = sets.allocate(device, 0, 0).unwrap();
+        device.write_descriptor_sets(Some(DescriptorSetWrite {
+            set: &set,
+            binding: 7,
+            array_offset: 0,
+            descriptors: Vec::<Descriptor<B>>::new(),
+        }));
+        self.set = Some(set);
+    }
+
+    fn draw<L, P>(
+        &mut self,
+        sets: &Descriptors<B>,
+        buffers: &[&B::Buffer],
+        layouts: &L,
+        pipelines: &P,
+        encoder: RenderPassInlineEncoder<B, Primary>,
+        aux: &T,
+    ) where
+        L: Index<usize>,
+        L::Output: Borrow<B::PipelineLayout>,
+        P: Index<usize>,
+        P::Output: Borrow<B::GraphicsPipeline>,
+    {
+        <SamplePass as RenderPass<B, D, T>>::draw(
+            &mut SamplePass,
+            sets,
+            buffers,
+            layouts,
+            pipelines,
+            encoder,
+            aux,
+        );
+    }
+
+    fn dispose(self, _device: &mut D, _aux: &mut T) {}
+}
+
+/// Id and usage of the first image created by the graph.
+fn graph_image(calls: &[Call]) -> (usize, image::Usage) {
+    calls
+        .iter()
+        .filter_map(|call| match *call {
+            Call::CreateImage { image, usage, .. } => Some((image, usage)),
+            _ => None,
+        })
+        .next()
+        .unwrap()
+}
+
+/// Find submission which commands satisfy the predicate.
+fn find_submission<F>(submissions: &[Submission], f: F) -> Submission
+where
+    F: Fn(&Command) -> bool,
+{
+    submissions
+        .iter()
+        .find(|submission| submission.commands.iter().any(&f))
+        .cloned()
+        .unwrap()
+}
+
+#[test]
+fn semaphores_and_barriers_are_recorded_between_queues() {
+    let families = vec![
+        mock::QueueFamily::new(0, QueueType::General, 1),
+        mock::QueueFamily::new(1, QueueType::Transfer, 1),
+    ];
+    let mut device = mock::Device::new();
+    let mut queues = device.queues(&families);
+    let log = device.log();
+
+    let mut builder = Builder::new();
+    let target = builder.create_image(image::Kind::D2(4, 4, 1, 1), Format::Rgba8Unorm, None);
+    let clear = builder.add_node(ClearNode::<Back>::builder().with_image(target));
+    builder.add_readback(
+        ReadbackBuilder::image(target, mock::memory_types()).with_dependency(clear),
+    );
+    let mut graph =
+        builder.build_headless(&families, create_buffer, create_image, &mut device, &mut ());
+
+    let (image, usage) = graph_image(&log.calls());
+    assert!(usage.contains(image::Usage::TRANSFER_DST | image::Usage::TRANSFER_SRC));
+    log.clear();
+
+    let mut fences = Vec::new();
+    let status = graph.run(&mut queues, &mut device, &mut (), &mut fences);
+    assert!(status.is_ok());
+
+    let submissions = log.submissions();
+    let clear = find_submission(&submissions, |command| match *command {
+        Command::ClearImage(id, image::Layout::TransferDstOptimal) => id == image,
+        _ => false,
+    });
+    let readback = find_submission(&submissions, |command| match *command {
+        Command::CopyImageToBuffer((id, image::Layout::TransferSrcOptimal), _) => id == image,
+        _ => false,
+    });
+
+    assert_eq!(clear.family, QueueFamilyId(0));
+    assert_eq!(readback.family, QueueFamilyId(1));
+    assert!(!clear.signals.is_empty());
+    for signal in &clear.signals {
+        assert!(readback.waits.iter().any(|&(wait, _)| wait == *signal));
+    }
+
+    // Image is transitioned for clearing before the clear and released to the transfer queue.
+    let layouts = |submission: &Submission| {
+        submission
+            .commands
+            .iter()
+            .filter_map(|command| match *command {
+                Command::Barrier(ref barrier) => Some(barrier.images.clone()),
+                _ => None,
+            })
+            .flat_map(|images| images)
+            .filter(|&(id, _)| id == image)
+            .map(|(_, states)| (states.start.1, states.end.1))
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(
+        layouts(&clear)[0],
+        (image::Layout::Undefined, image::Layout::TransferDstOptimal)
+    );
+    assert!(
+        layouts(&readback)
+            .iter()
+            .any(|&(_, layout)| layout == image::Layout::TransferSrcOptimal)
+    );
+
+    assert!(log.presents().is_empty());
+
+    for fence in &fences[..status.fences] {
+        device.wait_for_fence(fence, !0);
+    }
+    graph.dispose(&mut device, &mut ());
+}
+
+#[test]
+fn presents_wait_for_rendering() {
+    let families = mock::families();
+    let mut device = mock::Device::new();
+    let mut queues = device.queues(&families);
+    let log = device.log();
+
+    let mut surface = mock::Surface::new(4, 4);
+    let capabilities = surface.capabilities();
+
+    let mut builder = Builder::new();
+    let target = builder.create_image(image::Kind::D2(4, 4, 1, 1), Format::Rgba8Unorm, None);
+    builder.add_node(ClearNode::<Back>::builder().with_image(target));
+    let present = PresentBuilder::new(target, Format::Rgba8Unorm, &mut surface, capabilities);
+    let mut graph = builder.build(
+        &families,
+        create_buffer,
+        create_image,
+        Some(present),
+        &mut device,
+        &mut (),
+    );
+
+    let swapchain = log
+        .calls()
+        .iter()
+        .filter_map(|call| match *call {
+            Call::CreateSwapchain { swapchain, .. } => Some(swapchain),
+            _ => None,
+        })
+        .next()
+        .unwrap();
+
+    let mut fences = Vec::new();
+    for frame in 0..3 {
+        log.clear();
+        let status = graph.run(&mut queues, &mut device, &mut (), &mut fences);
+        assert!(status.is_ok());
+
+        let acquired = log
+            .calls()
+            .iter()
+            .filter_map(|call| match *call {
+                Call::AcquireImage { swapchain, index } => Some((swapchain, index)),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(acquired.len(), 1);
+        let index = acquired[0].1.unwrap();
+
+        let presents = log.presents();
+        assert_eq!(presents.len(), 1, "Frame {}", frame);
+
+        // Present waits for acquired image and signals the semaphore presentation waits for.
+        let stats = graph.last_frame_stats();
+        assert_eq!(stats.submissions, log.submissions().len());
+        assert_eq!(
+            stats.waits,
+            log.submissions()
+                .iter()
+                .map(|submission| submission.waits.len())
+                .sum::<usize>()
+        );
+        assert_eq!(
+            stats.signals,
+            log.submissions()
+                .iter()
+                .map(|submission| submission.signals.len())
+                .sum::<usize>()
+        );
+        assert_eq!(presents[0].swapchains, vec![(swapchain, index)]);
+
+        // Present waits for the semaphore signaled by the blit.
+        let submissions = log.submissions();
+        let blit = find_submission(&submissions, |command| match *command {
+            Command::BlitImage(..) | Command::CopyImage(..) => true,
+            _ => false,
+        });
+        assert!(!presents[0].waits.is_empty());
+        for wait in &presents[0].waits {
+            assert!(blit.signals.contains(wait));
+        }
+
+        for fence in &fences[..status.fences] {
+            device.wait_for_fence(fence, !0);
+        }
+    }
+
+    graph.dispose(&mut device, &mut ());
+}
+
+#[test]
+fn framebuffer_present_samples_image() {
+    let families = mock::families();
+    let mut device = mock::Device::new();
+    let mut queues = device.queues(&families);
+    let log = device.log();
+
+    let mut surface = mock::Surface::with_framebuffer(4, 4);
+    let capabilities = surface.capabilities();
+
+    let mut builder = Builder::new();
+    let target = builder.create_image(image::Kind::D2(4, 4, 1, 1), Format::Rgba8Unorm, None);
+    builder.add_node(ClearNode::<Back>::builder().with_image(target));
+    let present = PresentBuilder::new(target, Format::Rgba8Unorm, &mut surface, capabilities);
+    let mut graph = builder.build(
+        &families,
+        create_buffer,
+        create_image,
+        Some(present),
+        &mut device,
+        &mut (),
+    );
+
+    let (image, usage) = graph_image(&log.calls());
+    assert!(usage.contains(image::Usage::SAMPLED));
+    assert!(!usage.contains(image::Usage::TRANSFER_SRC));
+
+    let mut fences = Vec::new();
+    for _ in 0..2 {
+        log.clear();
+        let status = graph.run(&mut queues, &mut device, &mut (), &mut fences);
+        assert!(status.is_ok());
+
+        let submissions = log.submissions();
+        find_submission(&submissions, |command| match *command {
+            Command::BeginRenderPass { .. } => true,
+            _ => false,
+        });
+
+        // Image is sampled in the layout declared by the node. It's never read by transfer.
+        let barriers = log
+            .barriers()
+            .into_iter()
+            .flat_map(|barrier| barrier.images)
+            .filter(|&(id, _)| id == image)
+            .collect::<Vec<_>>();
+        assert!(
+            barriers
+                .iter()
+                .any(|&(_, ref states)| states.end.1 == image::Layout::ShaderReadOnlyOptimal)
+        );
+        assert!(barriers.iter().all(|&(_, ref states)| {
+            states.start.1 != image::Layout::TransferSrcOptimal
+                && states.end.1 != image::Layout::TransferSrcOptimal
+        }));
+
+        for fence in &fences[..status.fences] {
+            device.wait_for_fence(fence, !0);
+        }
+    }
+
+    graph.dispose(&mut device, &mut ());
+}
+
+#[test]
+fn inline_attachment_barriers_are_folded_into_render_pass() {
+    let families = mock::families();
+    let mut device = mock::Device::new();
+    let mut queues = device.queues(&families);
+    let log = device.log();
+
+    let mut builder = Builder::new();
+    let source = builder.create_image(image::Kind::D2(4, 4, 1, 1), Format::Rgba8Unorm, None);
+    let target = builder.create_image(image::Kind::D2(4, 4, 1, 1), Format::Rgba8Unorm, None);
+    let clear = builder.add_node(ClearNode::<Back>::builder().with_image(source));
+    builder.add_node(
+        <InlineSamplePass as RenderPassDesc<Back>>::builder()
+            .with_image(source)
+            .with_image(target)
+            .with_dependency(clear),
+    );
+    let mut graph =
+        builder.build_headless(&families, create_buffer, create_image, &mut device, &mut ());
+
+    let calls = log.calls();
+    let mut images = calls.iter().filter_map(|call| match *call {
+        Call::CreateImage { image, .. } => Some(image),
+        _ => None,
+    });
+    let (source, target) = (images.next().unwrap(), images.next().unwrap());
+
+    // Target is transitioned from its first use by the render pass.
+    let (layouts, dependencies) = calls
+        .iter()
+        .filter_map(|call| match *call {
+            Call::CreateRenderPass {
+                ref layouts,
+                ref dependencies,
+                ..
+            } => Some((layouts.clone(), dependencies.clone())),
+            _ => None,
+        })
+        .next()
+        .unwrap();
+    assert_eq!(
+        layouts,
+        vec![image::Layout::Undefined..image::Layout::ColorAttachmentOptimal]
+    );
+    assert!(dependencies.iter().any(|dependency| {
+        dependency.from.is_none() && dependency.to == Some(0)
+            && dependency.stages.end.contains(PipelineStage::COLOR_ATTACHMENT_OUTPUT)
+    }));
+
+    log.clear();
+    let mut fences = Vec::new();
+    let status = graph.run(&mut queues, &mut device, &mut (), &mut fences);
+    assert!(status.is_ok());
+
+    // Render pass submission has no barriers for the attachment.
+    let submissions = log.submissions();
+    let render = find_submission(&submissions, |command| match *command {
+        Command::BeginRenderPass { .. } => true,
+        _ => false,
+    });
+    let barriers = render
+        .commands
+        .iter()
+        .filter_map(|command| match *command {
+            Command::Barrier(ref barrier) => Some(barrier.images.clone()),
+            _ => None,
+        })
+        .flat_map(|images| images)
+        .collect::<Vec<_>>();
+    assert!(barriers.iter().all(|&(id, _)| id != target));
+    assert!(
+        log.barriers()
+            .into_iter()
+            .flat_map(|barrier| barrier.images)
+            .any(|(id, states)| {
+                id == source && states.end.1 == image::Layout::ShaderReadOnlyOptimal
+            })
+    );
+
+    for fence in &fences[..status.fences] {
+        device.wait_for_fence(fence, !0);
+    }
+    graph.dispose(&mut device, &mut ());
+}
+
+#[test]
+fn readback_waits_for_writers_added_before_it() {
+    let families = vec![
+        mock::QueueFamily::new(0, QueueType::General, 1),
+        mock::QueueFamily::new(1, QueueType::Transfer, 1),
+    ];
+    let mut device = mock::Device::new();
+    let mut queues = device.queues(&families);
+    let log = device.log();
+
+    let mut builder = Builder::new();
+    let target = builder.create_image(image::Kind::D2(4, 4, 1, 1), Format::Rgba8Unorm, None);
+    builder.add_node(ClearNode::<Back>::builder().with_image(target));
+    builder.add_readback(ReadbackBuilder::image(target, mock::memory_types()));
+    let mut graph =
+        builder.build_headless(&families, create_buffer, create_image, &mut device, &mut ());
+
+    let (image, _) = graph_image(&log.calls());
+    log.clear();
+
+    let mut fences = Vec::new();
+    let status = graph.run(&mut queues, &mut device, &mut (), &mut fences);
+    assert!(status.is_ok());
+
+    let submissions = log.submissions();
+    let clear = find_submission(&submissions, |command| match *command {
+        Command::ClearImage(id, _) => id == image,
+        _ => false,
+    });
+    let readback = find_submission(&submissions, |command| match *command {
+        Command::CopyImageToBuffer((id, _), _) => id == image,
+        _ => false,
+    });
+    assert!(!clear.signals.is_empty());
+    for signal in &clear.signals {
+        assert!(readback.waits.iter().any(|&(wait, _)| wait == *signal));
+    }
+
+    for fence in &fences[..status.fences] {
+        device.wait_for_fence(fence, !0);
+    }
+    graph.dispose(&mut device, &mut ());
+}
+
+#[test]
+#[should_panic(expected = "Depth stencil image 0 'depth' can't be read back")]
+fn depth_stencil_readback_is_rejected() {
+    let families = mock::families();
+    let mut device = mock::Device::new();
+    let mut builder = Builder::new();
+    let depth = builder.create_named_image(
+        Some("depth"),
+        image::Kind::D2(4, 4, 1, 1),
+        Format::D24UnormS8Uint,
+        None,
+    );
+    builder.add_readback(ReadbackBuilder::image(depth, mock::memory_types()));
+    builder.build_headless(&families, create_buffer, create_image, &mut device, &mut ());
+}
+
+#[test]
+fn stats_count_submitted_work() {
+    let families = mock::families();
+    let mut device = mock::Device::new();
+    let mut queues = device.queues(&families);
+    let log = device.log();
+
+    let mut builder = Builder::new()
+        .with_gpu_timings(1.0, 2)
+        .with_split_barriers();
+    let first = builder.create_image(image::Kind::D2(4, 4, 1, 1), Format::Rgba8Unorm, None);
+    let second = builder.create_image(image::Kind::D2(4, 4, 1, 1), Format::Rgba8Unorm, None);
+    let clear_first = builder.add_node(ClearNode::<Back>::builder().with_image(first));
+    let clear_second = builder.add_node(
+        ClearNode::<Back>::builder()
+            .with_image(second)
+            .with_dependency(clear_first),
+    );
+    builder.add_readback(
+        ReadbackBuilder::image(first, mock::memory_types()).with_dependency(clear_second),
+    );
+    let mut graph =
+        builder.build_headless(&families, create_buffer, create_image, &mut device, &mut ());
+
+    let images = log
+        .calls()
+        .iter()
+        .filter_map(|call| match *call {
+            Call::CreateImage { image, .. } => Some(image),
+            _ => None,
+        })
+        .collect::<Vec<_>>();
+
+    let mut fences = Vec::new();
+    for _ in 0..2 {
+        log.clear();
+        let status = graph.run(&mut queues, &mut device, &mut (), &mut fences);
+        assert!(status.is_ok());
+
+        // Split barrier is waited with events.
+        let submissions = log.submissions();
+        assert!(submissions.iter().any(|submission| {
+            submission.commands.iter().any(|command| match *command {
+                Command::WaitEvents(..) => true,
+                _ => false,
+            })
+        }));
+
+        let stats = graph.last_frame_stats();
+        assert_eq!(stats.submissions, submissions.len());
+        assert_eq!(stats.nodes.len(), 3);
+
+        // Staging buffer barrier of the readback is not a graph barrier.
+        let barriers = log
+            .barriers()
+            .into_iter()
+            .flat_map(|barrier| barrier.images)
+            .filter(|&(id, _)| images.contains(&id))
+            .count();
+        assert_eq!(stats.barriers, barriers);
+
+        for fence in &fences[..status.fences] {
+            device.wait_for_fence(fence, !0);
+        }
+    }
+
+    graph.dispose(&mut device, &mut ());
+}
+
+#[test]
+fn static_commands_are_resubmitted_until_dirty() {
+    let families = mock::families();
+    let mut device = mock::Device::new();
+    let mut queues = device.queues(&families);
+    let log = device.log();
+
+    let mut builder = Builder::new();
+    let source = builder.create_image(image::Kind::D2(4, 4, 1, 1), Format::Rgba8Unorm, None);
+    let target = builder.create_image(image::Kind::D2(4, 4, 1, 1), Format::Rgba8Unorm, None);
+    let clear = builder.add_node(ClearNode::<Back>::builder().with_image(source));
+    builder.add_node(
+        <StaticPass as RenderPassDesc<Back>>::builder()
+            .with_image(source)
+            .with_image(target)
+            .with_dependency(clear),
+    );
+    let mut graph =
+        builder.build_headless(&families, create_buffer, create_image, &mut device, &mut ());
+
+    let mut fences = Vec::new();
+    let mut run = |graph: &mut Graph, device: &mut mock::Device| {
+        log.clear();
+        let status = graph.run(&mut queues, device, &mut (), &mut fences);
+        assert!(status.is_ok());
+        for fence in &fences[..status.fences] {
+            device.wait_for_fence(fence, !0);
+        }
+        let submissions = log.submissions();
+        find_submission(&submissions, |command| match *command {
+            Command::BeginRenderPass { .. } => true,
+            _ => false,
+        })
+    };
+
+    // First frame records the render pass.
+    let first = run(&mut graph, &mut device);
+    assert_eq!(STATIC_PREPARED.load(Ordering::SeqCst), 1);
+    assert_eq!(STATIC_DRAWN.load(Ordering::SeqCst), 1);
+
+    // Second frame submits the same command buffer without calling the pass.
+    let second = run(&mut graph, &mut device);
+    assert_eq!(STATIC_PREPARED.load(Ordering::SeqCst), 1);
+    assert_eq!(STATIC_DRAWN.load(Ordering::SeqCst), 1);
+    // Only the one-shot buffer with `prepare` commands is left out.
+    assert_eq!(second.cbufs.len(), first.cbufs.len() - 1);
+    assert!(second.cbufs.iter().all(|cbuf| first.cbufs.contains(cbuf)));
+    assert_eq!(
+        second
+            .commands
+            .iter()
+            .filter(|command| **command == Command::EndRenderPass)
+            .count(),
+        1
+    );
+
+    // Render pass is recorded again once the pass reports changes.
+    STATIC_DIRTY.store(true, Ordering::SeqCst);
+    run(&mut graph, &mut device);
+    assert_eq!(STATIC_PREPARED.load(Ordering::SeqCst), 2);
+    assert_eq!(STATIC_DRAWN.load(Ordering::SeqCst), 2);
+
+    run(&mut graph, &mut device);
+    assert_eq!(STATIC_PREPARED.load(Ordering::SeqCst), 2);
+    assert_eq!(STATIC_DRAWN.load(Ordering::SeqCst), 2);
+
+    graph.dispose(&mut device, &mut ());
+}
+
+#[test]
+fn freed_descriptor_sets_are_reused_in_the_same_frame_slot() {
+    let families = mock::families();
+    let mut device = mock::Device::new();
+    let mut queues = device.queues(&families);
+    let log = device.log();
+
+    let mut builder = Builder::new().with_frames_in_flight(2);
+    let source = builder.create_image(image::Kind::D2(4, 4, 1, 1), Format::Rgba8Unorm, None);
+    let target = builder.create_image(image::Kind::D2(4, 4, 1, 1), Format::Rgba8Unorm, None);
+    let clear = builder.add_node(ClearNode::<Back>::builder().with_image(source));
+    builder.add_node(
+        <TransientPass<Back> as RenderPassDesc<Back>>::builder()
+            .with_image(source)
+            .with_image(target)
+            .with_dependency(clear),
+    );
+    let mut graph =
+        builder.build_headless(&families, create_buffer, create_image, &mut device, &mut ());
+
+    let mut fences = Vec::new();
+    for _ in 0..5 {
+        let status = graph.run(&mut queues, &mut device, &mut (), &mut fences);
+        assert!(status.is_ok());
+        for fence in &fences[..status.fences] {
+            device.wait_for_fence(fence, !0);
+        }
+    }
+
+    let sets = log
+        .calls()
+        .iter()
+        .filter_map(|call| match *call {
+            Call::WriteDescriptorSet { set, binding: 7, .. } => Some(set),
+            _ => None,
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(sets.len(), 5);
+
+    // Set freed in the second frame may still be used by the first one in flight.
+    // It is reused only when the second frame slot comes around again.
+    assert_ne!(sets[0], sets[1]);
+    assert_ne!(sets[2], sets[0]);
+    assert_ne!(sets[2], sets[1]);
+    assert_eq!(sets[3], sets[0]);
+    assert_eq!(sets[4], sets[1]);
+
+    graph.dispose(&mut device, &mut ());
+}
+
+#[test]
+fn split_barriers_are_recorded_within_node_submissions() {
+    let families = mock::families();
+    let mut device = mock::Device::new();
+    let mut queues = device.queues(&families);
+    let log = device.log();
+
+    let mut builder = Builder::new().with_split_barriers();
+    let first = builder.create_image(image::Kind::D2(4, 4, 1, 1), Format::Rgba8Unorm, None);
+    let second = builder.create_image(image::Kind::D2(4, 4, 1, 1), Format::Rgba8Unorm, None);
+    let producer = builder.add_node(ClearNode::<Back>::builder().with_image(first));
+    let unrelated = builder.add_node(
+        ClearNode::<Back>::builder()
+            .with_image(second)
+            .with_dependency(producer),
+    );
+    builder.add_node(
+        ClearNode::<Back>::builder()
+            .with_image(first)
+            .with_dependency(unrelated),
+    );
+    let mut graph =
+        builder.build_headless(&families, create_buffer, create_image, &mut device, &mut ());
+    let (image, _) = graph_image(&log.calls());
+
+    log.clear();
+    let mut fences = Vec::new();
+    let status = graph.run(&mut queues, &mut device, &mut (), &mut fences);
+    assert!(status.is_ok());
+
+    // Graph doesn't submit anything besides the nodes.
+    let submissions = log.submissions();
+    assert_eq!(submissions.len(), 3);
+    let clear = Command::ClearImage(image, image::Layout::TransferDstOptimal);
+    let position = |submission: &Submission, f: &Fn(&Command) -> bool| {
+        submission.commands.iter().position(f).unwrap()
+    };
+
+    // Producer sets the event after clearing.
+    let ref producer = submissions[0];
+    let set = position(producer, &|command| match *command {
+        Command::SetEvent(..) => true,
+        _ => false,
+    });
+    assert!(position(producer, &|command| *command == clear) < set);
+    let event = match producer.commands[set] {
+        Command::SetEvent(event, _) => event,
+        _ => unreachable!(),
+    };
+
+    // Consumer waits for the event with the barrier before clearing.
+    let ref consumer = submissions[2];
+    let wait = position(consumer, &|command| match *command {
+        Command::WaitEvents(ref events, ref barrier) => {
+            events.contains(&event) && barrier.images.iter().any(|&(id, _)| id == image)
+        }
+        _ => false,
+    });
+    assert!(wait < position(consumer, &|command| *command == clear));
+
+    // The barrier is not recorded by the consumer itself.
+    assert!(!consumer.commands.iter().any(|command| match *command {
+        Command::Barrier(ref barrier) => barrier.images.iter().any(|&(id, _)| id == image),
+        _ => false,
+    }));
+
+    for fence in &fences[..status.fences] {
+        device.wait_for_fence(fence, !0);
+    }
+    graph.dispose(&mut device, &mut ());
+}
+
+#[test]
+fn timestamps_are_written_within_node_submissions() {
+    let families = mock::families();
+    let mut device = mock::Device::new();
+    let mut queues = device.queues(&families);
+    let log = device.log();
+
+    let mut builder = Builder::new()
+        .with_gpu_timings(1.0, 2)
+        .with_split_barriers();
+    let first = builder.create_image(image::Kind::D2(4, 4, 1, 1), Format::Rgba8Unorm, None);
+    let second = builder.create_image(image::Kind::D2(4, 4, 1, 1), Format::Rgba8Unorm, None);
+    let producer = builder.add_node(ClearNode::<Back>::builder().with_image(first));
+    let unrelated = builder.add_node(
+        ClearNode::<Back>::builder()
+            .with_image(second)
+            .with_dependency(producer),
+    );
+    builder.add_node(
+        ClearNode::<Back>::builder()
+            .with_image(first)
+            .with_dependency(unrelated),
+    );
+    let mut graph =
+        builder.build_headless(&families, create_buffer, create_image, &mut device, &mut ());
+
+    log.clear();
+    let mut fences = Vec::new();
+    let status = graph.run(&mut queues, &mut device, &mut (), &mut fences);
+    assert!(status.is_ok());
+
+    // One submission per node with the fence signaled by the last one.
+    let submissions = log.submissions();
+    assert_eq!(submissions.len(), 3);
+    assert!(submissions[2].fence.is_some());
+
+    for submission in &submissions {
+        let timestamps = submission
+            .commands
+            .iter()
+            .enumerate()
+            .filter(|&(_, command)| match *command {
+                Command::WriteTimestamp(_) => true,
+                _ => false,
+            })
+            .map(|(index, _)| index)
+            .collect::<Vec<_>>();
+        assert_eq!(timestamps.len(), 2);
+
+        // Event waits precede and event sets follow the measured commands.
+        for (index, command) in submission.commands.iter().enumerate() {
+            match *command {
+                Command::WaitEvents(..) => assert!(index < timestamps[0]),
+                Command::SetEvent(..) => assert!(index > timestamps[1]),
+                Command::ClearImage(..) => {
+                    assert!(timestamps[0] < index && index < timestamps[1])
+                }
+                _ => {}
+            }
+        }
+    }
+
+    for fence in &fences[..status.fences] {
+        device.wait_for_fence(fence, !0);
+    }
+    graph.dispose(&mut device, &mut ());
+}
+
+#[test]
+fn history_images_are_swapped_between_frames() {
+    let families = mock::families();
+    let mut device = mock::Device::new();
+    let mut queues = device.queues(&families);
+    let log = device.log();
+
+    let mut builder = Builder::new();
+    let (current, previous) =
+        builder.create_history_image(image::Kind::D2(4, 4, 1, 1), Format::Rgba8Unorm, None);
+    builder.add_node(
+        <SamplePass as RenderPassDesc<Back>>::builder()
+            .with_image(previous)
+            .with_image(current),
+    );
+    let mut graph =
+        builder.build_headless(&families, create_buffer, create_image, &mut device, &mut ());
+
+    // The node is built once for both orders of the images.
+    let calls = log.calls();
+    let passes = calls
+        .iter()
+        .filter(|call| match **call {
+            Call::CreateRenderPass { .. } => true,
+            _ => false,
+        })
+        .count();
+    assert_eq!(passes, 1);
+    let images = calls
+        .iter()
+        .filter_map(|call| match *call {
+            Call::CreateImage { image, .. } => Some(image),
+            _ => None,
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(images.len(), 2);
+    let view_image = |view: usize| {
+        calls
+            .iter()
+            .filter_map(|call| match *call {
+                Call::CreateImageView { view: id, image } if id == view => Some(image),
+                _ => None,
+            })
+            .next()
+            .unwrap()
+    };
+    let set_image = |set: usize| {
+        calls
+            .iter()
+            .filter_map(|call| match *call {
+                Call::WriteDescriptorSet { set: id, ref views, .. } if id == set => Some(views[0]),
+                _ => None,
+            })
+            .map(view_image)
+            .next()
+            .unwrap()
+    };
+
+    let mut fences = Vec::new();
+    for frame in 0..3 {
+        log.clear();
+        let status = graph.run(&mut queues, &mut device, &mut (), &mut fences);
+        assert!(status.is_ok());
+
+        // Current frame image of even frames is the previous frame image of odd frames.
+        let (target, sampled) = if frame % 2 == 0 {
+            (images[0], images[1])
+        } else {
+            (images[1], images[0])
+        };
+
+        // Transitions are recorded within the node's submission.
+        let submissions = log.submissions();
+        assert_eq!(submissions.len(), 1);
+        let ref commands = submissions[0].commands;
+        let begin = commands
+            .iter()
+            .position(|command| match *command {
+                Command::BeginRenderPass { .. } => true,
+                _ => false,
+            })
+            .unwrap();
+        match commands[begin] {
+            Command::BeginRenderPass {
+                ref attachments, ..
+            } => assert_eq!(view_image(attachments[0]), target),
+            _ => unreachable!(),
+        }
+        let bound = commands
+            .iter()
+            .filter_map(|command| match *command {
+                Command::BindDescriptorSets(ref sets) => Some(sets[0]),
+                _ => None,
+            })
+            .next()
+            .unwrap();
+        assert_eq!(set_image(bound), sampled);
+
+        // Content is undefined on the first frame.
+        // Later each image continues from the state it was left in by the other id.
+        let transitions = commands[..begin]
+            .iter()
+            .filter_map(|command| match *command {
+                Command::Barrier(ref barrier) => Some(barrier.images.clone()),
+                _ => None,
+            })
+            .flat_map(|images| images)
+            .collect::<Vec<_>>();
+        let layouts = |image: usize| {
+            let states = transitions
+                .iter()
+                .find(|&&(id, _)| id == image)
+                .map(|&(_, ref states)| states.clone())
+                .unwrap();
+            (states.start.1, states.end.1)
+        };
+        let (target_source, sampled_source) = if frame == 0 {
+            (image::Layout::Undefined, image::Layout::Undefined)
+        } else {
+            (
+                image::Layout::ShaderReadOnlyOptimal,
+                image::Layout::ColorAttachmentOptimal,
+            )
+        };
+        assert_eq!(
+            layouts(target),
+            (target_source, image::Layout::ColorAttachmentOptimal)
+        );
+        assert_eq!(
+            layouts(sampled),
+            (sampled_source, image::Layout::ShaderReadOnlyOptimal)
+        );
+
+        for fence in &fences[..status.fences] {
+            device.wait_for_fence(fence, !0);
+        }
+    }
+
+    graph.dispose(&mut device, &mut ());
+}
+
+#[test]
+#[should_panic(expected = "History image 1 'accumulated (previous)' can't be read back")]
+fn history_image_readback_is_rejected_by_name() {
+    let families = mock::families();
+    let mut device = mock::Device::new();
+    let mut builder = Builder::new();
+    let (current, previous) = builder.create_named_history_image(
+        Some("accumulated"),
+        image::Kind::D2(4, 4, 1, 1),
+        Format::Rgba8Unorm,
+        None,
+    );
+    let clear = builder.add_node(ClearNode::<Back>::builder().with_image(current));
+    builder.add_readback(
+        ReadbackBuilder::image(previous, mock::memory_types()).with_dependency(clear),
+    );
+    builder.build_headless(&families, create_buffer, create_image, &mut device, &mut ());
+}
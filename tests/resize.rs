@@ -0,0 +1,295 @@
+#![cfg(feature = "testing")]
+
+extern crate gfx_hal as hal;
+extern crate smallvec;
+extern crate xfg;
+
+mod common;
+
+use hal::{
+    format::Format, image, window::{Extent2D, SurfaceCapabilities}, Device,
+};
+
+use xfg::{
+    present::{PresentBuilder, PresentError, RecreateError}, render::RenderPassDesc,
+    testing::mock::{self, Call, Command}, GraphBuilder,
+};
+
+use common::{create_buffer, create_image, ClearNode, SamplePass};
+
+type Back = mock::Backend;
+type Builder = GraphBuilder<Back, mock::Device, (), mock::Buffer, mock::Image>;
+
+#[test]
+fn recreated_images_are_rebound() {
+    let families = mock::families();
+    let mut device = mock::Device::new();
+    let mut queues = device.queues(&families);
+    let log = device.log();
+
+    let mut builder = Builder::new();
+    let source = builder.create_image(image::Kind::D2(4, 4, 1, 1), Format::Rgba8Unorm, None);
+    let target = builder.create_image(image::Kind::D2(4, 4, 1, 1), Format::Rgba8Unorm, None);
+    let clear = builder.add_node(ClearNode::<Back>::builder().with_image(source));
+    builder.add_node(
+        <SamplePass as RenderPassDesc<Back>>::builder()
+            .with_image(source)
+            .with_image(target)
+            .with_dependency(clear),
+    );
+    let mut graph =
+        builder.build_headless(&families, create_buffer, create_image, &mut device, &mut ());
+
+    let mut fences = Vec::new();
+    let status = graph.run(&mut queues, &mut device, &mut (), &mut fences);
+    assert!(status.is_ok());
+    for fence in &fences[..status.fences] {
+        device.wait_for_fence(fence, !0);
+    }
+
+    let views = |calls: &[Call]| {
+        calls
+            .iter()
+            .filter_map(|call| match *call {
+                Call::CreateImageView { view, image } => Some((view, image)),
+                _ => None,
+            })
+            .collect::<Vec<_>>()
+    };
+    let old_views = views(&log.calls());
+    log.clear();
+
+    let kind = image::Kind::D2(8, 8, 1, 1);
+    let replaced = graph.recreate_images(
+        &[(source, kind), (target, kind)],
+        &families,
+        create_image,
+        &mut device,
+        &mut (),
+    );
+    assert_eq!(replaced.len(), 2);
+
+    let calls = log.calls();
+    let created = calls
+        .iter()
+        .filter_map(|call| match *call {
+            Call::CreateImage { image, kind: created, .. } => {
+                assert_eq!(created, kind);
+                Some(image)
+            }
+            _ => None,
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(created.len(), 2);
+    let (new_source, new_target) = (created[0], created[1]);
+
+    let new_views = views(&calls);
+    let view_of = |image| {
+        new_views
+            .iter()
+            .find(|&&(_, viewed)| viewed == image)
+            .map(|&(view, _)| view)
+            .unwrap()
+    };
+    let (source_view, target_view) = (view_of(new_source), view_of(new_target));
+
+    // Sampled image is written into the descriptor set and target into the framebuffer.
+    assert!(calls.iter().any(|call| match *call {
+        Call::WriteDescriptorSet { ref views, .. } => views.contains(&source_view),
+        _ => false,
+    }));
+    assert!(calls.iter().any(|call| match *call {
+        Call::CreateFramebuffer {
+            ref attachments, ..
+        } => attachments.contains(&target_view),
+        _ => false,
+    }));
+
+    // Views of the old images are destroyed with the old nodes.
+    for &(view, _) in &old_views {
+        assert!(calls.contains(&Call::DestroyImageView(view)));
+    }
+
+    log.clear();
+    let status = graph.run(&mut queues, &mut device, &mut (), &mut fences);
+    assert!(status.is_ok());
+
+    let commands = log
+        .submissions()
+        .into_iter()
+        .flat_map(|submission| submission.commands)
+        .collect::<Vec<_>>();
+    assert!(commands.iter().any(|command| match *command {
+        Command::ClearImage(id, _) => id == new_source,
+        _ => false,
+    }));
+    assert!(commands.iter().any(|command| match *command {
+        Command::BeginRenderPass {
+            ref attachments, ..
+        } => attachments.contains(&target_view),
+        _ => false,
+    }));
+
+    for fence in &fences[..status.fences] {
+        device.wait_for_fence(fence, !0);
+    }
+    graph.dispose(&mut device, &mut ());
+}
+
+#[test]
+fn recreated_swapchain_has_extent_of_capabilities() {
+    let families = mock::families();
+    let mut device = mock::Device::new();
+    let mut queues = device.queues(&families);
+    let log = device.log();
+
+    let mut surface = mock::Surface::new(4, 4);
+    let capabilities = surface.capabilities();
+
+    let mut builder = Builder::new();
+    let target = builder.create_image(image::Kind::D2(4, 4, 1, 1), Format::Rgba8Unorm, None);
+    builder.add_node(ClearNode::<Back>::builder().with_image(target));
+    let present = PresentBuilder::new(target, Format::Rgba8Unorm, &mut surface, capabilities);
+    let mut graph = builder.build(
+        &families,
+        create_buffer,
+        create_image,
+        Some(present),
+        &mut device,
+        &mut (),
+    );
+
+    let extents = |calls: &[Call]| {
+        calls
+            .iter()
+            .filter_map(|call| match *call {
+                Call::CreateSwapchain { extent, .. } => Some((extent.width, extent.height)),
+                _ => None,
+            })
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(extents(&log.calls()), vec![(4, 4)]);
+
+    // Current extent reported by the surface is used.
+    surface.resize(8, 6);
+    let capabilities = surface.capabilities();
+    log.clear();
+    graph.recreate_swapchain(0, &mut surface, &capabilities, &mut device).unwrap();
+    assert_eq!(extents(&log.calls()), vec![(8, 6)]);
+
+    // Without current extent the extent of the surface is clamped to the supported range.
+    surface.resize(16, 16);
+    let capabilities = SurfaceCapabilities {
+        current_extent: None,
+        extents: Extent2D {
+            width: 1,
+            height: 1,
+        }..Extent2D {
+            width: 12,
+            height: 10,
+        },
+        ..surface.capabilities()
+    };
+    log.clear();
+    graph.recreate_swapchain(0, &mut surface, &capabilities, &mut device).unwrap();
+    assert_eq!(extents(&log.calls()), vec![(12, 10)]);
+
+    // Swapchain images have the picked extent.
+    let mut fences = Vec::new();
+    let status = graph.run(&mut queues, &mut device, &mut (), &mut fences);
+    assert!(status.is_ok());
+    assert!(log.submissions().iter().any(|submission| {
+        submission.commands.iter().any(|command| match *command {
+            Command::BlitImage(..) => true,
+            _ => false,
+        })
+    }));
+
+    for fence in &fences[..status.fences] {
+        device.wait_for_fence(fence, !0);
+    }
+    graph.dispose(&mut device, &mut ());
+}
+
+#[test]
+fn failed_direct_acquire_presents_other_images_unrendered() {
+    let families = mock::families();
+    let mut device = mock::Device::new();
+    let mut queues = device.queues(&families);
+    let log = device.log();
+
+    let mut first = mock::Surface::new(4, 4);
+    let mut second = mock::Surface::new(4, 4);
+    let (first_capabilities, second_capabilities) = (first.capabilities(), second.capabilities());
+
+    let mut builder = Builder::new();
+    let kind = image::Kind::D2(4, 4, 1, 1);
+    let first_image = builder.create_image(kind, Format::Rgba8Unorm, None);
+    let second_image = builder.create_image(kind, Format::Rgba8Unorm, None);
+    builder.add_node(ClearNode::<Back>::builder().with_image(first_image));
+    builder.add_node(ClearNode::<Back>::builder().with_image(second_image));
+    let presents = vec![
+        PresentBuilder::new(first_image, Format::Rgba8Unorm, &mut first, first_capabilities)
+            .with_direct(true),
+        PresentBuilder::new(second_image, Format::Rgba8Unorm, &mut second, second_capabilities)
+            .with_direct(true),
+    ];
+    let mut graph = builder.build(
+        &families,
+        create_buffer,
+        create_image,
+        presents,
+        &mut device,
+        &mut (),
+    );
+
+    let mut fences = Vec::new();
+    let status = graph.run(&mut queues, &mut device, &mut (), &mut fences);
+    assert!(status.is_ok());
+    assert_eq!(log.presents().len(), 2);
+    for fence in &fences[..status.fences] {
+        device.wait_for_fence(fence, !0);
+    }
+
+    // Image of the second present is acquired but nothing is rendered into it.
+    first.resize(8, 8);
+    log.clear();
+    let status = graph.run(&mut queues, &mut device, &mut (), &mut fences);
+    assert_eq!(status.fences, 0);
+    assert_eq!(&status.errors[..], &[(0, PresentError::Acquire)]);
+
+    let acquired = log
+        .calls()
+        .iter()
+        .filter_map(|call| match *call {
+            Call::AcquireImage {
+                swapchain,
+                index: Some(index),
+            } => Some((swapchain, index)),
+            _ => None,
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(acquired.len(), 1);
+
+    let submissions = log.submissions();
+    assert_eq!(submissions.len(), 1);
+    assert_eq!(submissions[0].waits.len(), 1);
+    assert!(submissions[0].commands.iter().all(|command| match *command {
+        Command::Barrier(..) => true,
+        _ => false,
+    }));
+
+    let presents = log.presents();
+    assert_eq!(presents.len(), 1);
+    assert_eq!(presents[0].swapchains, acquired);
+    assert_eq!(presents[0].waits, submissions[0].signals);
+
+    // Graph with direct presents must be rebuilt instead.
+    let capabilities = first.capabilities();
+    assert_eq!(
+        graph.recreate_swapchain(0, &mut first, &capabilities, &mut device),
+        Err(RecreateError::Direct)
+    );
+
+    graph.dispose(&mut device, &mut ());
+}
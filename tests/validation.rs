@@ -0,0 +1,351 @@
+#![cfg(all(feature = "testing", feature = "validation"))]
+
+extern crate gfx_hal as hal;
+extern crate smallvec;
+extern crate xfg;
+
+mod common;
+
+use std::{borrow::Borrow, ops::Index};
+
+use hal::{
+    buffer, command::{BufferCopy, Primary, RenderPassInlineEncoder}, format::Format, image,
+    pso::{
+        DescriptorSetLayoutBinding, DescriptorType, GraphicsShaderSet, PipelineStage,
+        ShaderStageFlags,
+    },
+    Backend, Device,
+};
+
+use xfg::{
+    render::{ImageBinding, Layout, PrepareBuffer, RenderPass, RenderPassDesc, SetLayout},
+    testing::mock, validate::{HazardKind, Resource, Validator}, Descriptors, Graph, GraphBuilder,
+};
+
+use common::{create_buffer, create_image, ClearNode, SamplePass};
+
+type Back = mock::Backend;
+type Builder = GraphBuilder<Back, mock::Device, (), mock::Buffer, mock::Image>;
+
+/// Same as `SamplePass` but binds the sampled image as storage image.
+struct StoragePass;
+
+impl<B> RenderPassDesc<B> for StoragePass
+where
+    B: Backend,
+{
+    fn name() -> &'static str {
+        "Storage"
+    }
+
+    fn sampled() -> usize {
+        1
+    }
+
+    fn sampled_bindings() -> Vec<(ImageBinding, image::SamplerInfo)> {
+        <SamplePass as RenderPassDesc<B>>::sampled_bindings()
+    }
+
+    fn colors() -> usize {
+        1
+    }
+
+    fn layouts() -> Vec<Layout> {
+        vec![Layout {
+            sets: vec![SetLayout {
+                bindings: vec![DescriptorSetLayoutBinding {
+                    binding: 0,
+                    ty: DescriptorType::StorageImage,
+                    count: 1,
+                    stage_flags: ShaderStageFlags::FRAGMENT,
+                }],
+            }],
+            push_constants: Vec::new(),
+        }]
+    }
+}
+
+impl<B, D, T> RenderPass<B, D, T> for StoragePass
+where
+    B: Backend,
+    D: Device<B>,
+{
+    fn load_shader_sets<'a>(
+        storage: &'a mut Vec<B::ShaderModule>,
+        device: &mut D,
+        aux: &mut T,
+    ) -> Vec<GraphicsShaderSet<'a, B>> {
+        <SamplePass as RenderPass<B, D, T>>::load_shader_sets(storage, device, aux)
+    }
+
+    fn build<I>(_sampled: I, _storage: I, _device: &mut D, _aux: &mut T) -> Self
+    where
+        I: IntoIterator,
+        I::Item: Borrow<B::ImageView>,
+    {
+        StoragePass
+    }
+
+    fn prepare(
+        &mut self,
+        _sets: &mut Descriptors<B>,
+        _buffers: &[&B::Buffer],
+        _cbuf: &mut PrepareBuffer<B>,
+        _device: &mut D,
+        _aux: &T,
+    ) {
+    }
+
+    fn draw<L, P>(
+        &mut self,
+        sets: &Descriptors<B>,
+        buffers: &[&B::Buffer],
+        layouts: &L,
+        pipelines: &P,
+        encoder: RenderPassInlineEncoder<B, Primary>,
+        aux: &T,
+    ) where
+        L: Index<usize>,
+        L::Output: Borrow<B::PipelineLayout>,
+        P: Index<usize>,
+        P::Output: Borrow<B::GraphicsPipeline>,
+    {
+        <SamplePass as RenderPass<B, D, T>>::draw(
+            &mut SamplePass,
+            sets,
+            buffers,
+            layouts,
+            pipelines,
+            encoder,
+            aux,
+        );
+    }
+
+    fn dispose(self, _device: &mut D, _aux: &mut T) {}
+}
+
+/// Same as `SamplePass` but copies between buffers it neither declares nor owns in `prepare`.
+struct UndeclaredCopyPass {
+    src: mock::Buffer,
+    dst: mock::Buffer,
+}
+
+impl RenderPassDesc<Back> for UndeclaredCopyPass {
+    fn name() -> &'static str {
+        "UndeclaredCopy"
+    }
+
+    fn sampled() -> usize {
+        1
+    }
+
+    fn sampled_bindings() -> Vec<(ImageBinding, image::SamplerInfo)> {
+        <SamplePass as RenderPassDesc<Back>>::sampled_bindings()
+    }
+
+    fn colors() -> usize {
+        1
+    }
+
+    fn layouts() -> Vec<Layout> {
+        <SamplePass as RenderPassDesc<Back>>::layouts()
+    }
+}
+
+impl RenderPass<Back, mock::Device, ()> for UndeclaredCopyPass {
+    fn load_shader_sets<'a>(
+        storage: &'a mut Vec<<Back as Backend>::ShaderModule>,
+        device: &mut mock::Device,
+        aux: &mut (),
+    ) -> Vec<GraphicsShaderSet<'a, Back>> {
+        <SamplePass as RenderPass<Back, mock::Device, ()>>::load_shader_sets(storage, device, aux)
+    }
+
+    fn build<I>(_sampled: I, _storage: I, device: &mut mock::Device, _aux: &mut ()) -> Self
+    where
+        I: IntoIterator,
+        I::Item: Borrow<<Back as Backend>::ImageView>,
+    {
+        UndeclaredCopyPass {
+            src: device.buffer(16, buffer::Usage::TRANSFER_SRC),
+            dst: device.buffer(16, buffer::Usage::TRANSFER_DST),
+        }
+    }
+
+    fn prepare(
+        &mut self,
+        _sets: &mut Descriptors<Back>,
+        _buffers: &[&mock::Buffer],
+        cbuf: &mut PrepareBuffer<Back>,
+        _device: &mut mock::Device,
+        _aux: &(),
+    ) {
+        cbuf.copy_buffer(
+            &self.src,
+            &self.dst,
+            Some(BufferCopy {
+                src: 0,
+                dst: 0,
+                size: 16,
+            }),
+        );
+    }
+
+    fn draw<L, P>(
+        &mut self,
+        sets: &Descriptors<Back>,
+        buffers: &[&mock::Buffer],
+        layouts: &L,
+        pipelines: &P,
+        encoder: RenderPassInlineEncoder<Back, Primary>,
+        aux: &(),
+    ) where
+        L: Index<usize>,
+        L::Output: Borrow<<Back as Backend>::PipelineLayout>,
+        P: Index<usize>,
+        P::Output: Borrow<<Back as Backend>::GraphicsPipeline>,
+    {
+        <SamplePass as RenderPass<Back, mock::Device, ()>>::draw(
+            &mut SamplePass,
+            sets,
+            buffers,
+            layouts,
+            pipelines,
+            encoder,
+            aux,
+        );
+    }
+
+    fn dispose(self, device: &mut mock::Device, _aux: &mut ()) {
+        device.destroy_buffer(self.src);
+        device.destroy_buffer(self.dst);
+    }
+}
+
+/// Build graph where `R` samples the cleared image and renders into another one.
+fn build<R>(device: &mut mock::Device) -> Graph<Back, mock::Device, (), mock::Buffer, mock::Image>
+where
+    R: RenderPass<Back, mock::Device, ()> + Send + Sync + 'static,
+{
+    let families = mock::families();
+    let mut builder = Builder::new();
+    let source = builder.create_named_image(
+        Some("source"),
+        image::Kind::D2(4, 4, 1, 1),
+        Format::Rgba8Unorm,
+        None,
+    );
+    let target = builder.create_image(image::Kind::D2(4, 4, 1, 1), Format::Rgba8Unorm, None);
+    let clear = builder.add_node(ClearNode::<Back>::builder().with_image(source));
+    builder.add_node(
+        <R as RenderPassDesc<Back>>::builder()
+            .with_image(source)
+            .with_image(target)
+            .with_dependency(clear),
+    );
+    builder.build_headless(&families, create_buffer, create_image, device, &mut ())
+}
+
+#[test]
+fn declared_accesses_are_not_reported() {
+    let mut device = mock::Device::new();
+    let graph = build::<SamplePass>(&mut device);
+    assert_eq!(graph.hazards(), Vec::new());
+    graph.dispose(&mut device, &mut ());
+}
+
+#[test]
+fn history_images_are_validated_per_frame() {
+    let families = mock::families();
+    let mut device = mock::Device::new();
+    let mut builder = Builder::new();
+    let (current, previous) =
+        builder.create_history_image(image::Kind::D2(4, 4, 1, 1), Format::Rgba8Unorm, None);
+    builder.add_node(
+        <SamplePass as RenderPassDesc<Back>>::builder()
+            .with_image(previous)
+            .with_image(current),
+    );
+    let graph =
+        builder.build_headless(&families, create_buffer, create_image, &mut device, &mut ());
+
+    // Each image is sampled in one frame and rendered into in the other.
+    assert_eq!(graph.hazards(), Vec::new());
+    graph.dispose(&mut device, &mut ());
+}
+
+#[test]
+fn misdeclared_descriptor_is_reported() {
+    let mut device = mock::Device::new();
+    let graph = build::<StoragePass>(&mut device);
+
+    // Sampled image is declared in `ShaderReadOnlyOptimal` layout
+    // while storage image descriptor requires `General`.
+    let hazards = graph.hazards();
+    assert_eq!(hazards.len(), 1);
+    assert_eq!(hazards[0].node, "Storage");
+    assert_eq!(hazards[0].command, "write_descriptor_sets");
+    assert_eq!(hazards[0].resource_name, Some("source".to_owned()));
+    assert_eq!(
+        hazards[0].kind,
+        HazardKind::Layout {
+            required: image::Layout::General,
+            current: image::Layout::ShaderReadOnlyOptimal,
+        }
+    );
+    graph.dispose(&mut device, &mut ());
+}
+
+#[test]
+fn undeclared_copy_in_prepare_is_reported() {
+    let families = mock::families();
+    let mut device = mock::Device::new();
+    let mut queues = device.queues(&families);
+    let mut graph = build::<UndeclaredCopyPass>(&mut device);
+
+    // `prepare` is recorded when the graph runs.
+    assert_eq!(graph.hazards(), Vec::new());
+
+    let mut fences = Vec::new();
+    for _ in 0..2 {
+        let status = graph.run(&mut queues, &mut device, &mut (), &mut fences);
+        assert!(status.is_ok());
+        for fence in &fences[..status.fences] {
+            device.wait_for_fence(fence, !0);
+        }
+    }
+
+    // Both buffers are unknown. The same hazard found again is not reported twice.
+    let hazards = graph.hazards();
+    assert_eq!(hazards.len(), 1);
+    assert_eq!(hazards[0].node, "UndeclaredCopy");
+    assert_eq!(hazards[0].command, "copy_buffer");
+    assert_eq!(hazards[0].resource, Resource::UnknownBuffer);
+    assert_eq!(hazards[0].kind, HazardKind::Undeclared);
+    graph.dispose(&mut device, &mut ());
+}
+
+#[test]
+fn undeclared_image_is_reported() {
+    let device = mock::Device::new();
+    let image = device.image(
+        image::Kind::D2(4, 4, 1, 1),
+        Format::Rgba8Unorm,
+        image::Usage::TRANSFER_SRC,
+    );
+
+    let mut validator = Validator::<Back>::new("PresentNode");
+    validator.access_image(
+        "blit_image",
+        &image,
+        (image::Access::TRANSFER_READ, image::Layout::TransferSrcOptimal),
+        PipelineStage::TRANSFER,
+    );
+
+    let hazards = validator.hazards();
+    assert_eq!(hazards.len(), 1);
+    assert_eq!(hazards[0].node, "PresentNode");
+    assert_eq!(hazards[0].command, "blit_image");
+    assert_eq!(hazards[0].resource, Resource::UnknownImage);
+    assert_eq!(hazards[0].kind, HazardKind::Undeclared);
+}